@@ -0,0 +1,169 @@
+//! Helpers for reading values out of an `Amf0Value` without having to match on
+//! every variant, and conversions used by the `amf0_object!` macro.
+
+use {Amf0Value, Amf0Properties};
+
+impl Amf0Value {
+    pub fn as_str(&self) -> Option<&str> {
+        match *self {
+            Amf0Value::Utf8String(ref value) => Some(value),
+            _ => None
+        }
+    }
+
+    pub fn as_f64(&self) -> Option<f64> {
+        match *self {
+            Amf0Value::Number(value) => Some(value),
+            _ => None
+        }
+    }
+
+    pub fn as_bool(&self) -> Option<bool> {
+        match *self {
+            Amf0Value::Boolean(value) => Some(value),
+            _ => None
+        }
+    }
+
+    /// Returns the properties of objects, ECMA arrays and typed objects
+    pub fn as_object(&self) -> Option<&Amf0Properties> {
+        match *self {
+            Amf0Value::Object(ref properties) => Some(properties),
+            Amf0Value::EcmaArray(ref properties) => Some(properties),
+            Amf0Value::TypedObject { ref properties, .. } => Some(properties),
+            _ => None
+        }
+    }
+
+    /// Takes the properties out of objects, ECMA arrays and typed objects
+    pub fn into_object(self) -> Option<Amf0Properties> {
+        match self {
+            Amf0Value::Object(properties) => Some(properties),
+            Amf0Value::EcmaArray(properties) => Some(properties),
+            Amf0Value::TypedObject { properties, .. } => Some(properties),
+            _ => None
+        }
+    }
+
+    /// Gets a property by name if this value has properties
+    pub fn get(&self, key: &str) -> Option<&Amf0Value> {
+        match self.as_object() {
+            Some(properties) => properties.get(key),
+            None => None
+        }
+    }
+
+    /// Follows a dot separated path of property names (e.g. `"info.code"`).
+    /// Numeric path segments index into strict arrays.
+    pub fn get_path(&self, path: &str) -> Option<&Amf0Value> {
+        let mut current = self;
+        for segment in path.split('.') {
+            let next = match *current {
+                Amf0Value::StrictArray(ref values) => match segment.parse::<usize>() {
+                    Ok(index) => values.get(index),
+                    Err(_) => None
+                },
+
+                _ => current.get(segment)
+            };
+
+            current = match next {
+                Some(value) => value,
+                None => return None
+            };
+        }
+
+        Some(current)
+    }
+}
+
+impl From<f64> for Amf0Value {
+    fn from(value: f64) -> Amf0Value {
+        Amf0Value::Number(value)
+    }
+}
+
+impl From<i32> for Amf0Value {
+    fn from(value: i32) -> Amf0Value {
+        Amf0Value::Number(value as f64)
+    }
+}
+
+impl From<u32> for Amf0Value {
+    fn from(value: u32) -> Amf0Value {
+        Amf0Value::Number(value as f64)
+    }
+}
+
+impl From<bool> for Amf0Value {
+    fn from(value: bool) -> Amf0Value {
+        Amf0Value::Boolean(value)
+    }
+}
+
+impl<'a> From<&'a str> for Amf0Value {
+    fn from(value: &'a str) -> Amf0Value {
+        Amf0Value::Utf8String(value.to_string())
+    }
+}
+
+impl From<String> for Amf0Value {
+    fn from(value: String) -> Amf0Value {
+        Amf0Value::Utf8String(value)
+    }
+}
+
+impl From<Amf0Properties> for Amf0Value {
+    fn from(value: Amf0Properties) -> Amf0Value {
+        Amf0Value::Object(value)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use {Amf0Value, Amf0Properties};
+
+    #[test]
+    fn can_get_nested_values_by_path() {
+        let value = amf0_object!{
+            "level" => "status",
+            "info" => amf0_object!{
+                "code" => "NetStream.Play.Start",
+                "codecs" => Amf0Value::StrictArray(vec![Amf0Value::from("avc1")]),
+            },
+        };
+
+        assert_eq!(value.get("level").and_then(|x| x.as_str()), Some("status"));
+        assert_eq!(value.get_path("info.code").and_then(|x| x.as_str()), Some("NetStream.Play.Start"));
+        assert_eq!(value.get_path("info.codecs.0").and_then(|x| x.as_str()), Some("avc1"));
+        assert_eq!(value.get_path("info.missing"), None);
+        assert_eq!(value.get_path("level.code"), None);
+    }
+
+    #[test]
+    fn macro_keeps_property_order() {
+        let value = amf0_object!{
+            "fmsVer" => "FMS/3,0,1,123",
+            "capabilities" => 31,
+            "fpad" => false,
+        };
+
+        let mut expected = Amf0Properties::new();
+        expected.insert("fmsVer".to_string(), Amf0Value::Utf8String("FMS/3,0,1,123".to_string()));
+        expected.insert("capabilities".to_string(), Amf0Value::Number(31.0));
+        expected.insert("fpad".to_string(), Amf0Value::Boolean(false));
+
+        assert_eq!(value, Amf0Value::Object(expected));
+    }
+
+    #[test]
+    fn typed_accessors_return_none_for_other_types() {
+        let value = Amf0Value::Number(5.0);
+
+        assert_eq!(value.as_f64(), Some(5.0));
+        assert_eq!(value.as_str(), None);
+        assert_eq!(value.as_bool(), None);
+        assert_eq!(value.get("a"), None);
+        assert_eq!(value.into_object(), None);
+    }
+}
@@ -0,0 +1,336 @@
+//! Serde deserializer that reads any `Deserialize` type out of an `Amf0Value` tree.
+//!
+//! Objects, ECMA arrays and typed objects are all exposed as maps, strict arrays
+//! as sequences, and both null and undefined as unit (or `None` for options).
+//! Dates are exposed as their unix time in milliseconds.
+
+use std::io::Read;
+use std::vec;
+use serde::de;
+use serde::de::{Visitor, DeserializeOwned, IntoDeserializer};
+use Amf0Value;
+use errors::Amf0SerdeError;
+use deserialization::deserialize_value;
+
+/// Converts an `Amf0Value` into any deserializable type
+pub fn from_amf0<T: DeserializeOwned>(value: Amf0Value) -> Result<T, Amf0SerdeError> {
+    T::deserialize(value)
+}
+
+/// Reads the next AMF0 value from the bytes and converts it into a deserializable
+/// type.  Any bytes after the value are left unread.
+pub fn from_amf0_bytes<T: DeserializeOwned>(bytes: &mut Read) -> Result<T, Amf0SerdeError> {
+    match try!(deserialize_value(bytes)) {
+        Some(value) => from_amf0(value),
+        None => Err(Amf0SerdeError::Message("No AMF0 values found".to_string()))
+    }
+}
+
+impl de::Error for Amf0SerdeError {
+    fn custom<T: ::std::fmt::Display>(message: T) -> Self {
+        Amf0SerdeError::Message(message.to_string())
+    }
+}
+
+impl<'de> IntoDeserializer<'de, Amf0SerdeError> for Amf0Value {
+    type Deserializer = Amf0Value;
+
+    fn into_deserializer(self) -> Amf0Value {
+        self
+    }
+}
+
+impl<'de> de::Deserializer<'de> for Amf0Value {
+    type Error = Amf0SerdeError;
+
+    fn deserialize_any<V: Visitor<'de>>(self, visitor: V) -> Result<V::Value, Amf0SerdeError> {
+        match self {
+            Amf0Value::Number(number) => visit_number(number, visitor),
+            Amf0Value::Boolean(value) => visitor.visit_bool(value),
+            Amf0Value::Utf8String(value) => visitor.visit_string(value),
+            Amf0Value::Null => visitor.visit_unit(),
+            Amf0Value::Undefined => visitor.visit_unit(),
+            Amf0Value::Date { unix_time, time_zone: _ } => visitor.visit_f64(unix_time),
+            Amf0Value::StrictArray(values) => visitor.visit_seq(SeqAccess { values: values.into_iter() }),
+
+            Amf0Value::Object(properties)
+            | Amf0Value::EcmaArray(properties)
+            | Amf0Value::TypedObject { class_name: _, properties } => {
+                visitor.visit_map(MapAccess { properties: properties.into_iter(), next_value: None })
+            },
+
+            Amf0Value::Reference(_) => Err(Amf0SerdeError::UnsupportedValue("reference")),
+            Amf0Value::AvmPlus(_) => Err(Amf0SerdeError::UnsupportedValue("AVM+")),
+        }
+    }
+
+    fn deserialize_option<V: Visitor<'de>>(self, visitor: V) -> Result<V::Value, Amf0SerdeError> {
+        match self {
+            Amf0Value::Null | Amf0Value::Undefined => visitor.visit_none(),
+            value => visitor.visit_some(value),
+        }
+    }
+
+    fn deserialize_newtype_struct<V: Visitor<'de>>(self, _name: &'static str, visitor: V) -> Result<V::Value, Amf0SerdeError> {
+        visitor.visit_newtype_struct(self)
+    }
+
+    fn deserialize_enum<V: Visitor<'de>>(self,
+                                         _name: &'static str,
+                                         _variants: &'static [&'static str],
+                                         visitor: V) -> Result<V::Value, Amf0SerdeError> {
+        match self {
+            // Unit variants are written as just their name
+            Amf0Value::Utf8String(variant) => visitor.visit_enum(variant.into_deserializer()),
+
+            // Variants with data are written as an object with a single property
+            Amf0Value::Object(properties) => {
+                if properties.len() != 1 {
+                    return Err(Amf0SerdeError::Message("Enum objects must have exactly one property".to_string()));
+                }
+
+                let (variant, value) = properties.into_iter().next().unwrap();
+                visitor.visit_enum(EnumAccess { variant: variant, value: value })
+            },
+
+            _ => Err(Amf0SerdeError::Message("Enums must be a string or an object".to_string())),
+        }
+    }
+
+    forward_to_deserialize_any! {
+        bool i8 i16 i32 i64 i128 u8 u16 u32 u64 u128 f32 f64 char str string
+        bytes byte_buf unit unit_struct seq tuple tuple_struct map struct
+        identifier ignored_any
+    }
+}
+
+fn visit_number<'de, V: Visitor<'de>>(number: f64, visitor: V) -> Result<V::Value, Amf0SerdeError> {
+    // All AMF0 numbers are doubles, but hand whole numbers to the visitor as
+    // integers so they can be deserialized into integer fields
+    if number.fract() == 0.0 {
+        if number >= 0.0 && number <= ::std::u64::MAX as f64 {
+            return visitor.visit_u64(number as u64);
+        }
+
+        if number < 0.0 && number >= ::std::i64::MIN as f64 {
+            return visitor.visit_i64(number as i64);
+        }
+    }
+
+    visitor.visit_f64(number)
+}
+
+struct SeqAccess {
+    values: vec::IntoIter<Amf0Value>
+}
+
+impl<'de> de::SeqAccess<'de> for SeqAccess {
+    type Error = Amf0SerdeError;
+
+    fn next_element_seed<T: de::DeserializeSeed<'de>>(&mut self, seed: T) -> Result<Option<T::Value>, Amf0SerdeError> {
+        match self.values.next() {
+            Some(value) => seed.deserialize(value).map(Some),
+            None => Ok(None),
+        }
+    }
+
+    fn size_hint(&self) -> Option<usize> {
+        Some(self.values.len())
+    }
+}
+
+struct MapAccess {
+    properties: vec::IntoIter<(String, Amf0Value)>,
+    next_value: Option<Amf0Value>
+}
+
+impl<'de> de::MapAccess<'de> for MapAccess {
+    type Error = Amf0SerdeError;
+
+    fn next_key_seed<K: de::DeserializeSeed<'de>>(&mut self, seed: K) -> Result<Option<K::Value>, Amf0SerdeError> {
+        match self.properties.next() {
+            Some((name, value)) => {
+                self.next_value = Some(value);
+                seed.deserialize(Amf0Value::Utf8String(name)).map(Some)
+            },
+
+            None => Ok(None),
+        }
+    }
+
+    fn next_value_seed<V: de::DeserializeSeed<'de>>(&mut self, seed: V) -> Result<V::Value, Amf0SerdeError> {
+        match self.next_value.take() {
+            Some(value) => seed.deserialize(value),
+            None => Err(Amf0SerdeError::Message("Map value requested before its key".to_string())),
+        }
+    }
+
+    fn size_hint(&self) -> Option<usize> {
+        Some(self.properties.len())
+    }
+}
+
+struct EnumAccess {
+    variant: String,
+    value: Amf0Value
+}
+
+impl<'de> de::EnumAccess<'de> for EnumAccess {
+    type Error = Amf0SerdeError;
+    type Variant = Amf0Value;
+
+    fn variant_seed<V: de::DeserializeSeed<'de>>(self, seed: V) -> Result<(V::Value, Amf0Value), Amf0SerdeError> {
+        let variant = try!(seed.deserialize(Amf0Value::Utf8String(self.variant)));
+        Ok((variant, self.value))
+    }
+}
+
+impl<'de> de::VariantAccess<'de> for Amf0Value {
+    type Error = Amf0SerdeError;
+
+    fn unit_variant(self) -> Result<(), Amf0SerdeError> {
+        de::Deserialize::deserialize(self)
+    }
+
+    fn newtype_variant_seed<T: de::DeserializeSeed<'de>>(self, seed: T) -> Result<T::Value, Amf0SerdeError> {
+        seed.deserialize(self)
+    }
+
+    fn tuple_variant<V: Visitor<'de>>(self, _length: usize, visitor: V) -> Result<V::Value, Amf0SerdeError> {
+        de::Deserializer::deserialize_seq(self, visitor)
+    }
+
+    fn struct_variant<V: Visitor<'de>>(self, _fields: &'static [&'static str], visitor: V) -> Result<V::Value, Amf0SerdeError> {
+        de::Deserializer::deserialize_map(self, visitor)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::io::Cursor;
+    use std::collections::HashMap;
+    use {Amf0Value, Amf0Properties};
+    use super::{from_amf0, from_amf0_bytes};
+    use ser::{to_amf0, to_amf0_bytes};
+
+    #[derive(Serialize, Deserialize, PartialEq, Debug)]
+    #[serde(rename_all = "camelCase")]
+    struct ConnectCommand {
+        app: String,
+        flash_ver: String,
+        tc_url: Option<String>,
+        fpad: bool,
+        audio_codecs: u32,
+        object_encoding: f64,
+    }
+
+    #[derive(Serialize, Deserialize, PartialEq, Debug)]
+    enum Level {
+        Status,
+        Error { code: String },
+    }
+
+    #[test]
+    fn struct_is_serialized_as_object_in_field_order() {
+        let command = ConnectCommand {
+            app: "live".to_string(),
+            flash_ver: "FMLE/3.0".to_string(),
+            tc_url: None,
+            fpad: false,
+            audio_codecs: 3575,
+            object_encoding: 0.0,
+        };
+
+        let mut expected = Amf0Properties::new();
+        expected.insert("app".to_string(), Amf0Value::Utf8String("live".to_string()));
+        expected.insert("flashVer".to_string(), Amf0Value::Utf8String("FMLE/3.0".to_string()));
+        expected.insert("tcUrl".to_string(), Amf0Value::Null);
+        expected.insert("fpad".to_string(), Amf0Value::Boolean(false));
+        expected.insert("audioCodecs".to_string(), Amf0Value::Number(3575.0));
+        expected.insert("objectEncoding".to_string(), Amf0Value::Number(0.0));
+
+        let result = to_amf0(&command).unwrap();
+        assert_eq!(result, Amf0Value::Object(expected));
+    }
+
+    #[test]
+    fn struct_round_trips_through_bytes() {
+        let command = ConnectCommand {
+            app: "live".to_string(),
+            flash_ver: "FMLE/3.0".to_string(),
+            tc_url: Some("rtmp://localhost/live".to_string()),
+            fpad: true,
+            audio_codecs: 3575,
+            object_encoding: 0.0,
+        };
+
+        let bytes = to_amf0_bytes(&command).unwrap();
+        let result: ConnectCommand = from_amf0_bytes(&mut Cursor::new(bytes)).unwrap();
+        assert_eq!(result, command);
+    }
+
+    #[test]
+    fn only_first_value_is_read_from_bytes() {
+        let mut bytes = to_amf0_bytes(&"live".to_string()).unwrap();
+        let value_length = bytes.len() as u64;
+        bytes.extend(&[0xFF, 0x00, 0x13]);
+
+        let mut cursor = Cursor::new(bytes);
+        let result: String = from_amf0_bytes(&mut cursor).unwrap();
+
+        assert_eq!(result, "live");
+        assert_eq!(cursor.position(), value_length);
+    }
+
+    #[test]
+    fn map_is_serialized_as_ecma_array() {
+        let mut map = HashMap::new();
+        map.insert("duration".to_string(), 12.5);
+
+        let mut expected = Amf0Properties::new();
+        expected.insert("duration".to_string(), Amf0Value::Number(12.5));
+
+        let result = to_amf0(&map).unwrap();
+        assert_eq!(result, Amf0Value::EcmaArray(expected));
+
+        let deserialized: HashMap<String, f64> = from_amf0(result).unwrap();
+        assert_eq!(deserialized, map);
+    }
+
+    #[test]
+    fn sequence_round_trips_as_strict_array() {
+        let values = vec![1u8, 2, 3];
+        let result = to_amf0(&values).unwrap();
+        assert_eq!(result, Amf0Value::StrictArray(vec![
+            Amf0Value::Number(1.0),
+            Amf0Value::Number(2.0),
+            Amf0Value::Number(3.0),
+        ]));
+
+        let deserialized: Vec<u8> = from_amf0(result).unwrap();
+        assert_eq!(deserialized, values);
+    }
+
+    #[test]
+    fn enums_round_trip() {
+        let unit = to_amf0(&Level::Status).unwrap();
+        assert_eq!(unit, Amf0Value::Utf8String("Status".to_string()));
+        assert_eq!(from_amf0::<Level>(unit).unwrap(), Level::Status);
+
+        let error = Level::Error { code: "NetConnection.Connect.Rejected".to_string() };
+        let value = to_amf0(&error).unwrap();
+        assert_eq!(from_amf0::<Level>(value).unwrap(), error);
+    }
+
+    #[test]
+    fn undefined_is_deserialized_as_none() {
+        let result: Option<String> = from_amf0(Amf0Value::Undefined).unwrap();
+        assert_eq!(result, None);
+    }
+
+    #[test]
+    fn references_can_not_be_deserialized() {
+        let result = from_amf0::<String>(Amf0Value::Reference(1));
+        assert!(result.is_err());
+    }
+}
@@ -0,0 +1,899 @@
+//! This module contains a borrowing AMF0 decoder that works directly on a byte
+//! slice.  Decoded values borrow their strings from the input, so callers can
+//! inspect a message payload (e.g. to find a command name or a single metadata
+//! property) without copying every string.
+//!
+//! AMF0 objects and arrays do not encode their length in bytes, so each top level
+//! value is walked once to validate it against the decode limits and find where
+//! it ends.  That walk records every object property and array value in a flat
+//! index shared by all of the value's objects and arrays, so iterating over them
+//! later reads the index instead of decoding the bytes again.
+
+use std::borrow::Cow;
+use std::fmt;
+use std::io::Cursor;
+use std::rc::Rc;
+use std::str;
+use byteorder::{BigEndian, ByteOrder};
+use amf3;
+use amf3::Amf3Value;
+use super::{Amf0Value, Amf0Properties};
+use super::errors::{Amf0DeserializationError, Amf0ErrorLocation, PathSegment, get_error_location};
+use deserialization::is_skippable_marker;
+use limits::{Amf0DecodeLimits, get_amf3_limits, convert_amf3_error};
+use markers;
+
+/// AMF0 value that borrows from the bytes it was decoded from
+#[derive(PartialEq, Debug, Clone)]
+pub enum Amf0ValueRef<'a> {
+    Number(f64),
+    Boolean(bool),
+    Utf8String(Cow<'a, str>),
+    Object(Amf0ObjectRef<'a>),
+    Null,
+    EcmaArray(Amf0ObjectRef<'a>),
+    StrictArray(Amf0ArrayRef<'a>),
+    Date { unix_time: f64, time_zone: i16 },
+    Undefined,
+    TypedObject { class_name: Cow<'a, str>, properties: Amf0ObjectRef<'a> },
+    Reference(u16),
+
+    /// AMF3 values have no borrowed representation, so they are decoded into
+    /// owned values
+    AvmPlus(Amf3Value),
+}
+
+/// Properties of an object, ECMA array or typed object
+#[derive(Clone)]
+pub struct Amf0ObjectRef<'a> {
+    entries: EntrySlice<'a>
+}
+
+/// Values of a strict array
+#[derive(Clone)]
+pub struct Amf0ArrayRef<'a> {
+    entries: EntrySlice<'a>
+}
+
+/// A property or array value recorded while validating a top level value.
+/// Array values have an empty name.
+struct Entry<'a> {
+    name: Cow<'a, str>,
+    value: IndexedValue<'a>
+}
+
+/// A value in the index.  Objects and arrays refer to the range of the index
+/// holding their entries.
+enum IndexedValue<'a> {
+    Value(Amf0ValueRef<'a>),
+    Object { first: usize, count: usize },
+    EcmaArray { first: usize, count: usize },
+    StrictArray { first: usize, count: usize },
+    TypedObject { class_name: Cow<'a, str>, first: usize, count: usize },
+}
+
+/// The entries of one object or array within a shared index
+#[derive(Clone)]
+struct EntrySlice<'a> {
+    index: Rc<Vec<Entry<'a>>>,
+    first: usize,
+    count: usize
+}
+
+/// Decodes AMF0 values one at a time from a byte slice, keeping track of how
+/// many bytes have been consumed.
+///
+/// # Examples
+/// ```
+/// use amf0::{Amf0Value, Amf0Decoder, Amf0ValueRef, serialize};
+///
+/// let bytes = serialize(&vec![Amf0Value::Utf8String("connect".to_string()), Amf0Value::Number(1.0)]).unwrap();
+///
+/// let mut decoder = Amf0Decoder::new(&bytes);
+/// match decoder.decode_next().unwrap() {
+///     Some(Amf0ValueRef::Utf8String(name)) => assert_eq!(name, "connect"),
+///     x => panic!("Unexpected value: {:?}", x)
+/// }
+///
+/// assert_eq!(decoder.bytes_consumed(), 10);
+/// ```
+pub struct Amf0Decoder<'a> {
+    reader: Reader<'a>,
+    limits: Amf0DecodeLimits,
+    total_values: usize,
+    values_decoded: usize,
+    error_location: Option<Amf0ErrorLocation>
+}
+
+impl<'a> Amf0Decoder<'a> {
+    /// Creates a decoder that enforces the default `Amf0DecodeLimits`
+    pub fn new(bytes: &'a [u8]) -> Amf0Decoder<'a> {
+        Amf0Decoder::with_limits(bytes, Amf0DecodeLimits::default())
+    }
+
+    /// Creates a decoder that fails as soon as the input goes over one of the
+    /// specified limits.  Value counts are shared by all values decoded.
+    pub fn with_limits(bytes: &'a [u8], limits: Amf0DecodeLimits) -> Amf0Decoder<'a> {
+        Amf0Decoder {
+            reader: Reader { bytes: bytes, position: 0 },
+            limits: limits,
+            total_values: 0,
+            values_decoded: 0,
+            error_location: None
+        }
+    }
+
+    /// Decodes the next value.  `None` is returned once all bytes have been
+    /// consumed (or an object end marker is found where a value was expected).
+    pub fn decode_next(&mut self) -> Result<Option<Amf0ValueRef<'a>>, Amf0DeserializationError> {
+        self.error_location = None;
+        if self.reader.is_empty() {
+            return Ok(None);
+        }
+
+        let marker = try!(self.reader.read_u8());
+        if marker == markers::OBJECT_END_MARKER {
+            return Ok(None);
+        }
+
+        let (result, entries) = {
+            let mut indexer = Indexer {
+                reader: &mut self.reader,
+                limits: &self.limits,
+                depth: 0,
+                total_values: &mut self.total_values,
+                path: vec![PathSegment::Index(self.values_decoded)],
+                entries: Vec::new()
+            };
+
+            let result = match indexer.read_value(marker) {
+                Ok(value) => Ok(value),
+                Err(err) => {
+                    let location = get_error_location(&err, indexer.reader.position, &indexer.path);
+                    Err((err, location))
+                }
+            };
+
+            (result, indexer.entries)
+        };
+
+        match result {
+            Ok(value) => {
+                self.values_decoded = self.values_decoded + 1;
+                Ok(Some(value.to_value_ref(&Rc::new(entries))))
+            },
+
+            Err((err, location)) => {
+                self.error_location = Some(location);
+                Err(err)
+            }
+        }
+    }
+
+    /// Decodes all remaining values
+    pub fn decode_all(&mut self) -> Result<Vec<Amf0ValueRef<'a>>, Amf0DeserializationError> {
+        let mut results = Vec::new();
+        while let Some(value) = try!(self.decode_next()) {
+            results.push(value);
+        }
+
+        Ok(results)
+    }
+
+    /// Number of bytes from the start of the input that have been decoded
+    pub fn bytes_consumed(&self) -> usize {
+        self.reader.position
+    }
+
+    /// Where the error returned by the last call to `decode_next` happened, with
+    /// the same offset and path `deserialize_located` would report
+    pub fn error_location(&self) -> Option<&Amf0ErrorLocation> {
+        self.error_location.as_ref()
+    }
+}
+
+impl<'a> Amf0ValueRef<'a> {
+    /// Creates an owned copy of the value, including all nested properties
+    pub fn into_owned(self) -> Amf0Value {
+        match self {
+            Amf0ValueRef::Number(x) => Amf0Value::Number(x),
+            Amf0ValueRef::Boolean(x) => Amf0Value::Boolean(x),
+            Amf0ValueRef::Utf8String(x) => Amf0Value::Utf8String(x.into_owned()),
+            Amf0ValueRef::Object(x) => Amf0Value::Object(x.into_owned()),
+            Amf0ValueRef::Null => Amf0Value::Null,
+            Amf0ValueRef::EcmaArray(x) => Amf0Value::EcmaArray(x.into_owned()),
+            Amf0ValueRef::StrictArray(x) => Amf0Value::StrictArray(x.iter().map(|value| value.into_owned()).collect()),
+            Amf0ValueRef::Date { unix_time, time_zone } => Amf0Value::Date { unix_time: unix_time, time_zone: time_zone },
+            Amf0ValueRef::Undefined => Amf0Value::Undefined,
+            Amf0ValueRef::TypedObject { class_name, properties } => Amf0Value::TypedObject {
+                class_name: class_name.into_owned(),
+                properties: properties.into_owned()
+            },
+
+            Amf0ValueRef::Reference(x) => Amf0Value::Reference(x),
+            Amf0ValueRef::AvmPlus(x) => Amf0Value::AvmPlus(x),
+        }
+    }
+}
+
+impl<'a> Amf0ObjectRef<'a> {
+    pub fn len(&self) -> usize {
+        self.entries.count
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.entries.count == 0
+    }
+
+    /// Iterates over the properties in the order they were encoded
+    pub fn iter(&self) -> Amf0PropertyIter<'a> {
+        Amf0PropertyIter { entries: self.entries.clone(), position: 0 }
+    }
+
+    /// Finds the first property with the specified name
+    pub fn get(&self, name: &str) -> Option<Amf0ValueRef<'a>> {
+        self.iter()
+            .find(|&(ref property_name, _)| property_name == name)
+            .map(|(_, value)| value)
+    }
+
+    fn into_owned(self) -> Amf0Properties {
+        let mut properties = Amf0Properties::new();
+        for (name, value) in self.iter() {
+            properties.insert(name.into_owned(), value.into_owned());
+        }
+
+        properties
+    }
+}
+
+impl<'a> PartialEq for Amf0ObjectRef<'a> {
+    fn eq(&self, other: &Amf0ObjectRef<'a>) -> bool {
+        self.len() == other.len() && self.iter().zip(other.iter()).all(|(x, y)| x == y)
+    }
+}
+
+impl<'a> fmt::Debug for Amf0ObjectRef<'a> {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.debug_list().entries(self.iter()).finish()
+    }
+}
+
+impl<'a> Amf0ArrayRef<'a> {
+    pub fn len(&self) -> usize {
+        self.entries.count
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.entries.count == 0
+    }
+
+    pub fn iter(&self) -> Amf0ArrayIter<'a> {
+        Amf0ArrayIter { entries: self.entries.clone(), position: 0 }
+    }
+}
+
+impl<'a> PartialEq for Amf0ArrayRef<'a> {
+    fn eq(&self, other: &Amf0ArrayRef<'a>) -> bool {
+        self.len() == other.len() && self.iter().zip(other.iter()).all(|(x, y)| x == y)
+    }
+}
+
+impl<'a> fmt::Debug for Amf0ArrayRef<'a> {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.debug_list().entries(self.iter()).finish()
+    }
+}
+
+pub struct Amf0PropertyIter<'a> {
+    entries: EntrySlice<'a>,
+    position: usize
+}
+
+impl<'a> Iterator for Amf0PropertyIter<'a> {
+    type Item = (Cow<'a, str>, Amf0ValueRef<'a>);
+
+    fn next(&mut self) -> Option<Self::Item> {
+        if self.position >= self.entries.count {
+            return None;
+        }
+
+        let entry = &self.entries.index[self.entries.first + self.position];
+        self.position = self.position + 1;
+        Some((entry.name.clone(), entry.value.to_value_ref(&self.entries.index)))
+    }
+}
+
+pub struct Amf0ArrayIter<'a> {
+    entries: EntrySlice<'a>,
+    position: usize
+}
+
+impl<'a> Iterator for Amf0ArrayIter<'a> {
+    type Item = Amf0ValueRef<'a>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        if self.position >= self.entries.count {
+            return None;
+        }
+
+        let entry = &self.entries.index[self.entries.first + self.position];
+        self.position = self.position + 1;
+        Some(entry.value.to_value_ref(&self.entries.index))
+    }
+}
+
+impl<'a> IndexedValue<'a> {
+    fn to_value_ref(&self, index: &Rc<Vec<Entry<'a>>>) -> Amf0ValueRef<'a> {
+        let slice = |first: usize, count: usize| EntrySlice { index: index.clone(), first: first, count: count };
+
+        match *self {
+            IndexedValue::Value(ref value) => value.clone(),
+            IndexedValue::Object { first, count } => Amf0ValueRef::Object(Amf0ObjectRef { entries: slice(first, count) }),
+            IndexedValue::EcmaArray { first, count } => Amf0ValueRef::EcmaArray(Amf0ObjectRef { entries: slice(first, count) }),
+            IndexedValue::StrictArray { first, count } => Amf0ValueRef::StrictArray(Amf0ArrayRef { entries: slice(first, count) }),
+            IndexedValue::TypedObject { ref class_name, first, count } => Amf0ValueRef::TypedObject {
+                class_name: class_name.clone(),
+                properties: Amf0ObjectRef { entries: slice(first, count) }
+            },
+        }
+    }
+}
+
+struct Reader<'a> {
+    bytes: &'a [u8],
+    position: usize
+}
+
+impl<'a> Reader<'a> {
+    fn is_empty(&self) -> bool {
+        self.position >= self.bytes.len()
+    }
+
+    fn read_slice(&mut self, length: usize) -> Result<&'a [u8], Amf0DeserializationError> {
+        if self.bytes.len() - self.position < length {
+            return Err(Amf0DeserializationError::UnexpectedEof);
+        }
+
+        let slice = &self.bytes[self.position..self.position + length];
+        self.position = self.position + length;
+        Ok(slice)
+    }
+
+    fn read_u8(&mut self) -> Result<u8, Amf0DeserializationError> {
+        let slice = try!(self.read_slice(1));
+        Ok(slice[0])
+    }
+
+    fn read_u16(&mut self) -> Result<u16, Amf0DeserializationError> {
+        let slice = try!(self.read_slice(2));
+        Ok(BigEndian::read_u16(slice))
+    }
+
+    fn read_i16(&mut self) -> Result<i16, Amf0DeserializationError> {
+        let slice = try!(self.read_slice(2));
+        Ok(BigEndian::read_i16(slice))
+    }
+
+    fn read_u32(&mut self) -> Result<u32, Amf0DeserializationError> {
+        let slice = try!(self.read_slice(4));
+        Ok(BigEndian::read_u32(slice))
+    }
+
+    fn read_f64(&mut self) -> Result<f64, Amf0DeserializationError> {
+        let slice = try!(self.read_slice(8));
+        Ok(BigEndian::read_f64(slice))
+    }
+
+    fn read_utf8(&mut self, length: usize) -> Result<Cow<'a, str>, Amf0DeserializationError> {
+        let slice = try!(self.read_slice(length));
+        match str::from_utf8(slice) {
+            Ok(value) => Ok(Cow::Borrowed(value)),
+
+            // Run the owned conversion to get an error of the same type the
+            // owned deserializer returns
+            Err(_) => match String::from_utf8(slice.to_vec()) {
+                Ok(value) => Ok(Cow::Owned(value)),
+                Err(err) => Err(Amf0DeserializationError::from(err))
+            }
+        }
+    }
+}
+
+/// Walks a top level value, enforcing the decode limits and recording the
+/// entries of every object and array in it
+struct Indexer<'a: 'r, 'r> {
+    reader: &'r mut Reader<'a>,
+    limits: &'r Amf0DecodeLimits,
+    depth: usize,
+    total_values: &'r mut usize,
+    path: Vec<PathSegment<'a>>,
+    entries: Vec<Entry<'a>>
+}
+
+impl<'a, 'r> Indexer<'a, 'r> {
+    fn read_value(&mut self, marker: u8) -> Result<IndexedValue<'a>, Amf0DeserializationError> {
+        if *self.total_values >= self.limits.max_total_values {
+            return Err(Amf0DeserializationError::TooManyValues(self.limits.max_total_values));
+        }
+
+        *self.total_values = *self.total_values + 1;
+
+        let value = match marker {
+            markers::NUMBER_MARKER => Amf0ValueRef::Number(try!(self.reader.read_f64())),
+            markers::BOOLEAN_MARKER => Amf0ValueRef::Boolean(try!(self.reader.read_u8()) == 1),
+            markers::STRING_MARKER => {
+                let length = try!(self.reader.read_u16());
+                Amf0ValueRef::Utf8String(try!(self.read_utf8(length as usize)))
+            },
+
+            markers::LONG_STRING_MARKER => {
+                let length = try!(self.reader.read_u32());
+                Amf0ValueRef::Utf8String(try!(self.read_utf8(length as usize)))
+            },
+
+            markers::NULL_MARKER => Amf0ValueRef::Null,
+            markers::UNDEFINED_MARKER => Amf0ValueRef::Undefined,
+            markers::REFERENCE_MARKER => Amf0ValueRef::Reference(try!(self.reader.read_u16())),
+            markers::DATE_MARKER => {
+                let unix_time = try!(self.reader.read_f64());
+                let time_zone = try!(self.reader.read_i16());
+                Amf0ValueRef::Date { unix_time: unix_time, time_zone: time_zone }
+            },
+
+            markers::OBJECT_MARKER => {
+                let (first, count) = try!(self.read_properties());
+                return Ok(IndexedValue::Object { first: first, count: count });
+            },
+
+            markers::ECMA_ARRAY_MARKER => {
+                // The associative count is only a hint, the object end marker
+                // signals the end of the array
+                let _associative_count = try!(self.reader.read_u32());
+                let (first, count) = try!(self.read_properties());
+                return Ok(IndexedValue::EcmaArray { first: first, count: count });
+            },
+
+            markers::TYPED_OBJECT_MARKER => {
+                let length = try!(self.reader.read_u16());
+                let class_name = try!(self.read_utf8(length as usize));
+                let (first, count) = try!(self.read_properties());
+                return Ok(IndexedValue::TypedObject { class_name: class_name, first: first, count: count });
+            },
+
+            markers::STRICT_ARRAY_MARKER => {
+                let (first, count) = try!(self.read_strict_array());
+                return Ok(IndexedValue::StrictArray { first: first, count: count });
+            },
+
+            markers::AVMPLUS_OBJECT_MARKER => Amf0ValueRef::AvmPlus(try!(self.read_avmplus())),
+            _ => return Err(Amf0DeserializationError::UnknownMarker(marker))
+        };
+
+        Ok(IndexedValue::Value(value))
+    }
+
+    fn read_utf8(&mut self, length: usize) -> Result<Cow<'a, str>, Amf0DeserializationError> {
+        if length > self.limits.max_string_length {
+            return Err(Amf0DeserializationError::StringTooLong { length: length, limit: self.limits.max_string_length });
+        }
+
+        self.reader.read_utf8(length)
+    }
+
+    fn read_avmplus(&mut self) -> Result<Amf3Value, Amf0DeserializationError> {
+        let amf3_limits = get_amf3_limits(self.limits, self.depth, *self.total_values);
+        let mut cursor = Cursor::new(&self.reader.bytes[self.reader.position..]);
+        let (value, value_count) = match amf3::deserialize_value_with_limits(&mut cursor, &amf3_limits) {
+            Ok(result) => result,
+            Err(err) => return Err(convert_amf3_error(err, self.limits))
+        };
+
+        self.reader.position = self.reader.position + cursor.position() as usize;
+        *self.total_values = *self.total_values + value_count - 1;
+        Ok(value)
+    }
+
+    fn skip_unknown_value(&mut self, marker: u8) -> Result<(), Amf0DeserializationError> {
+        if marker == markers::XML_DOCUMENT_MARKER {
+            let length = try!(self.reader.read_u32()) as usize;
+            if length > self.limits.max_string_length {
+                return Err(Amf0DeserializationError::StringTooLong { length: length, limit: self.limits.max_string_length });
+            }
+
+            try!(self.reader.read_slice(length));
+        }
+
+        Ok(())
+    }
+
+    fn enter_nested(&mut self) -> Result<(), Amf0DeserializationError> {
+        if self.depth >= self.limits.max_depth {
+            return Err(Amf0DeserializationError::MaxDepthExceeded(self.limits.max_depth));
+        }
+
+        self.depth = self.depth + 1;
+        Ok(())
+    }
+
+    /// Moves the entries of one object or array into the index after all of
+    /// their nested entries, so each object's entries are contiguous.  Returns
+    /// the range they were stored in.
+    fn store_entries(&mut self, entries: Vec<Entry<'a>>) -> (usize, usize) {
+        let first = self.entries.len();
+        let count = entries.len();
+        self.entries.extend(entries);
+        (first, count)
+    }
+
+    fn read_properties(&mut self) -> Result<(usize, usize), Amf0DeserializationError> {
+        try!(self.enter_nested());
+        let mut entries = Vec::new();
+
+        loop {
+            let label_length = try!(self.reader.read_u16());
+            if label_length == 0 {
+                let byte = try!(self.reader.read_u8());
+                if byte != markers::OBJECT_END_MARKER {
+                    return Err(Amf0DeserializationError::UnexpectedEmptyObjectPropertyName);
+                }
+
+                break;
+            }
+
+            if entries.len() >= self.limits.max_properties_per_object {
+                return Err(Amf0DeserializationError::TooManyProperties(self.limits.max_properties_per_object));
+            }
+
+            let label = try!(self.read_utf8(label_length as usize));
+            self.path.push(PathSegment::Property(label.clone()));
+
+            let marker = try!(self.reader.read_u8());
+            if marker == markers::OBJECT_END_MARKER {
+                return Err(Amf0DeserializationError::UnexpectedEof);
+            }
+
+            if self.limits.skip_unknown_values && is_skippable_marker(marker) {
+                // Drop the property but keep the rest of the object
+                try!(self.skip_unknown_value(marker));
+            } else {
+                let value = try!(self.read_value(marker));
+                entries.push(Entry { name: label, value: value });
+            }
+
+            self.path.pop();
+        }
+
+        self.depth = self.depth - 1;
+        Ok(self.store_entries(entries))
+    }
+
+    fn read_strict_array(&mut self) -> Result<(usize, usize), Amf0DeserializationError> {
+        let count = try!(self.reader.read_u32());
+        if count as usize > self.limits.max_properties_per_object {
+            return Err(Amf0DeserializationError::TooManyProperties(self.limits.max_properties_per_object));
+        }
+
+        try!(self.enter_nested());
+        let mut entries = Vec::new();
+        for index in 0..count {
+            self.path.push(PathSegment::Index(index as usize));
+            let marker = try!(self.reader.read_u8());
+            if marker == markers::OBJECT_END_MARKER {
+                return Err(Amf0DeserializationError::UnexpectedEof);
+            }
+
+            let value = try!(self.read_value(marker));
+            entries.push(Entry { name: Cow::Borrowed(""), value: value });
+            self.path.pop();
+        }
+
+        self.depth = self.depth - 1;
+        Ok(self.store_entries(entries))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::borrow::Cow;
+    use std::io::Cursor;
+    use super::{Amf0Decoder, Amf0ValueRef};
+    use limits::Amf0DecodeLimits;
+    use super::super::{Amf0Value, Amf0Properties, serialize, deserialize, deserialize_located};
+    use super::super::errors::{Amf0DeserializationError, Amf0ErrorLocation};
+    use amf3::Amf3Value;
+    use markers;
+    use byteorder::{BigEndian, WriteBytesExt};
+
+    #[test]
+    fn strings_borrow_from_input() {
+        let bytes = serialize(&vec![Amf0Value::Utf8String("test".to_string())]).unwrap();
+
+        let mut decoder = Amf0Decoder::new(&bytes);
+        let result = decoder.decode_next().unwrap();
+
+        match result {
+            Some(Amf0ValueRef::Utf8String(Cow::Borrowed(value))) => assert_eq!(value, "test"),
+            x => panic!("Expected borrowed string, instead got {:?}", x)
+        }
+    }
+
+    #[test]
+    fn reports_bytes_consumed_after_each_value() {
+        let bytes = serialize(&vec![Amf0Value::Number(1.0), Amf0Value::Null, Amf0Value::Boolean(true)]).unwrap();
+
+        let mut decoder = Amf0Decoder::new(&bytes);
+        decoder.decode_next().unwrap();
+        assert_eq!(decoder.bytes_consumed(), 9);
+
+        decoder.decode_next().unwrap();
+        assert_eq!(decoder.bytes_consumed(), 10);
+
+        decoder.decode_next().unwrap();
+        assert_eq!(decoder.bytes_consumed(), 12);
+
+        assert_eq!(decoder.decode_next().unwrap(), None);
+    }
+
+    #[test]
+    fn object_properties_are_iterated_in_wire_order() {
+        let mut properties = Amf0Properties::new();
+        properties.insert("b".to_string(), Amf0Value::Number(1.0));
+        properties.insert("a".to_string(), Amf0Value::Utf8String("value".to_string()));
+
+        let bytes = serialize(&vec![Amf0Value::Object(properties), Amf0Value::Null]).unwrap();
+
+        let mut decoder = Amf0Decoder::new(&bytes);
+        let object = match decoder.decode_next().unwrap() {
+            Some(Amf0ValueRef::Object(object)) => object,
+            x => panic!("Expected object, instead got {:?}", x)
+        };
+
+        let properties: Vec<(Cow<str>, Amf0ValueRef)> = object.iter().collect();
+        assert_eq!(properties, vec![
+            (Cow::Borrowed("b"), Amf0ValueRef::Number(1.0)),
+            (Cow::Borrowed("a"), Amf0ValueRef::Utf8String(Cow::Borrowed("value"))),
+        ]);
+
+        assert_eq!(object.get("a"), Some(Amf0ValueRef::Utf8String(Cow::Borrowed("value"))));
+        assert_eq!(object.get("c"), None);
+        assert_eq!(decoder.decode_next().unwrap(), Some(Amf0ValueRef::Null));
+    }
+
+    #[test]
+    fn can_decode_into_same_values_as_owned_deserializer() {
+        let mut inner = Amf0Properties::new();
+        inner.insert("videocodecid".to_string(), Amf0Value::Number(7.0));
+        inner.insert("list".to_string(), Amf0Value::StrictArray(vec![Amf0Value::Boolean(false), Amf0Value::Undefined]));
+
+        let mut typed = Amf0Properties::new();
+        typed.insert("x".to_string(), Amf0Value::Reference(1));
+
+        let input = vec![
+            Amf0Value::Utf8String("@setDataFrame".to_string()),
+            Amf0Value::EcmaArray(inner),
+            Amf0Value::TypedObject { class_name: "Class".to_string(), properties: typed },
+            Amf0Value::Date { unix_time: 5.0, time_zone: 0 },
+            Amf0Value::AvmPlus(Amf3Value::Integer(5)),
+        ];
+
+        let bytes = serialize(&input).unwrap();
+
+        let mut decoder = Amf0Decoder::new(&bytes);
+        let results: Vec<Amf0Value> = decoder.decode_all().unwrap()
+            .into_iter()
+            .map(|value| value.into_owned())
+            .collect();
+
+        let expected = deserialize(&mut Cursor::new(bytes.clone())).unwrap();
+        assert_eq!(results, expected);
+        assert_eq!(results, input);
+        assert_eq!(decoder.bytes_consumed(), bytes.len());
+    }
+
+    #[test]
+    fn error_when_object_is_truncated() {
+        let mut vector = vec![];
+        vector.push(markers::OBJECT_MARKER);
+        vector.write_u16::<BigEndian>(4).unwrap();
+        vector.extend("test".as_bytes());
+        vector.push(markers::NUMBER_MARKER);
+        vector.write_f64::<BigEndian>(1.0).unwrap();
+
+        let mut decoder = Amf0Decoder::new(&vector);
+        let result = decoder.decode_next();
+
+        assert!(match result {
+            Err(Amf0DeserializationError::UnexpectedEof) => true,
+            _ => false}
+        );
+    }
+
+    #[test]
+    fn error_on_unknown_marker() {
+        let vector = vec![0x0D];
+
+        let mut decoder = Amf0Decoder::new(&vector);
+        let result = decoder.decode_next();
+
+        assert!(match result {
+            Err(Amf0DeserializationError::UnknownMarker(0x0D)) => true,
+            _ => false}
+        );
+    }
+
+    #[test]
+    fn nested_values_are_iterated_from_index() {
+        let mut inner = Amf0Properties::new();
+        inner.insert("c".to_string(), Amf0Value::Number(3.0));
+
+        let mut outer = Amf0Properties::new();
+        outer.insert("a".to_string(), Amf0Value::StrictArray(vec![Amf0Value::Object(inner), Amf0Value::Null]));
+        outer.insert("b".to_string(), Amf0Value::Boolean(true));
+
+        let bytes = serialize(&vec![Amf0Value::Object(outer)]).unwrap();
+
+        let mut decoder = Amf0Decoder::new(&bytes);
+        let object = match decoder.decode_next().unwrap() {
+            Some(Amf0ValueRef::Object(object)) => object,
+            x => panic!("Expected object, instead got {:?}", x)
+        };
+
+        assert_eq!(object.len(), 2);
+        assert_eq!(object.get("b"), Some(Amf0ValueRef::Boolean(true)));
+
+        let array = match object.get("a") {
+            Some(Amf0ValueRef::StrictArray(array)) => array,
+            x => panic!("Expected strict array, instead got {:?}", x)
+        };
+
+        let values: Vec<Amf0ValueRef> = array.iter().collect();
+        assert_eq!(values.len(), 2);
+        assert_eq!(values[1], Amf0ValueRef::Null);
+        match values[0] {
+            Amf0ValueRef::Object(ref inner) => assert_eq!(inner.get("c"), Some(Amf0ValueRef::Number(3.0))),
+            ref x => panic!("Expected object, instead got {:?}", x)
+        }
+    }
+
+    #[test]
+    fn error_when_strict_arrays_are_deeply_nested() {
+        let mut vector = vec![];
+        for _ in 0..200000 {
+            vector.push(markers::STRICT_ARRAY_MARKER);
+            vector.write_u32::<BigEndian>(1).unwrap();
+        }
+
+        let mut decoder = Amf0Decoder::new(&vector);
+        let result = decoder.decode_next();
+
+        assert!(match result {
+            Err(Amf0DeserializationError::MaxDepthExceeded(32)) => true,
+            _ => false}
+        );
+    }
+
+    #[test]
+    fn error_when_string_is_over_limit() {
+        let mut vector = vec![];
+        vector.push(markers::LONG_STRING_MARKER);
+        vector.write_u32::<BigEndian>(0xFFFFFFFF).unwrap();
+
+        let limits = Amf0DecodeLimits { max_string_length: 100, .. Amf0DecodeLimits::default() };
+        let mut decoder = Amf0Decoder::with_limits(&vector, limits);
+        let result = decoder.decode_next();
+
+        assert!(match result {
+            Err(Amf0DeserializationError::StringTooLong { length: 0xFFFFFFFF, limit: 100 }) => true,
+            _ => false}
+        );
+    }
+
+    #[test]
+    fn error_when_object_has_too_many_properties() {
+        let mut properties = Amf0Properties::new();
+        properties.insert("a".to_string(), Amf0Value::Null);
+        properties.insert("b".to_string(), Amf0Value::Null);
+        properties.insert("c".to_string(), Amf0Value::Null);
+
+        let bytes = serialize(&vec![Amf0Value::Object(properties)]).unwrap();
+        let limits = Amf0DecodeLimits { max_properties_per_object: 2, .. Amf0DecodeLimits::default() };
+        let mut decoder = Amf0Decoder::with_limits(&bytes, limits);
+        let result = decoder.decode_next();
+
+        assert!(match result {
+            Err(Amf0DeserializationError::TooManyProperties(2)) => true,
+            _ => false}
+        );
+    }
+
+    #[test]
+    fn total_values_are_counted_across_decoded_values() {
+        let bytes = serialize(&vec![Amf0Value::Null, Amf0Value::StrictArray(vec![Amf0Value::Null]), Amf0Value::Null]).unwrap();
+        let limits = Amf0DecodeLimits { max_total_values: 3, .. Amf0DecodeLimits::default() };
+        let mut decoder = Amf0Decoder::with_limits(&bytes, limits);
+
+        assert_eq!(decoder.decode_next().unwrap(), Some(Amf0ValueRef::Null));
+        assert!(decoder.decode_next().unwrap().is_some());
+        assert!(match decoder.decode_next() {
+            Err(Amf0DeserializationError::TooManyValues(3)) => true,
+            _ => false}
+        );
+    }
+
+    #[test]
+    fn error_when_avmplus_value_is_deeply_nested() {
+        let mut vector = vec![markers::AVMPLUS_OBJECT_MARKER];
+        for _ in 0..200000 {
+            vector.extend(vec![0x09, 0x03, 0x01]);
+        }
+
+        let mut decoder = Amf0Decoder::new(&vector);
+        let result = decoder.decode_next();
+
+        assert!(match result {
+            Err(Amf0DeserializationError::MaxDepthExceeded(32)) => true,
+            _ => false}
+        );
+    }
+
+    #[test]
+    fn error_location_matches_owned_deserializer() {
+        let mut vector = vec![];
+        vector.push(markers::NULL_MARKER);
+        vector.push(markers::ECMA_ARRAY_MARKER);
+        vector.write_u32::<BigEndian>(1).unwrap();
+        vector.write_u16::<BigEndian>(4).unwrap();
+        vector.extend("list".as_bytes());
+        vector.push(markers::STRICT_ARRAY_MARKER);
+        vector.write_u32::<BigEndian>(2).unwrap();
+        vector.push(markers::NULL_MARKER);
+        vector.push(0x0D);
+
+        let mut decoder = Amf0Decoder::new(&vector);
+        assert_eq!(decoder.decode_next().unwrap(), Some(Amf0ValueRef::Null));
+        assert_eq!(decoder.error_location(), None);
+
+        match decoder.decode_next() {
+            Err(Amf0DeserializationError::UnknownMarker(0x0D)) => (),
+            x => panic!("Expected UnknownMarker error, instead received {:?}", x)
+        }
+
+        let expected = Amf0ErrorLocation { offset: 18, path: "[1].list[1]".to_string() };
+        assert_eq!(decoder.error_location(), Some(&expected));
+
+        let owned_error = deserialize_located(&mut Cursor::new(vector.clone()), &Amf0DecodeLimits::default()).unwrap_err();
+        assert_eq!(owned_error.location, expected);
+    }
+
+    #[test]
+    fn lenient_mode_skips_object_properties_with_unknown_markers() {
+        let mut vector = vec![];
+        vector.push(markers::OBJECT_MARKER);
+        vector.write_u16::<BigEndian>(11).unwrap();
+        vector.extend("unsupported".as_bytes());
+        vector.push(markers::UNSUPPORTED_MARKER);
+        vector.write_u16::<BigEndian>(3).unwrap();
+        vector.extend("xml".as_bytes());
+        vector.push(markers::XML_DOCUMENT_MARKER);
+        vector.write_u32::<BigEndian>(4).unwrap();
+        vector.extend("<a/>".as_bytes());
+        vector.write_u16::<BigEndian>(4).unwrap();
+        vector.extend("test".as_bytes());
+        vector.push(markers::BOOLEAN_MARKER);
+        vector.push(1);
+        vector.write_u16::<BigEndian>(markers::UTF_8_EMPTY_MARKER).unwrap();
+        vector.push(markers::OBJECT_END_MARKER);
+
+        let limits = Amf0DecodeLimits { skip_unknown_values: true, .. Amf0DecodeLimits::default() };
+        let mut decoder = Amf0Decoder::with_limits(&vector, limits);
+        let object = match decoder.decode_next().unwrap() {
+            Some(Amf0ValueRef::Object(object)) => object,
+            x => panic!("Expected object, instead got {:?}", x)
+        };
+
+        let properties: Vec<(Cow<str>, Amf0ValueRef)> = object.iter().collect();
+        assert_eq!(properties, vec![(Cow::Borrowed("test"), Amf0ValueRef::Boolean(true))]);
+        assert_eq!(decoder.bytes_consumed(), vector.len());
+    }
+}
@@ -2,19 +2,49 @@
 //! that were encoded via the AMF0 specification 
 //! (http://wwwimages.adobe.com/content/dam/Adobe/en/devnet/amf/pdf/amf0-file-format-specification.pdf)
 
+use std::borrow::Cow;
+use std::io;
 use std::io::Read;
-use std::collections::HashMap;
-use super::Amf0Value;
-use super::errors::Amf0DeserializationError;
+use super::{Amf0Value, Amf0Properties};
+use super::errors::{Amf0DeserializationError, Amf0LocatedError, PathSegment, get_error_location};
+use limits::{Amf0DecodeLimits, get_amf3_limits, convert_amf3_error};
 use markers;
+use amf3;
 use byteorder::{BigEndian, ReadBytesExt};
 
-/// Turns any readable byte stream and converts it into an array of AMF0 values
+/// Turns any readable byte stream and converts it into an array of AMF0 values.
+/// The default `Amf0DecodeLimits` are enforced.
 pub fn deserialize(bytes: &mut Read) -> Result<Vec<Amf0Value>, Amf0DeserializationError> {
+    deserialize_with_limits(bytes, &Amf0DecodeLimits::default())
+}
+
+/// Turns any readable byte stream and converts it into an array of AMF0 values,
+/// failing as soon as the input goes over one of the specified limits.
+pub fn deserialize_with_limits(bytes: &mut Read, limits: &Amf0DecodeLimits) -> Result<Vec<Amf0Value>, Amf0DeserializationError> {
+    deserialize_located(bytes, limits).map_err(Amf0DeserializationError::from)
+}
+
+/// Same as `deserialize_with_limits`, but errors also report the byte offset and
+/// the path of the value that could not be decoded.
+pub fn deserialize_located(bytes: &mut Read, limits: &Amf0DecodeLimits) -> Result<Vec<Amf0Value>, Amf0LocatedError> {
+    let mut reader = CountingReader { inner: bytes, position: 0 };
+    let mut state = DecodeState { limits: limits, depth: 0, total_values: 0, path: Vec::new() };
     let mut results = vec![];
 
     loop {
-        match try!(read_next_value(bytes)) {
+        let value = match read_next_value(&mut reader, &mut state) {
+            Ok(value) => value,
+            Err(err) => {
+                // Segments were added innermost first while the error unwound
+                state.path.push(PathSegment::Index(results.len()));
+                state.path.reverse();
+
+                let location = get_error_location(&err, reader.position, &state.path);
+                return Err(Amf0LocatedError { error: err, location: location });
+            }
+        };
+
+        match value {
             Some(x) => results.push(x),
             None => break
         };
@@ -23,28 +53,135 @@ pub fn deserialize(bytes: &mut Read) -> Result<Vec<Amf0Value>, Amf0Deserializati
     Ok(results)
 }
 
-fn read_next_value(bytes: &mut Read) -> Result<Option<Amf0Value>, Amf0DeserializationError> {
+/// Reads a single AMF0 value, leaving any bytes after it unread.  `None` is
+/// returned if the stream has no more values.  The default `Amf0DecodeLimits`
+/// are enforced.
+pub fn deserialize_value(bytes: &mut Read) -> Result<Option<Amf0Value>, Amf0DeserializationError> {
+    deserialize_value_with_limits(bytes, &Amf0DecodeLimits::default())
+}
+
+/// Reads a single AMF0 value, leaving any bytes after it unread, and failing as
+/// soon as the value goes over one of the specified limits
+pub fn deserialize_value_with_limits(bytes: &mut Read, limits: &Amf0DecodeLimits) -> Result<Option<Amf0Value>, Amf0DeserializationError> {
+    let mut reader = CountingReader { inner: bytes, position: 0 };
+    let mut state = DecodeState { limits: limits, depth: 0, total_values: 0, path: Vec::new() };
+    read_next_value(&mut reader, &mut state)
+}
+
+/// Keeps track of how many bytes have been read, so errors can report where
+/// they happened
+struct CountingReader<'a> {
+    inner: &'a mut Read,
+    position: usize
+}
+
+impl<'a> Read for CountingReader<'a> {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        let bytes_read = try!(self.inner.read(buf));
+        self.position = self.position + bytes_read;
+        Ok(bytes_read)
+    }
+}
+
+/// Progress towards the decode limits.  `path` stays empty while values decode
+/// successfully, and is filled in with the innermost segment first as an error
+/// unwinds, so property names only get copied when an error is reported.
+struct DecodeState<'a> {
+    limits: &'a Amf0DecodeLimits,
+    depth: usize,
+    total_values: usize,
+    path: Vec<PathSegment<'static>>
+}
+
+impl<'a> DecodeState<'a> {
+    fn count_value(&mut self) -> Result<(), Amf0DeserializationError> {
+        if self.total_values >= self.limits.max_total_values {
+            return Err(Amf0DeserializationError::TooManyValues(self.limits.max_total_values));
+        }
+
+        self.total_values = self.total_values + 1;
+        Ok(())
+    }
+
+    fn enter_nested(&mut self) -> Result<(), Amf0DeserializationError> {
+        if self.depth >= self.limits.max_depth {
+            return Err(Amf0DeserializationError::MaxDepthExceeded(self.limits.max_depth));
+        }
+
+        self.depth = self.depth + 1;
+        Ok(())
+    }
+
+    fn exit_nested(&mut self) {
+        self.depth = self.depth - 1;
+    }
+}
+
+fn read_marker(bytes: &mut CountingReader) -> Result<Option<u8>, Amf0DeserializationError> {
     let mut buffer: [u8; 1] = [0];
     let bytes_read = try!(bytes.read(&mut buffer));
 
-    if bytes_read == 0 {
+    if bytes_read == 0 || buffer[0] == markers::OBJECT_END_MARKER {
         return Ok(None);
     }
 
-    if buffer[0] == markers::OBJECT_END_MARKER {
-        return Ok(None);
+    Ok(Some(buffer[0]))
+}
+
+fn read_next_value(bytes: &mut CountingReader, state: &mut DecodeState) -> Result<Option<Amf0Value>, Amf0DeserializationError> {
+    match try!(read_marker(bytes)) {
+        Some(marker) => parse_value(bytes, marker, state).map(Some),
+        None => Ok(None)
     }
+}
 
-    match buffer[0] {
-        markers::BOOLEAN_MARKER => parse_bool(bytes).map(Some),
-        markers::NULL_MARKER => parse_null().map(Some),
-        markers::NUMBER_MARKER => parse_number(bytes).map(Some),
-        markers::OBJECT_MARKER => parse_object(bytes).map(Some),
-        markers::STRING_MARKER => parse_string(bytes).map(Some),
-        _ => Err(Amf0DeserializationError::UnknownMarker(0))
+fn parse_value(bytes: &mut CountingReader, marker: u8, state: &mut DecodeState) -> Result<Amf0Value, Amf0DeserializationError> {
+    try!(state.count_value());
+    match marker {
+        markers::BOOLEAN_MARKER => parse_bool(bytes),
+        markers::NULL_MARKER => parse_null(),
+        markers::NUMBER_MARKER => parse_number(bytes),
+        markers::OBJECT_MARKER => parse_object(bytes, state),
+        markers::STRING_MARKER => parse_string(bytes, state),
+        markers::ECMA_ARRAY_MARKER => parse_ecma_array(bytes, state),
+        markers::STRICT_ARRAY_MARKER => parse_strict_array(bytes, state),
+        markers::DATE_MARKER => parse_date(bytes),
+        markers::UNDEFINED_MARKER => parse_undefined(),
+        markers::REFERENCE_MARKER => parse_reference(bytes),
+        markers::LONG_STRING_MARKER => parse_long_string(bytes, state),
+        markers::TYPED_OBJECT_MARKER => parse_typed_object(bytes, state),
+        markers::AVMPLUS_OBJECT_MARKER => parse_avmplus(bytes, state),
+        _ => Err(Amf0DeserializationError::UnknownMarker(marker))
     }
 }
 
+/// Whether a value with an unsupported marker can be skipped in lenient mode.
+/// Only markers whose framing is known can be skipped: the unsupported marker
+/// has no payload and an XML document is a long string.  For any other marker
+/// there is no way to tell where the value ends.
+pub fn is_skippable_marker(marker: u8) -> bool {
+    marker == markers::UNSUPPORTED_MARKER || marker == markers::XML_DOCUMENT_MARKER
+}
+
+fn skip_unknown_value(bytes: &mut CountingReader, marker: u8, state: &DecodeState) -> Result<(), Amf0DeserializationError> {
+    if marker == markers::XML_DOCUMENT_MARKER {
+        let length = try!(bytes.read_u32::<BigEndian>()) as usize;
+        if length > state.limits.max_string_length {
+            return Err(Amf0DeserializationError::StringTooLong {
+                length: length,
+                limit: state.limits.max_string_length
+            });
+        }
+
+        let skipped = try!(io::copy(&mut bytes.take(length as u64), &mut io::sink()));
+        if skipped < length as u64 {
+            return Err(Amf0DeserializationError::UnexpectedEof);
+        }
+    }
+
+    Ok(())
+}
+
 fn parse_number(bytes: &mut Read) -> Result<Amf0Value, Amf0DeserializationError> {
     let number = try!(bytes.read_f64::<BigEndian>());
     let value = Amf0Value::Number(number);
@@ -56,6 +193,10 @@ fn parse_null() -> Result<Amf0Value, Amf0DeserializationError> {
     Ok(Amf0Value::Null)
 }
 
+fn parse_undefined() -> Result<Amf0Value, Amf0DeserializationError> {
+    Ok(Amf0Value::Undefined)
+}
+
 fn parse_bool(bytes: &mut Read) -> Result<Amf0Value, Amf0DeserializationError> {
     let value = try!(bytes.read_u8());
 
@@ -67,17 +208,112 @@ fn parse_bool(bytes: &mut Read) -> Result<Amf0Value, Amf0DeserializationError> {
     }    
 }
 
-fn parse_string(bytes: &mut Read) -> Result<Amf0Value, Amf0DeserializationError> {
+fn parse_string(bytes: &mut Read, state: &DecodeState) -> Result<Amf0Value, Amf0DeserializationError> {
     let length = try!(bytes.read_u16::<BigEndian>()); 
-    let mut buffer: Vec<u8> = vec![0; (length as usize)];    
-    try!(bytes.read(&mut buffer));
+    let value = try!(read_utf8(bytes, length as usize, state));
+    Ok(Amf0Value::Utf8String(value))
+}
 
-    let value = try!(String::from_utf8(buffer));
+fn parse_long_string(bytes: &mut Read, state: &DecodeState) -> Result<Amf0Value, Amf0DeserializationError> {
+    let length = try!(bytes.read_u32::<BigEndian>());
+    let value = try!(read_utf8(bytes, length as usize, state));
     Ok(Amf0Value::Utf8String(value))
 }
 
-fn parse_object(bytes: &mut Read) -> Result<Amf0Value, Amf0DeserializationError> {
-    let mut properties = HashMap::new();
+fn read_utf8(bytes: &mut Read, length: usize, state: &DecodeState) -> Result<String, Amf0DeserializationError> {
+    // Check the length before allocating, since it comes straight from the input
+    if length > state.limits.max_string_length {
+        return Err(Amf0DeserializationError::StringTooLong {
+            length: length,
+            limit: state.limits.max_string_length
+        });
+    }
+
+    let mut buffer: Vec<u8> = vec![0; length];
+    try!(bytes.read_exact(&mut buffer));
+
+    let value = try!(String::from_utf8(buffer));
+    Ok(value)
+}
+
+fn parse_reference(bytes: &mut Read) -> Result<Amf0Value, Amf0DeserializationError> {
+    let index = try!(bytes.read_u16::<BigEndian>());
+    Ok(Amf0Value::Reference(index))
+}
+
+fn parse_object(bytes: &mut CountingReader, state: &mut DecodeState) -> Result<Amf0Value, Amf0DeserializationError> {
+    let properties = try!(parse_properties(bytes, state));
+    Ok(Amf0Value::Object(properties))
+}
+
+/// Decodes an AMF3 value within what is left of the AMF0 limits
+fn parse_avmplus(bytes: &mut Read, state: &mut DecodeState) -> Result<Amf0Value, Amf0DeserializationError> {
+    let amf3_limits = get_amf3_limits(state.limits, state.depth, state.total_values);
+    let (value, value_count) = match amf3::deserialize_value_with_limits(bytes, &amf3_limits) {
+        Ok(result) => result,
+        Err(err) => return Err(convert_amf3_error(err, state.limits))
+    };
+
+    // The AVM+ marker was already counted, and the AMF3 value counts itself again
+    state.total_values = state.total_values + value_count - 1;
+    Ok(Amf0Value::AvmPlus(value))
+}
+
+fn parse_typed_object(bytes: &mut CountingReader, state: &mut DecodeState) -> Result<Amf0Value, Amf0DeserializationError> {
+    let name_length = try!(bytes.read_u16::<BigEndian>());
+    let class_name = try!(read_utf8(bytes, name_length as usize, state));
+    let properties = try!(parse_properties(bytes, state));
+
+    Ok(Amf0Value::TypedObject { class_name: class_name, properties: properties })
+}
+
+fn parse_ecma_array(bytes: &mut CountingReader, state: &mut DecodeState) -> Result<Amf0Value, Amf0DeserializationError> {
+    // The associative count is only a hint (encoders are known to send 0 here),
+    // so rely on the object end marker to know when the array is finished
+    let _associative_count = try!(bytes.read_u32::<BigEndian>());
+    let properties = try!(parse_properties(bytes, state));
+    Ok(Amf0Value::EcmaArray(properties))
+}
+
+fn parse_strict_array(bytes: &mut CountingReader, state: &mut DecodeState) -> Result<Amf0Value, Amf0DeserializationError> {
+    let count = try!(bytes.read_u32::<BigEndian>());
+    if count as usize > state.limits.max_properties_per_object {
+        return Err(Amf0DeserializationError::TooManyProperties(state.limits.max_properties_per_object));
+    }
+
+    try!(state.enter_nested());
+    let mut values = Vec::new();
+
+    for index in 0..count {
+        let result = match read_next_value(bytes, state) {
+            Ok(Some(value)) => Ok(value),
+            Ok(None) => Err(Amf0DeserializationError::UnexpectedEof),
+            Err(err) => Err(err)
+        };
+
+        match result {
+            Ok(value) => values.push(value),
+            Err(err) => {
+                state.path.push(PathSegment::Index(index as usize));
+                return Err(err);
+            }
+        }
+    }
+
+    state.exit_nested();
+    Ok(Amf0Value::StrictArray(values))
+}
+
+fn parse_date(bytes: &mut Read) -> Result<Amf0Value, Amf0DeserializationError> {
+    let unix_time = try!(bytes.read_f64::<BigEndian>());
+    let time_zone = try!(bytes.read_i16::<BigEndian>());
+
+    Ok(Amf0Value::Date { unix_time: unix_time, time_zone: time_zone })
+}
+
+fn parse_properties(bytes: &mut CountingReader, state: &mut DecodeState) -> Result<Amf0Properties, Amf0DeserializationError> {
+    try!(state.enter_nested());
+    let mut properties = Amf0Properties::new();
 
     loop {
         let label_length = try!(bytes.read_u16::<BigEndian>());
@@ -92,26 +328,52 @@ fn parse_object(bytes: &mut Read) -> Result<Amf0Value, Amf0DeserializationError>
             break;
         }
 
-        let mut label_buffer = vec![0; (label_length as usize)];
-        try!(bytes.read(&mut label_buffer));
+        if properties.len() >= state.limits.max_properties_per_object {
+            return Err(Amf0DeserializationError::TooManyProperties(state.limits.max_properties_per_object));
+        }
+
+        let label = try!(read_utf8(bytes, label_length as usize, state));
+        match read_property_value(bytes, state) {
+            Ok(Some(property_value)) => { properties.insert(label, property_value); },
+            Ok(None) => (),
+            Err(err) => {
+                state.path.push(PathSegment::Property(Cow::Owned(label)));
+                return Err(err);
+            }
+        }
+    }
+
+    state.exit_nested();
+    Ok(properties)
+}
 
-        let label = try!(String::from_utf8(label_buffer));
-        match try!(read_next_value(bytes)) {
-            Some(property_value) => properties.insert(label, property_value),
-            None => return Err(Amf0DeserializationError::UnexpectedEof)  
-        };        
+/// Reads the value of an object property, or `None` if the property should be
+/// left out of the object
+fn read_property_value(bytes: &mut CountingReader, state: &mut DecodeState) -> Result<Option<Amf0Value>, Amf0DeserializationError> {
+    let marker = match try!(read_marker(bytes)) {
+        Some(marker) => marker,
+        None => return Err(Amf0DeserializationError::UnexpectedEof)
+    };
+
+    if state.limits.skip_unknown_values && is_skippable_marker(marker) {
+        // Drop the property but keep the rest of the object
+        try!(skip_unknown_value(bytes, marker, state));
+        return Ok(None);
     }
 
-    let deserialized_value = Amf0Value::Object(properties);
-    Ok(deserialized_value)
+    parse_value(bytes, marker, state).map(Some)
 }
 
 #[cfg(test)]
 mod tests {
     use std::io::Cursor;
-    use std::collections::HashMap;
-    use super::deserialize;
+    use Amf0Properties;
+    use super::{deserialize, deserialize_with_limits, deserialize_located, deserialize_value};
+    use limits::Amf0DecodeLimits;
     use super::super::Amf0Value;
+    use super::super::serialize;
+    use super::super::errors::{Amf0DeserializationError, Amf0ErrorLocation};
+    use amf3::{Amf3Value, Amf3DeserializationError};
     use markers;
     use byteorder::{BigEndian, WriteBytesExt};
 
@@ -200,10 +462,560 @@ mod tests {
         let mut input = Cursor::new(vector);
         let result = deserialize(&mut input).unwrap();
 
-        let mut properties = HashMap::new();
+        let mut properties = Amf0Properties::new();
         properties.insert("test".to_string(), Amf0Value::Number(NUMBER));
 
         let expected = vec![Amf0Value::Object(properties)];
         assert_eq!(result, expected);
     }
+
+    #[test]
+    fn object_properties_keep_wire_order() {
+        let mut vector = vec![];
+        vector.push(markers::OBJECT_MARKER);
+        for name in vec!["level", "code", "description"] {
+            vector.write_u16::<BigEndian>(name.len() as u16).unwrap();
+            vector.extend(name.as_bytes());
+            vector.push(markers::NULL_MARKER);
+        }
+
+        vector.write_u16::<BigEndian>(markers::UTF_8_EMPTY_MARKER).unwrap();
+        vector.push(markers::OBJECT_END_MARKER);
+
+        let mut input = Cursor::new(vector.clone());
+        let result = deserialize(&mut input).unwrap();
+
+        let names: Vec<String> = match result[0] {
+            Amf0Value::Object(ref properties) => properties.iter().map(|(name, _)| name.clone()).collect(),
+            _ => panic!("Expected an object")
+        };
+
+        assert_eq!(names, vec!["level", "code", "description"]);
+        assert_eq!(serialize(&result).unwrap(), vector);
+    }
+
+    #[test]
+    fn can_deserialize_ecma_array() {
+        const NUMBER: f64 = 332.0;
+
+        let mut vector = vec![];
+        vector.push(markers::ECMA_ARRAY_MARKER);
+        vector.write_u32::<BigEndian>(1).unwrap();
+        vector.write_u16::<BigEndian>(4).unwrap();
+        vector.extend("test".as_bytes());
+        vector.push(markers::NUMBER_MARKER);
+        vector.write_f64::<BigEndian>(NUMBER).unwrap();
+        vector.write_u16::<BigEndian>(markers::UTF_8_EMPTY_MARKER).unwrap();
+        vector.push(markers::OBJECT_END_MARKER);
+
+        let mut input = Cursor::new(vector);
+        let result = deserialize(&mut input).unwrap();
+
+        let mut properties = Amf0Properties::new();
+        properties.insert("test".to_string(), Amf0Value::Number(NUMBER));
+
+        let expected = vec![Amf0Value::EcmaArray(properties)];
+        assert_eq!(result, expected);
+    }
+
+    #[test]
+    fn can_deserialize_ecma_array_with_incorrect_associative_count() {
+        let mut vector = vec![];
+        vector.push(markers::ECMA_ARRAY_MARKER);
+        vector.write_u32::<BigEndian>(0).unwrap();
+        vector.write_u16::<BigEndian>(4).unwrap();
+        vector.extend("test".as_bytes());
+        vector.push(markers::NULL_MARKER);
+        vector.write_u16::<BigEndian>(markers::UTF_8_EMPTY_MARKER).unwrap();
+        vector.push(markers::OBJECT_END_MARKER);
+
+        let mut input = Cursor::new(vector);
+        let result = deserialize(&mut input).unwrap();
+
+        let mut properties = Amf0Properties::new();
+        properties.insert("test".to_string(), Amf0Value::Null);
+
+        let expected = vec![Amf0Value::EcmaArray(properties)];
+        assert_eq!(result, expected);
+    }
+
+    #[test]
+    fn can_deserialize_strict_array() {
+        let mut vector = vec![];
+        vector.push(markers::STRICT_ARRAY_MARKER);
+        vector.write_u32::<BigEndian>(2).unwrap();
+        vector.push(markers::NUMBER_MARKER);
+        vector.write_f64::<BigEndian>(15.0).unwrap();
+        vector.push(markers::BOOLEAN_MARKER);
+        vector.push(1);
+
+        let mut input = Cursor::new(vector);
+        let result = deserialize(&mut input).unwrap();
+
+        let expected = vec![Amf0Value::StrictArray(vec![Amf0Value::Number(15.0), Amf0Value::Boolean(true)])];
+        assert_eq!(result, expected);
+    }
+
+    #[test]
+    fn can_deserialize_date() {
+        let mut vector = vec![];
+        vector.push(markers::DATE_MARKER);
+        vector.write_f64::<BigEndian>(1474215410000.0).unwrap();
+        vector.write_i16::<BigEndian>(0).unwrap();
+
+        let mut input = Cursor::new(vector);
+        let result = deserialize(&mut input).unwrap();
+
+        let expected = vec![Amf0Value::Date { unix_time: 1474215410000.0, time_zone: 0 }];
+        assert_eq!(result, expected);
+    }
+
+    #[test]
+    fn error_when_strict_array_has_fewer_values_than_count() {
+        let mut vector = vec![];
+        vector.push(markers::STRICT_ARRAY_MARKER);
+        vector.write_u32::<BigEndian>(2).unwrap();
+        vector.push(markers::NULL_MARKER);
+
+        let mut input = Cursor::new(vector);
+        let result = deserialize(&mut input);
+
+        assert!(match result.unwrap_err() {
+            Amf0DeserializationError::UnexpectedEof => true,
+            _ => false}
+        );
+    }
+
+    #[test]
+    fn can_round_trip_arrays_and_dates() {
+        let mut properties = Amf0Properties::new();
+        properties.insert("duration".to_string(), Amf0Value::Number(0.0));
+        properties.insert("encoder".to_string(), Amf0Value::Utf8String("obs-output module".to_string()));
+        properties.insert("keyframes".to_string(), Amf0Value::StrictArray(vec![Amf0Value::Number(1.0), Amf0Value::Number(2.0)]));
+
+        let input = vec![
+            Amf0Value::Utf8String("onMetaData".to_string()),
+            Amf0Value::EcmaArray(properties),
+            Amf0Value::Date { unix_time: 1474215410000.0, time_zone: 0 },
+        ];
+
+        let bytes = serialize(&input).unwrap();
+        let mut cursor = Cursor::new(bytes);
+        let result = deserialize(&mut cursor).unwrap();
+
+        assert_eq!(result, input);
+    }
+
+    #[test]
+    fn can_deserialize_undefined() {
+        let vector = vec![markers::UNDEFINED_MARKER];
+
+        let mut input = Cursor::new(vector);
+        let result = deserialize(&mut input).unwrap();
+
+        let expected = vec![Amf0Value::Undefined];
+        assert_eq!(result, expected);
+    }
+
+    #[test]
+    fn can_deserialize_reference() {
+        let mut vector = vec![];
+        vector.push(markers::REFERENCE_MARKER);
+        vector.write_u16::<BigEndian>(3).unwrap();
+
+        let mut input = Cursor::new(vector);
+        let result = deserialize(&mut input).unwrap();
+
+        let expected = vec![Amf0Value::Reference(3)];
+        assert_eq!(result, expected);
+    }
+
+    #[test]
+    fn can_deserialize_long_string() {
+        let value = "test";
+
+        let mut vector = vec![];
+        vector.push(markers::LONG_STRING_MARKER);
+        vector.write_u32::<BigEndian>(value.len() as u32).unwrap();
+        vector.extend(value.as_bytes());
+
+        let mut input = Cursor::new(vector);
+        let result = deserialize(&mut input).unwrap();
+
+        let expected = vec![Amf0Value::Utf8String(value.to_string())];
+        assert_eq!(result, expected);
+    }
+
+    #[test]
+    fn can_deserialize_typed_object() {
+        let mut vector = vec![];
+        vector.push(markers::TYPED_OBJECT_MARKER);
+        vector.write_u16::<BigEndian>(5).unwrap();
+        vector.extend("Class".as_bytes());
+        vector.write_u16::<BigEndian>(4).unwrap();
+        vector.extend("test".as_bytes());
+        vector.push(markers::UNDEFINED_MARKER);
+        vector.write_u16::<BigEndian>(markers::UTF_8_EMPTY_MARKER).unwrap();
+        vector.push(markers::OBJECT_END_MARKER);
+
+        let mut input = Cursor::new(vector);
+        let result = deserialize(&mut input).unwrap();
+
+        let mut properties = Amf0Properties::new();
+        properties.insert("test".to_string(), Amf0Value::Undefined);
+
+        let expected = vec![Amf0Value::TypedObject { class_name: "Class".to_string(), properties: properties }];
+        assert_eq!(result, expected);
+    }
+
+    #[test]
+    fn can_deserialize_avmplus_value() {
+        let vector = vec![markers::AVMPLUS_OBJECT_MARKER, 0x04, 0x05, markers::NULL_MARKER];
+
+        let mut input = Cursor::new(vector);
+        let result = deserialize(&mut input).unwrap();
+
+        let expected = vec![Amf0Value::AvmPlus(Amf3Value::Integer(5)), Amf0Value::Null];
+        assert_eq!(result, expected);
+    }
+
+    #[test]
+    fn error_when_avmplus_arrays_nested_too_deep() {
+        // Each AMF3 array has no associative values and a single dense value
+        let mut vector = vec![markers::AVMPLUS_OBJECT_MARKER];
+        for _ in 0..200000 {
+            vector.extend(&[0x09, 0x03, 0x01]);
+        }
+
+        vector.push(0x01);
+
+        let mut input = Cursor::new(vector);
+        let result = deserialize(&mut input);
+
+        match result.unwrap_err() {
+            Amf0DeserializationError::MaxDepthExceeded(32) => (),
+            x => panic!("Expected MaxDepthExceeded error, instead received {:?}", x)
+        }
+    }
+
+    #[test]
+    fn avmplus_values_use_remaining_depth() {
+        // AMF0 object containing an AVM+ value with two nested AMF3 arrays
+        let mut vector = vec![markers::OBJECT_MARKER];
+        vector.write_u16::<BigEndian>(1).unwrap();
+        vector.extend("a".as_bytes());
+        vector.push(markers::AVMPLUS_OBJECT_MARKER);
+        vector.extend(&[0x09, 0x03, 0x01, 0x09, 0x03, 0x01, 0x01]);
+        vector.write_u16::<BigEndian>(0).unwrap();
+        vector.push(markers::OBJECT_END_MARKER);
+
+        let limits = Amf0DecodeLimits { max_depth: 3, .. Amf0DecodeLimits::default() };
+        let mut input = Cursor::new(vector.clone());
+        assert!(deserialize_with_limits(&mut input, &limits).is_ok());
+
+        let limits = Amf0DecodeLimits { max_depth: 2, .. Amf0DecodeLimits::default() };
+        let mut input = Cursor::new(vector);
+        let result = deserialize_with_limits(&mut input, &limits);
+
+        match result.unwrap_err() {
+            Amf0DeserializationError::MaxDepthExceeded(2) => (),
+            x => panic!("Expected MaxDepthExceeded error, instead received {:?}", x)
+        }
+    }
+
+    #[test]
+    fn error_when_avmplus_string_longer_than_limit() {
+        // AMF3 string with the largest inline U29 length and almost no data
+        let vector = vec![markers::AVMPLUS_OBJECT_MARKER, 0x06, 0xFF, 0xFF, 0xFF, 0xFF, b'a'];
+
+        let mut input = Cursor::new(vector);
+        let result = deserialize(&mut input);
+
+        match result.unwrap_err() {
+            Amf0DeserializationError::StringTooLong { length: 0x0FFFFFFF, limit: 1048576 } => (),
+            x => panic!("Expected StringTooLong error, instead received {:?}", x)
+        }
+    }
+
+    #[test]
+    fn error_when_avmplus_references_expand_past_byte_limit() {
+        // AMF3 array holding a 1MB string followed by 100 references to it
+        let mut vector = vec![markers::AVMPLUS_OBJECT_MARKER, 0x09, 0x81, 0x4B, 0x01, 0x06, 0x80, 0xC0, 0x80, 0x01];
+        vector.extend(vec![b'a'; 1024 * 1024]);
+        for _ in 0..100 {
+            vector.extend(&[0x06, 0x00]);
+        }
+
+        let mut input = Cursor::new(vector);
+        let result = deserialize(&mut input);
+
+        match result.unwrap_err() {
+            Amf0DeserializationError::Amf3DeserializationError(Amf3DeserializationError::TooManyBytes(16777216)) => (),
+            x => panic!("Expected TooManyBytes error, instead received {:?}", x)
+        }
+    }
+
+    #[test]
+    fn avmplus_values_count_towards_total_values() {
+        // Two AVM+ arrays of two values each (3 values apiece)
+        let mut vector = vec![];
+        for _ in 0..2 {
+            vector.push(markers::AVMPLUS_OBJECT_MARKER);
+            vector.extend(&[0x09, 0x05, 0x01, 0x01, 0x01]);
+        }
+
+        let limits = Amf0DecodeLimits { max_total_values: 6, .. Amf0DecodeLimits::default() };
+        let mut input = Cursor::new(vector.clone());
+        assert_eq!(deserialize_with_limits(&mut input, &limits).unwrap().len(), 2);
+
+        let limits = Amf0DecodeLimits { max_total_values: 5, .. Amf0DecodeLimits::default() };
+        let mut input = Cursor::new(vector);
+        let result = deserialize_with_limits(&mut input, &limits);
+
+        match result.unwrap_err() {
+            Amf0DeserializationError::TooManyValues(5) => (),
+            x => panic!("Expected TooManyValues error, instead received {:?}", x)
+        }
+    }
+
+    #[test]
+    fn deserialize_value_leaves_following_bytes_unread() {
+        let mut vector = vec![markers::BOOLEAN_MARKER, 1];
+        vector.extend(&[0xFF, 0xFF, 0xFF]);
+
+        let mut input = Cursor::new(vector);
+        let result = deserialize_value(&mut input).unwrap();
+
+        assert_eq!(result, Some(Amf0Value::Boolean(true)));
+        assert_eq!(input.position(), 2);
+        assert_eq!(deserialize_value(&mut input).is_err(), true);
+    }
+
+    #[test]
+    fn deserialize_value_returns_none_when_no_values_left() {
+        let mut input = Cursor::new(vec![]);
+        assert_eq!(deserialize_value(&mut input).unwrap(), None);
+    }
+
+    #[test]
+    fn error_when_string_shorter_than_length() {
+        let mut vector = vec![];
+        vector.push(markers::STRING_MARKER);
+        vector.write_u16::<BigEndian>(10).unwrap();
+        vector.extend("test".as_bytes());
+
+        let mut input = Cursor::new(vector);
+        let result = deserialize(&mut input);
+
+        assert!(result.is_err());
+    }
+
+    #[test]
+    fn error_when_objects_nested_deeper_than_limit() {
+        let mut vector = vec![];
+        for _ in 0..3 {
+            vector.push(markers::OBJECT_MARKER);
+            vector.write_u16::<BigEndian>(1).unwrap();
+            vector.extend("a".as_bytes());
+        }
+
+        vector.push(markers::NULL_MARKER);
+
+        let limits = Amf0DecodeLimits { max_depth: 2, .. Amf0DecodeLimits::default() };
+        let mut input = Cursor::new(vector);
+        let result = deserialize_with_limits(&mut input, &limits);
+
+        match result.unwrap_err() {
+            Amf0DeserializationError::MaxDepthExceeded(2) => (),
+            x => panic!("Expected MaxDepthExceeded error, instead received {:?}", x)
+        }
+    }
+
+    #[test]
+    fn error_when_string_longer_than_limit_without_reading_it() {
+        let mut vector = vec![];
+        vector.push(markers::LONG_STRING_MARKER);
+        vector.write_u32::<BigEndian>(u32::max_value()).unwrap();
+
+        let limits = Amf0DecodeLimits { max_string_length: 100, .. Amf0DecodeLimits::default() };
+        let mut input = Cursor::new(vector);
+        let result = deserialize_with_limits(&mut input, &limits);
+
+        match result.unwrap_err() {
+            Amf0DeserializationError::StringTooLong { length, limit: 100 } => assert_eq!(length, u32::max_value() as usize),
+            x => panic!("Expected StringTooLong error, instead received {:?}", x)
+        }
+    }
+
+    #[test]
+    fn error_when_object_has_more_properties_than_limit() {
+        let mut properties = Amf0Properties::new();
+        properties.insert("a".to_string(), Amf0Value::Null);
+        properties.insert("b".to_string(), Amf0Value::Null);
+        properties.insert("c".to_string(), Amf0Value::Null);
+        let bytes = serialize(&vec![Amf0Value::EcmaArray(properties)]).unwrap();
+
+        let limits = Amf0DecodeLimits { max_properties_per_object: 2, .. Amf0DecodeLimits::default() };
+        let mut input = Cursor::new(bytes);
+        let result = deserialize_with_limits(&mut input, &limits);
+
+        match result.unwrap_err() {
+            Amf0DeserializationError::TooManyProperties(2) => (),
+            x => panic!("Expected TooManyProperties error, instead received {:?}", x)
+        }
+    }
+
+    #[test]
+    fn error_when_strict_array_count_greater_than_limit() {
+        let mut vector = vec![];
+        vector.push(markers::STRICT_ARRAY_MARKER);
+        vector.write_u32::<BigEndian>(u32::max_value()).unwrap();
+
+        let mut input = Cursor::new(vector);
+        let result = deserialize(&mut input);
+
+        match result.unwrap_err() {
+            Amf0DeserializationError::TooManyProperties(_) => (),
+            x => panic!("Expected TooManyProperties error, instead received {:?}", x)
+        }
+    }
+
+    #[test]
+    fn error_when_total_values_greater_than_limit() {
+        let values = vec![
+            Amf0Value::Number(1.0),
+            Amf0Value::StrictArray(vec![Amf0Value::Null, Amf0Value::Null]),
+        ];
+
+        let bytes = serialize(&values).unwrap();
+
+        let limits = Amf0DecodeLimits { max_total_values: 3, .. Amf0DecodeLimits::default() };
+        let mut input = Cursor::new(bytes);
+        let result = deserialize_with_limits(&mut input, &limits);
+
+        match result.unwrap_err() {
+            Amf0DeserializationError::TooManyValues(3) => (),
+            x => panic!("Expected TooManyValues error, instead received {:?}", x)
+        }
+    }
+
+    #[test]
+    fn unknown_marker_error_contains_marker_offset_and_path() {
+        let mut vector = vec![];
+        vector.push(markers::STRING_MARKER);
+        vector.write_u16::<BigEndian>(10).unwrap();
+        vector.extend("onMetaData".as_bytes());
+        vector.push(markers::ECMA_ARRAY_MARKER);
+        vector.write_u32::<BigEndian>(2).unwrap();
+        vector.write_u16::<BigEndian>(8).unwrap();
+        vector.extend("duration".as_bytes());
+        vector.push(markers::NUMBER_MARKER);
+        vector.write_f64::<BigEndian>(10.0).unwrap();
+        vector.write_u16::<BigEndian>(12).unwrap();
+        vector.extend("videocodecid".as_bytes());
+        vector.push(0x0D);
+
+        let mut input = Cursor::new(vector);
+        let result = deserialize_located(&mut input, &Amf0DecodeLimits::default());
+
+        let err = result.unwrap_err();
+        assert_eq!(err.location, Amf0ErrorLocation { offset: 51, path: "[1].videocodecid".to_string() });
+        match err.error {
+            Amf0DeserializationError::UnknownMarker(0x0D) => (),
+            x => panic!("Expected UnknownMarker error, instead received {:?}", x)
+        }
+    }
+
+    #[test]
+    fn deserialize_returns_errors_without_location() {
+        let vector = vec![markers::NULL_MARKER, 0x0D];
+
+        let mut input = Cursor::new(vector);
+        let result = deserialize(&mut input);
+
+        match result {
+            Err(Amf0DeserializationError::UnknownMarker(0x0D)) => (),
+            x => panic!("Expected UnknownMarker error, instead received {:?}", x)
+        }
+    }
+
+    #[test]
+    fn error_path_includes_strict_array_indexes() {
+        let mut vector = vec![];
+        vector.push(markers::STRICT_ARRAY_MARKER);
+        vector.write_u32::<BigEndian>(2).unwrap();
+        vector.push(markers::NULL_MARKER);
+        vector.push(markers::STRING_MARKER);
+        vector.write_u16::<BigEndian>(10).unwrap();
+
+        let mut input = Cursor::new(vector);
+        let result = deserialize_located(&mut input, &Amf0DecodeLimits::default());
+
+        assert_eq!(result.unwrap_err().location.path, "[0][1]");
+    }
+
+    #[test]
+    fn lenient_mode_skips_object_properties_with_unknown_markers() {
+        let mut vector = vec![];
+        vector.push(markers::OBJECT_MARKER);
+        vector.write_u16::<BigEndian>(11).unwrap();
+        vector.extend("unsupported".as_bytes());
+        vector.push(0x0D);
+        vector.write_u16::<BigEndian>(3).unwrap();
+        vector.extend("xml".as_bytes());
+        vector.push(markers::XML_DOCUMENT_MARKER);
+        vector.write_u32::<BigEndian>(6).unwrap();
+        vector.extend("<a/>  ".as_bytes());
+        vector.write_u16::<BigEndian>(4).unwrap();
+        vector.extend("test".as_bytes());
+        vector.push(markers::BOOLEAN_MARKER);
+        vector.push(1);
+        vector.write_u16::<BigEndian>(markers::UTF_8_EMPTY_MARKER).unwrap();
+        vector.push(markers::OBJECT_END_MARKER);
+        vector.push(markers::NULL_MARKER);
+
+        let limits = Amf0DecodeLimits { skip_unknown_values: true, .. Amf0DecodeLimits::default() };
+        let mut input = Cursor::new(vector);
+        let result = deserialize_with_limits(&mut input, &limits).unwrap();
+
+        let mut properties = Amf0Properties::new();
+        properties.insert("test".to_string(), Amf0Value::Boolean(true));
+
+        let expected = vec![Amf0Value::Object(properties), Amf0Value::Null];
+        assert_eq!(result, expected);
+    }
+
+    #[test]
+    fn lenient_mode_still_fails_on_unknown_top_level_markers() {
+        let vector = vec![markers::NULL_MARKER, 0x0D];
+
+        let limits = Amf0DecodeLimits { skip_unknown_values: true, .. Amf0DecodeLimits::default() };
+        let mut input = Cursor::new(vector);
+        let result = deserialize_with_limits(&mut input, &limits);
+
+        match result.unwrap_err() {
+            Amf0DeserializationError::UnknownMarker(0x0D) => (),
+            x => panic!("Expected UnknownMarker error, instead received {:?}", x)
+        }
+    }
+
+    #[test]
+    fn lenient_mode_fails_on_markers_with_unknown_framing() {
+        let mut vector = vec![];
+        vector.push(markers::OBJECT_MARKER);
+        vector.write_u16::<BigEndian>(9).unwrap();
+        vector.extend("recordset".as_bytes());
+        vector.push(0x0E);
+        vector.write_u16::<BigEndian>(markers::UTF_8_EMPTY_MARKER).unwrap();
+        vector.push(markers::OBJECT_END_MARKER);
+
+        let limits = Amf0DecodeLimits { skip_unknown_values: true, .. Amf0DecodeLimits::default() };
+        let mut input = Cursor::new(vector);
+        let result = deserialize_with_limits(&mut input, &limits);
+
+        match result {
+            Err(Amf0DeserializationError::UnknownMarker(0x0E)) => (),
+            x => panic!("Expected UnknownMarker error, instead received {:?}", x)
+        }
+    }
 }
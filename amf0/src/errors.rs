@@ -1,10 +1,14 @@
-use std::{io, string};
+use std::{fmt, io, string};
+use std::borrow::Cow;
+use std::error::Error;
+use amf3::{Amf3DeserializationError, Amf3SerializationError};
 
 quick_error! {
     #[derive(Debug)]
     pub enum Amf0DeserializationError {
         UnknownMarker(marker: u8) {
             description("Encountered unknown marker")
+            display("Encountered unknown marker 0x{:02x}", marker)
         }
 
         UnexpectedEmptyObjectPropertyName {
@@ -15,6 +19,32 @@ quick_error! {
             description("Hit end of the byte buffer but was expecting more data")
         }
 
+        MaxDepthExceeded(limit: usize) {
+            description("Values were nested deeper than allowed")
+            display("Values were nested deeper than the limit of {}", limit)
+        }
+
+        StringTooLong { length: usize, limit: usize } {
+            description("String length is greater than allowed")
+            display("String length of {} is greater than the limit of {}", length, limit)
+        }
+
+        TooManyProperties(limit: usize) {
+            description("Object or array contains more entries than allowed")
+            display("Object or array contains more than {} entries", limit)
+        }
+
+        TooManyValues(limit: usize) {
+            description("Input contains more values than allowed")
+            display("Input contains more than {} values", limit)
+        }
+
+        Amf3DeserializationError(err: Amf3DeserializationError) {
+            cause(err)
+            description(err.description())
+            from()
+        }
+
         Io(err: io::Error) {
             cause(err)
             description(err.description())
@@ -29,11 +59,92 @@ quick_error! {
     }
 }
 
+/// Where in the input a deserialization error happened
+#[derive(Clone, Debug, PartialEq)]
+pub struct Amf0ErrorLocation {
+    /// Byte offset from the start of the input.  For unknown markers this is the
+    /// offset of the marker itself.
+    pub offset: usize,
+
+    /// The value being decoded, with top level values as indexes and object
+    /// properties by name (e.g. `[1].videocodecid`)
+    pub path: String,
+}
+
+impl fmt::Display for Amf0ErrorLocation {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "byte offset {} ({})", self.offset, self.path)
+    }
+}
+
+/// An `Amf0DeserializationError` along with where in the input it happened
+#[derive(Debug)]
+pub struct Amf0LocatedError {
+    pub error: Amf0DeserializationError,
+    pub location: Amf0ErrorLocation,
+}
+
+impl fmt::Display for Amf0LocatedError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{} at {}", self.error, self.location)
+    }
+}
+
+impl Error for Amf0LocatedError {
+    fn description(&self) -> &str {
+        self.error.description()
+    }
+
+    fn cause(&self) -> Option<&Error> {
+        Some(&self.error)
+    }
+}
+
+impl From<Amf0LocatedError> for Amf0DeserializationError {
+    fn from(err: Amf0LocatedError) -> Amf0DeserializationError {
+        err.error
+    }
+}
+
+/// A step in the path to the value being decoded
+pub enum PathSegment<'a> {
+    Index(usize),
+    Property(Cow<'a, str>)
+}
+
+/// Locates an error that stopped decoding after `position` bytes were read
+pub fn get_error_location(err: &Amf0DeserializationError, position: usize, path: &[PathSegment]) -> Amf0ErrorLocation {
+    let offset = match *err {
+        // Point at the marker itself instead of the byte after it
+        Amf0DeserializationError::UnknownMarker(_) => position - 1,
+        _ => position
+    };
+
+    let mut formatted_path = String::new();
+    for segment in path {
+        match *segment {
+            PathSegment::Index(index) => formatted_path.push_str(&format!("[{}]", index)),
+            PathSegment::Property(ref name) => {
+                formatted_path.push('.');
+                formatted_path.push_str(name);
+            }
+        }
+    }
+
+    Amf0ErrorLocation { offset: offset, path: formatted_path }
+}
+
 quick_error! {
     #[derive(Debug)]
     pub enum Amf0SerializationError {
         NormalStringTooLong {
-            description("String length greater than 65,535")
+            description("Object property or class name length greater than 65,535")
+        }
+
+        Amf3SerializationError(err: Amf3SerializationError) {
+            cause(err)
+            description(err.description())
+            from()
         }
         
         Io(err: io::Error) {
@@ -42,4 +153,32 @@ quick_error! {
             from()
         }
     }
-}
\ No newline at end of file
+}
+
+#[cfg(feature = "serde")]
+quick_error! {
+    #[derive(Debug)]
+    pub enum Amf0SerdeError {
+        Message(message: String) {
+            description(message)
+            display("{}", message)
+        }
+
+        UnsupportedValue(value_type: &'static str) {
+            description("AMF0 value can not be represented by serde")
+            display("AMF0 {} values can not be represented by serde", value_type)
+        }
+
+        Serialization(err: Amf0SerializationError) {
+            cause(err)
+            description(err.description())
+            from()
+        }
+
+        Deserialization(err: Amf0DeserializationError) {
+            cause(err)
+            description(err.description())
+            from()
+        }
+    }
+}
@@ -4,11 +4,10 @@
 //! # Examples
 //! ```
 //! use std::io::Cursor;
-//! use std::collections::HashMap;
-//! use amf0::{Amf0Value, serialize, deserialize};
+//! use amf0::{Amf0Value, Amf0Properties, serialize, deserialize};
 //!
 //! // Put some data into the Amf0Value types
-//! let mut properties = HashMap::new();
+//! let mut properties = Amf0Properties::new();
 //! properties.insert("app".to_string(), Amf0Value::Number(99.0));
 //! properties.insert("second".to_string(), Amf0Value::Utf8String("test".to_string()));
 //!
@@ -30,24 +29,54 @@
 
 #[macro_use] extern crate quick_error;
 extern crate byteorder;
+extern crate amf3;
+#[cfg(feature = "serde")] #[macro_use] extern crate serde;
+#[cfg(all(test, feature = "serde"))] #[macro_use] extern crate serde_derive;
 
+#[macro_use] mod macros;
 mod serialization;
 mod deserialization;
 mod errors;
+mod properties;
+mod decoder;
+mod limits;
+mod accessors;
+#[cfg(feature = "serde")] mod ser;
+#[cfg(feature = "serde")] mod de;
 
 pub use serialization::serialize;
-pub use deserialization::deserialize;
-pub use errors::{Amf0DeserializationError, Amf0SerializationError};
+pub use deserialization::{deserialize, deserialize_with_limits, deserialize_located, deserialize_value, deserialize_value_with_limits};
+pub use errors::{Amf0DeserializationError, Amf0SerializationError, Amf0ErrorLocation, Amf0LocatedError};
+pub use properties::Amf0Properties;
+pub use limits::Amf0DecodeLimits;
+pub use decoder::{Amf0Decoder, Amf0ValueRef, Amf0ObjectRef, Amf0ArrayRef, Amf0PropertyIter, Amf0ArrayIter};
+#[cfg(feature = "serde")] pub use errors::Amf0SerdeError;
+#[cfg(feature = "serde")] pub use ser::{to_amf0, to_amf0_bytes};
+#[cfg(feature = "serde")] pub use de::{from_amf0, from_amf0_bytes};
 
-use std::collections::HashMap;
+use amf3::Amf3Value;
 
 #[derive(PartialEq, Debug)]
 pub enum Amf0Value {
     Number(f64),
     Boolean(bool),
     Utf8String(String),
-    Object(HashMap<String, Amf0Value>),
+    Object(Amf0Properties),
     Null,
+    EcmaArray(Amf0Properties),
+    StrictArray(Vec<Amf0Value>),
+    Date { unix_time: f64, time_zone: i16 },
+    Undefined,
+    TypedObject { class_name: String, properties: Amf0Properties },
+
+    /// Index into the table of complex values (objects, typed objects and arrays)
+    /// previously seen in the same message.  References are not resolved
+    /// during deserialization and are written back out as-is.
+    Reference(u16),
+
+    /// A value encoded with AMF3, signaled by the AVM+ marker.  Each AVM+ value
+    /// is encoded with its own AMF3 reference tables.
+    AvmPlus(Amf3Value),
 }
 
 mod markers {
@@ -56,6 +85,16 @@ mod markers {
     pub const STRING_MARKER: u8 = 2;
     pub const OBJECT_MARKER: u8 = 3;
     pub const NULL_MARKER: u8 = 5; 
+    pub const UNDEFINED_MARKER: u8 = 6;
+    pub const REFERENCE_MARKER: u8 = 7;
+    pub const ECMA_ARRAY_MARKER: u8 = 8;
     pub const OBJECT_END_MARKER: u8 = 9;
+    pub const STRICT_ARRAY_MARKER: u8 = 10;
+    pub const DATE_MARKER: u8 = 11;
+    pub const LONG_STRING_MARKER: u8 = 12;
+    pub const UNSUPPORTED_MARKER: u8 = 13;
+    pub const XML_DOCUMENT_MARKER: u8 = 15;
+    pub const TYPED_OBJECT_MARKER: u8 = 16;
+    pub const AVMPLUS_OBJECT_MARKER: u8 = 17;
     pub const UTF_8_EMPTY_MARKER: u16 = 0;
 }
\ No newline at end of file
@@ -0,0 +1,96 @@
+//! Limits (and leniency) applied while deserializing AMF0 values.  Values usually
+//! come straight from a remote peer, so without limits a single message could
+//! claim huge string lengths or nest objects deep enough to exhaust memory or the
+//! stack.
+
+use std::usize;
+use amf3::{Amf3DecodeLimits, Amf3DeserializationError};
+use errors::Amf0DeserializationError;
+
+/// Bounds on the values accepted by `deserialize_with_limits` and `Amf0Decoder`.
+/// Exceeding any of them aborts deserialization with the matching
+/// `Amf0DeserializationError`.
+#[derive(Clone, Debug, PartialEq)]
+pub struct Amf0DecodeLimits {
+    /// How many objects, ECMA arrays, typed objects and strict arrays can be
+    /// nested inside each other
+    pub max_depth: usize,
+
+    /// Maximum length in bytes of any string, property name or class name
+    pub max_string_length: usize,
+
+    /// Maximum number of properties in a single object, ECMA array or typed object
+    /// (also applies to the number of values in a strict array)
+    pub max_properties_per_object: usize,
+
+    /// Maximum number of values decoded in total, including nested values
+    pub max_total_values: usize,
+
+    /// Maximum number of bytes of strings, byte arrays and vectors a single AVM+
+    /// (AMF3) value can decode to, counting every copy made when AMF3 references
+    /// are resolved
+    pub max_amf3_bytes: usize,
+
+    /// When set, object properties holding an unsupported (0x0D) or XML
+    /// document (0x0F) value are left out of the object instead of failing the
+    /// whole deserialization.  Other unknown markers still fail, since there is
+    /// no way to tell where their values end.
+    pub skip_unknown_values: bool,
+}
+
+impl Amf0DecodeLimits {
+    /// Limits that accept any input, matching the behavior of deserializers
+    /// that do not enforce limits
+    pub fn unlimited() -> Amf0DecodeLimits {
+        Amf0DecodeLimits {
+            max_depth: usize::MAX,
+            max_string_length: usize::MAX,
+            max_properties_per_object: usize::MAX,
+            max_total_values: usize::MAX,
+            max_amf3_bytes: usize::MAX,
+            skip_unknown_values: false,
+        }
+    }
+}
+
+impl Default for Amf0DecodeLimits {
+    /// Limits that comfortably fit RTMP commands and metadata
+    fn default() -> Amf0DecodeLimits {
+        Amf0DecodeLimits {
+            max_depth: 32,
+            max_string_length: 1024 * 1024,
+            max_properties_per_object: 1024,
+            max_total_values: 16 * 1024,
+            max_amf3_bytes: 16 * 1024 * 1024,
+            skip_unknown_values: false,
+        }
+    }
+}
+
+/// Limits for an AVM+ value found `depth` levels deep after `total_values` values
+/// (including the AVM+ marker itself) were decoded.  The AMF3 value gets whatever
+/// depth and values are left, plus one value since it counts itself again.
+pub fn get_amf3_limits(limits: &Amf0DecodeLimits, depth: usize, total_values: usize) -> Amf3DecodeLimits {
+    Amf3DecodeLimits {
+        max_depth: limits.max_depth - depth,
+        max_string_length: limits.max_string_length,
+        max_total_values: limits.max_total_values - total_values + 1,
+        max_total_bytes: limits.max_amf3_bytes,
+    }
+}
+
+/// Reports AMF3 limit errors against the AMF0 limits they were derived from
+pub fn convert_amf3_error(err: Amf3DeserializationError, limits: &Amf0DecodeLimits) -> Amf0DeserializationError {
+    match err {
+        Amf3DeserializationError::MaxDepthExceeded(_)
+            => Amf0DeserializationError::MaxDepthExceeded(limits.max_depth),
+
+        Amf3DeserializationError::TooManyValues(_)
+            => Amf0DeserializationError::TooManyValues(limits.max_total_values),
+
+        Amf3DeserializationError::StringTooLong { length, limit }
+            => Amf0DeserializationError::StringTooLong { length: length, limit: limit },
+
+        err => Amf0DeserializationError::from(err)
+    }
+}
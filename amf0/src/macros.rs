@@ -0,0 +1,28 @@
+/// Creates an `Amf0Value::Object` with properties in the order they are listed.
+/// Values can be anything that converts into an `Amf0Value`.
+///
+/// # Examples
+/// ```
+/// #[macro_use] extern crate amf0;
+///
+/// # fn main() {
+/// let info = amf0_object!{
+///     "level" => "status",
+///     "code" => "NetConnection.Connect.Success",
+///     "objectEncoding" => 0.0,
+/// };
+///
+/// assert_eq!(info.get("code").and_then(|x| x.as_str()), Some("NetConnection.Connect.Success"));
+/// # }
+/// ```
+#[macro_export]
+macro_rules! amf0_object {
+    ($($name:expr => $value:expr),* $(,)*) => {{
+        let mut properties = $crate::Amf0Properties::new();
+        $(
+            properties.insert($name.to_string(), $crate::Amf0Value::from($value));
+        )*
+
+        $crate::Amf0Value::Object(properties)
+    }};
+}
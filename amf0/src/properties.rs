@@ -0,0 +1,209 @@
+//! Ordered collection of named values used by AMF0 objects, ECMA arrays and
+//! typed objects.  Properties keep the order they were inserted in (or the order
+//! they were read off the wire), so serializing the same object always produces
+//! the same bytes.
+
+use std::collections::HashMap;
+use std::fmt;
+use std::iter::FromIterator;
+use std::slice;
+use std::vec;
+use Amf0Value;
+
+/// Insertion ordered map of property names to values.  Lookups by name go through
+/// a hash index so they stay constant time.
+pub struct Amf0Properties {
+    entries: Vec<(String, Amf0Value)>,
+    indexes: HashMap<String, usize>
+}
+
+impl Amf0Properties {
+    pub fn new() -> Amf0Properties {
+        Amf0Properties {
+            entries: Vec::new(),
+            indexes: HashMap::new()
+        }
+    }
+
+    pub fn len(&self) -> usize {
+        self.entries.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.entries.is_empty()
+    }
+
+    /// Adds a property to the end of the collection.  If a property with the same
+    /// name already exists its value is replaced in place (keeping its position)
+    /// and the old value is returned.
+    pub fn insert(&mut self, name: String, value: Amf0Value) -> Option<Amf0Value> {
+        if let Some(&index) = self.indexes.get(&name) {
+            let old_value = ::std::mem::replace(&mut self.entries[index].1, value);
+            return Some(old_value);
+        }
+
+        self.indexes.insert(name.clone(), self.entries.len());
+        self.entries.push((name, value));
+        None
+    }
+
+    pub fn get(&self, name: &str) -> Option<&Amf0Value> {
+        match self.indexes.get(name) {
+            Some(&index) => Some(&self.entries[index].1),
+            None => None
+        }
+    }
+
+    pub fn get_mut(&mut self, name: &str) -> Option<&mut Amf0Value> {
+        match self.indexes.get(name) {
+            Some(&index) => Some(&mut self.entries[index].1),
+            None => None
+        }
+    }
+
+    pub fn contains_key(&self, name: &str) -> bool {
+        self.indexes.contains_key(name)
+    }
+
+    /// Removes a property while preserving the order of the remaining properties
+    pub fn remove(&mut self, name: &str) -> Option<Amf0Value> {
+        let index = match self.indexes.remove(name) {
+            Some(index) => index,
+            None => return None
+        };
+
+        let (_, value) = self.entries.remove(index);
+        for &(ref entry_name, _) in &self.entries[index..] {
+            if let Some(entry_index) = self.indexes.get_mut(entry_name) {
+                *entry_index -= 1;
+            }
+        }
+
+        Some(value)
+    }
+
+    /// Iterates over the properties in order
+    pub fn iter<'a>(&'a self) -> Iter<'a> {
+        Iter { inner: self.entries.iter() }
+    }
+}
+
+pub struct Iter<'a> {
+    inner: slice::Iter<'a, (String, Amf0Value)>
+}
+
+impl<'a> Iterator for Iter<'a> {
+    type Item = (&'a String, &'a Amf0Value);
+
+    fn next(&mut self) -> Option<Self::Item> {
+        self.inner.next().map(|&(ref name, ref value)| (name, value))
+    }
+}
+
+impl<'a> IntoIterator for &'a Amf0Properties {
+    type Item = (&'a String, &'a Amf0Value);
+    type IntoIter = Iter<'a>;
+
+    fn into_iter(self) -> Iter<'a> {
+        self.iter()
+    }
+}
+
+impl IntoIterator for Amf0Properties {
+    type Item = (String, Amf0Value);
+    type IntoIter = vec::IntoIter<(String, Amf0Value)>;
+
+    fn into_iter(self) -> Self::IntoIter {
+        self.entries.into_iter()
+    }
+}
+
+impl FromIterator<(String, Amf0Value)> for Amf0Properties {
+    fn from_iter<I: IntoIterator<Item = (String, Amf0Value)>>(iter: I) -> Self {
+        let mut properties = Amf0Properties::new();
+        for (name, value) in iter {
+            properties.insert(name, value);
+        }
+
+        properties
+    }
+}
+
+/// Properties are only equal if they have the same values in the same order
+impl PartialEq for Amf0Properties {
+    fn eq(&self, other: &Amf0Properties) -> bool {
+        self.entries == other.entries
+    }
+}
+
+impl fmt::Debug for Amf0Properties {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.debug_map().entries(self.iter()).finish()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::Amf0Properties;
+    use Amf0Value;
+
+    #[test]
+    fn properties_are_iterated_in_insertion_order() {
+        let mut properties = Amf0Properties::new();
+        properties.insert("z".to_string(), Amf0Value::Number(1.0));
+        properties.insert("a".to_string(), Amf0Value::Number(2.0));
+        properties.insert("m".to_string(), Amf0Value::Number(3.0));
+
+        let names: Vec<&String> = properties.iter().map(|(name, _)| name).collect();
+        assert_eq!(names, vec!["z", "a", "m"]);
+    }
+
+    #[test]
+    fn inserting_existing_name_replaces_value_in_place() {
+        let mut properties = Amf0Properties::new();
+        properties.insert("a".to_string(), Amf0Value::Number(1.0));
+        properties.insert("b".to_string(), Amf0Value::Number(2.0));
+
+        let old_value = properties.insert("a".to_string(), Amf0Value::Null);
+
+        assert_eq!(old_value, Some(Amf0Value::Number(1.0)));
+        assert_eq!(properties.len(), 2);
+
+        let names: Vec<&String> = properties.iter().map(|(name, _)| name).collect();
+        assert_eq!(names, vec!["a", "b"]);
+        assert_eq!(properties.get("a"), Some(&Amf0Value::Null));
+    }
+
+    #[test]
+    fn removing_property_keeps_order_and_lookups_of_remaining_properties() {
+        let mut properties = Amf0Properties::new();
+        properties.insert("a".to_string(), Amf0Value::Number(1.0));
+        properties.insert("b".to_string(), Amf0Value::Number(2.0));
+        properties.insert("c".to_string(), Amf0Value::Number(3.0));
+
+        let removed = properties.remove("a");
+
+        assert_eq!(removed, Some(Amf0Value::Number(1.0)));
+        assert_eq!(properties.get("a"), None);
+        assert_eq!(properties.get("b"), Some(&Amf0Value::Number(2.0)));
+        assert_eq!(properties.get("c"), Some(&Amf0Value::Number(3.0)));
+
+        let names: Vec<&String> = properties.iter().map(|(name, _)| name).collect();
+        assert_eq!(names, vec!["b", "c"]);
+    }
+
+    #[test]
+    fn equality_depends_on_order() {
+        let first: Amf0Properties = vec![
+            ("a".to_string(), Amf0Value::Null),
+            ("b".to_string(), Amf0Value::Null),
+        ].into_iter().collect();
+
+        let second: Amf0Properties = vec![
+            ("b".to_string(), Amf0Value::Null),
+            ("a".to_string(), Amf0Value::Null),
+        ].into_iter().collect();
+
+        assert!(first != second);
+    }
+}
@@ -0,0 +1,313 @@
+//! Serde serializer that turns any `Serialize` type into an `Amf0Value` tree.
+//!
+//! Structs become AMF0 objects (with properties in field order), maps become
+//! ECMA arrays, sequences and tuples become strict arrays, and all numeric types
+//! become AMF0 numbers.  Enum variants with data are written as an object with a
+//! single property named after the variant.
+
+use serde::ser;
+use serde::ser::Serialize;
+use {Amf0Value, Amf0Properties};
+use errors::Amf0SerdeError;
+use serialization::serialize;
+
+/// Converts a serializable value into an `Amf0Value`
+pub fn to_amf0<T: ?Sized + Serialize>(value: &T) -> Result<Amf0Value, Amf0SerdeError> {
+    value.serialize(Amf0ValueSerializer)
+}
+
+/// Converts a serializable value into AMF0 encoded bytes
+pub fn to_amf0_bytes<T: ?Sized + Serialize>(value: &T) -> Result<Vec<u8>, Amf0SerdeError> {
+    let amf0_value = try!(to_amf0(value));
+    let bytes = try!(serialize(&vec![amf0_value]));
+    Ok(bytes)
+}
+
+impl ser::Error for Amf0SerdeError {
+    fn custom<T: ::std::fmt::Display>(message: T) -> Self {
+        Amf0SerdeError::Message(message.to_string())
+    }
+}
+
+struct Amf0ValueSerializer;
+
+impl ser::Serializer for Amf0ValueSerializer {
+    type Ok = Amf0Value;
+    type Error = Amf0SerdeError;
+
+    type SerializeSeq = SeqSerializer;
+    type SerializeTuple = SeqSerializer;
+    type SerializeTupleStruct = SeqSerializer;
+    type SerializeTupleVariant = TupleVariantSerializer;
+    type SerializeMap = MapSerializer;
+    type SerializeStruct = StructSerializer;
+    type SerializeStructVariant = StructVariantSerializer;
+
+    fn serialize_bool(self, value: bool) -> Result<Amf0Value, Amf0SerdeError> {
+        Ok(Amf0Value::Boolean(value))
+    }
+
+    fn serialize_i8(self, value: i8) -> Result<Amf0Value, Amf0SerdeError> {
+        Ok(Amf0Value::Number(value as f64))
+    }
+
+    fn serialize_i16(self, value: i16) -> Result<Amf0Value, Amf0SerdeError> {
+        Ok(Amf0Value::Number(value as f64))
+    }
+
+    fn serialize_i32(self, value: i32) -> Result<Amf0Value, Amf0SerdeError> {
+        Ok(Amf0Value::Number(value as f64))
+    }
+
+    fn serialize_i64(self, value: i64) -> Result<Amf0Value, Amf0SerdeError> {
+        Ok(Amf0Value::Number(value as f64))
+    }
+
+    fn serialize_u8(self, value: u8) -> Result<Amf0Value, Amf0SerdeError> {
+        Ok(Amf0Value::Number(value as f64))
+    }
+
+    fn serialize_u16(self, value: u16) -> Result<Amf0Value, Amf0SerdeError> {
+        Ok(Amf0Value::Number(value as f64))
+    }
+
+    fn serialize_u32(self, value: u32) -> Result<Amf0Value, Amf0SerdeError> {
+        Ok(Amf0Value::Number(value as f64))
+    }
+
+    fn serialize_u64(self, value: u64) -> Result<Amf0Value, Amf0SerdeError> {
+        Ok(Amf0Value::Number(value as f64))
+    }
+
+    fn serialize_f32(self, value: f32) -> Result<Amf0Value, Amf0SerdeError> {
+        Ok(Amf0Value::Number(value as f64))
+    }
+
+    fn serialize_f64(self, value: f64) -> Result<Amf0Value, Amf0SerdeError> {
+        Ok(Amf0Value::Number(value))
+    }
+
+    fn serialize_char(self, value: char) -> Result<Amf0Value, Amf0SerdeError> {
+        Ok(Amf0Value::Utf8String(value.to_string()))
+    }
+
+    fn serialize_str(self, value: &str) -> Result<Amf0Value, Amf0SerdeError> {
+        Ok(Amf0Value::Utf8String(value.to_string()))
+    }
+
+    fn serialize_bytes(self, value: &[u8]) -> Result<Amf0Value, Amf0SerdeError> {
+        // AMF0 has no byte array type
+        let values = value.iter().map(|x| Amf0Value::Number(*x as f64)).collect();
+        Ok(Amf0Value::StrictArray(values))
+    }
+
+    fn serialize_none(self) -> Result<Amf0Value, Amf0SerdeError> {
+        Ok(Amf0Value::Null)
+    }
+
+    fn serialize_some<T: ?Sized + Serialize>(self, value: &T) -> Result<Amf0Value, Amf0SerdeError> {
+        value.serialize(self)
+    }
+
+    fn serialize_unit(self) -> Result<Amf0Value, Amf0SerdeError> {
+        Ok(Amf0Value::Null)
+    }
+
+    fn serialize_unit_struct(self, _name: &'static str) -> Result<Amf0Value, Amf0SerdeError> {
+        Ok(Amf0Value::Null)
+    }
+
+    fn serialize_unit_variant(self, _name: &'static str, _index: u32, variant: &'static str) -> Result<Amf0Value, Amf0SerdeError> {
+        Ok(Amf0Value::Utf8String(variant.to_string()))
+    }
+
+    fn serialize_newtype_struct<T: ?Sized + Serialize>(self, _name: &'static str, value: &T) -> Result<Amf0Value, Amf0SerdeError> {
+        value.serialize(self)
+    }
+
+    fn serialize_newtype_variant<T: ?Sized + Serialize>(self,
+                                                        _name: &'static str,
+                                                        _index: u32,
+                                                        variant: &'static str,
+                                                        value: &T) -> Result<Amf0Value, Amf0SerdeError> {
+        let mut properties = Amf0Properties::new();
+        properties.insert(variant.to_string(), try!(to_amf0(value)));
+        Ok(Amf0Value::Object(properties))
+    }
+
+    fn serialize_seq(self, length: Option<usize>) -> Result<SeqSerializer, Amf0SerdeError> {
+        Ok(SeqSerializer { values: Vec::with_capacity(length.unwrap_or(0)) })
+    }
+
+    fn serialize_tuple(self, length: usize) -> Result<SeqSerializer, Amf0SerdeError> {
+        self.serialize_seq(Some(length))
+    }
+
+    fn serialize_tuple_struct(self, _name: &'static str, length: usize) -> Result<SeqSerializer, Amf0SerdeError> {
+        self.serialize_seq(Some(length))
+    }
+
+    fn serialize_tuple_variant(self,
+                               _name: &'static str,
+                               _index: u32,
+                               variant: &'static str,
+                               length: usize) -> Result<TupleVariantSerializer, Amf0SerdeError> {
+        Ok(TupleVariantSerializer { variant: variant, values: Vec::with_capacity(length) })
+    }
+
+    fn serialize_map(self, _length: Option<usize>) -> Result<MapSerializer, Amf0SerdeError> {
+        Ok(MapSerializer { properties: Amf0Properties::new(), next_key: None })
+    }
+
+    fn serialize_struct(self, _name: &'static str, _length: usize) -> Result<StructSerializer, Amf0SerdeError> {
+        Ok(StructSerializer { properties: Amf0Properties::new() })
+    }
+
+    fn serialize_struct_variant(self,
+                                _name: &'static str,
+                                _index: u32,
+                                variant: &'static str,
+                                _length: usize) -> Result<StructVariantSerializer, Amf0SerdeError> {
+        Ok(StructVariantSerializer { variant: variant, properties: Amf0Properties::new() })
+    }
+}
+
+struct SeqSerializer {
+    values: Vec<Amf0Value>
+}
+
+impl ser::SerializeSeq for SeqSerializer {
+    type Ok = Amf0Value;
+    type Error = Amf0SerdeError;
+
+    fn serialize_element<T: ?Sized + Serialize>(&mut self, value: &T) -> Result<(), Amf0SerdeError> {
+        self.values.push(try!(to_amf0(value)));
+        Ok(())
+    }
+
+    fn end(self) -> Result<Amf0Value, Amf0SerdeError> {
+        Ok(Amf0Value::StrictArray(self.values))
+    }
+}
+
+impl ser::SerializeTuple for SeqSerializer {
+    type Ok = Amf0Value;
+    type Error = Amf0SerdeError;
+
+    fn serialize_element<T: ?Sized + Serialize>(&mut self, value: &T) -> Result<(), Amf0SerdeError> {
+        ser::SerializeSeq::serialize_element(self, value)
+    }
+
+    fn end(self) -> Result<Amf0Value, Amf0SerdeError> {
+        ser::SerializeSeq::end(self)
+    }
+}
+
+impl ser::SerializeTupleStruct for SeqSerializer {
+    type Ok = Amf0Value;
+    type Error = Amf0SerdeError;
+
+    fn serialize_field<T: ?Sized + Serialize>(&mut self, value: &T) -> Result<(), Amf0SerdeError> {
+        ser::SerializeSeq::serialize_element(self, value)
+    }
+
+    fn end(self) -> Result<Amf0Value, Amf0SerdeError> {
+        ser::SerializeSeq::end(self)
+    }
+}
+
+struct TupleVariantSerializer {
+    variant: &'static str,
+    values: Vec<Amf0Value>
+}
+
+impl ser::SerializeTupleVariant for TupleVariantSerializer {
+    type Ok = Amf0Value;
+    type Error = Amf0SerdeError;
+
+    fn serialize_field<T: ?Sized + Serialize>(&mut self, value: &T) -> Result<(), Amf0SerdeError> {
+        self.values.push(try!(to_amf0(value)));
+        Ok(())
+    }
+
+    fn end(self) -> Result<Amf0Value, Amf0SerdeError> {
+        let mut properties = Amf0Properties::new();
+        properties.insert(self.variant.to_string(), Amf0Value::StrictArray(self.values));
+        Ok(Amf0Value::Object(properties))
+    }
+}
+
+struct MapSerializer {
+    properties: Amf0Properties,
+    next_key: Option<String>
+}
+
+impl ser::SerializeMap for MapSerializer {
+    type Ok = Amf0Value;
+    type Error = Amf0SerdeError;
+
+    fn serialize_key<T: ?Sized + Serialize>(&mut self, key: &T) -> Result<(), Amf0SerdeError> {
+        // Property names must be strings, but allow numeric keys by formatting them
+        let name = match try!(to_amf0(key)) {
+            Amf0Value::Utf8String(name) => name,
+            Amf0Value::Number(number) => number.to_string(),
+            _ => return Err(Amf0SerdeError::Message("AMF0 property names must be strings".to_string()))
+        };
+
+        self.next_key = Some(name);
+        Ok(())
+    }
+
+    fn serialize_value<T: ?Sized + Serialize>(&mut self, value: &T) -> Result<(), Amf0SerdeError> {
+        let name = match self.next_key.take() {
+            Some(name) => name,
+            None => return Err(Amf0SerdeError::Message("Map value serialized before its key".to_string()))
+        };
+
+        self.properties.insert(name, try!(to_amf0(value)));
+        Ok(())
+    }
+
+    fn end(self) -> Result<Amf0Value, Amf0SerdeError> {
+        Ok(Amf0Value::EcmaArray(self.properties))
+    }
+}
+
+struct StructSerializer {
+    properties: Amf0Properties
+}
+
+impl ser::SerializeStruct for StructSerializer {
+    type Ok = Amf0Value;
+    type Error = Amf0SerdeError;
+
+    fn serialize_field<T: ?Sized + Serialize>(&mut self, name: &'static str, value: &T) -> Result<(), Amf0SerdeError> {
+        self.properties.insert(name.to_string(), try!(to_amf0(value)));
+        Ok(())
+    }
+
+    fn end(self) -> Result<Amf0Value, Amf0SerdeError> {
+        Ok(Amf0Value::Object(self.properties))
+    }
+}
+
+struct StructVariantSerializer {
+    variant: &'static str,
+    properties: Amf0Properties
+}
+
+impl ser::SerializeStructVariant for StructVariantSerializer {
+    type Ok = Amf0Value;
+    type Error = Amf0SerdeError;
+
+    fn serialize_field<T: ?Sized + Serialize>(&mut self, name: &'static str, value: &T) -> Result<(), Amf0SerdeError> {
+        self.properties.insert(name.to_string(), try!(to_amf0(value)));
+        Ok(())
+    }
+
+    fn end(self) -> Result<Amf0Value, Amf0SerdeError> {
+        let mut properties = Amf0Properties::new();
+        properties.insert(self.variant.to_string(), Amf0Value::Object(self.properties));
+        Ok(Amf0Value::Object(properties))
+    }
+}
@@ -2,8 +2,9 @@
 //! bytes based on the AMF0 specification 
 //! (http://wwwimages.adobe.com/content/dam/Adobe/en/devnet/amf/pdf/amf0-file-format-specification.pdf)
 
-use std::collections::HashMap;
-use Amf0Value;
+use {Amf0Value, Amf0Properties};
+use amf3;
+use amf3::Amf3Value;
 use errors::Amf0SerializationError;
 use markers;
 use byteorder::{BigEndian, WriteBytesExt};
@@ -24,7 +25,14 @@ fn serialize_value(value: &Amf0Value, bytes: &mut Vec<u8>) -> Result<(), Amf0Ser
         Amf0Value::Null => Ok(serialize_null(bytes)),
         Amf0Value::Number(ref val) => serialize_number(&val, bytes),
         Amf0Value::Utf8String(ref val) => serialize_string(&val, bytes),
-        Amf0Value::Object(ref val) => serialize_object(&val, bytes)
+        Amf0Value::Object(ref val) => serialize_object(&val, bytes),
+        Amf0Value::EcmaArray(ref val) => serialize_ecma_array(&val, bytes),
+        Amf0Value::StrictArray(ref val) => serialize_strict_array(&val, bytes),
+        Amf0Value::Date { unix_time, time_zone } => serialize_date(unix_time, time_zone, bytes),
+        Amf0Value::Undefined => Ok(serialize_undefined(bytes)),
+        Amf0Value::Reference(index) => serialize_reference(index, bytes),
+        Amf0Value::TypedObject { ref class_name, ref properties } => serialize_typed_object(class_name, properties, bytes),
+        Amf0Value::AvmPlus(ref val) => serialize_avmplus(val, bytes)
     }
 }
 
@@ -40,11 +48,23 @@ fn serialize_bool(value: &bool, bytes: &mut Vec<u8>) {
 }
 
 fn serialize_string(value: &String, bytes: &mut Vec<u8>) -> Result<(), Amf0SerializationError> {
+    if value.len() > (u16::max_value() as usize) {
+        bytes.push(markers::LONG_STRING_MARKER);
+        try!(bytes.write_u32::<BigEndian>(value.len() as u32));
+    } else {
+        bytes.push(markers::STRING_MARKER);
+        try!(bytes.write_u16::<BigEndian>(value.len() as u16));
+    }
+
+    bytes.extend(value.as_bytes());
+    Ok(())
+}
+
+fn serialize_normal_string_value(value: &String, bytes: &mut Vec<u8>) -> Result<(), Amf0SerializationError> {
     if value.len() > (u16::max_value() as usize) {
         return Err(Amf0SerializationError::NormalStringTooLong)
     }
 
-    bytes.push(markers::STRING_MARKER);
     try!(bytes.write_u16::<BigEndian>(value.len() as u16));
     bytes.extend(value.as_bytes());
     Ok(())
@@ -54,13 +74,60 @@ fn serialize_null(bytes: &mut Vec<u8>) {
     bytes.push(markers::NULL_MARKER);
 }
 
-fn serialize_object(properties: &HashMap<String, Amf0Value>, bytes: &mut Vec<u8>) -> Result<(), Amf0SerializationError> {
+fn serialize_undefined(bytes: &mut Vec<u8>) {
+    bytes.push(markers::UNDEFINED_MARKER);
+}
+
+fn serialize_reference(index: u16, bytes: &mut Vec<u8>) -> Result<(), Amf0SerializationError> {
+    bytes.push(markers::REFERENCE_MARKER);
+    try!(bytes.write_u16::<BigEndian>(index));
+    Ok(())
+}
+
+fn serialize_object(properties: &Amf0Properties, bytes: &mut Vec<u8>) -> Result<(), Amf0SerializationError> {
     bytes.push(markers::OBJECT_MARKER);
+    serialize_properties(properties, bytes)
+}
+
+fn serialize_avmplus(value: &Amf3Value, bytes: &mut Vec<u8>) -> Result<(), Amf0SerializationError> {
+    bytes.push(markers::AVMPLUS_OBJECT_MARKER);
+    try!(amf3::serialize_value(value, bytes));
+    Ok(())
+}
 
+fn serialize_typed_object(class_name: &String, properties: &Amf0Properties, bytes: &mut Vec<u8>) -> Result<(), Amf0SerializationError> {
+    bytes.push(markers::TYPED_OBJECT_MARKER);
+    try!(serialize_normal_string_value(class_name, bytes));
+    serialize_properties(properties, bytes)
+}
+
+fn serialize_ecma_array(properties: &Amf0Properties, bytes: &mut Vec<u8>) -> Result<(), Amf0SerializationError> {
+    bytes.push(markers::ECMA_ARRAY_MARKER);
+    try!(bytes.write_u32::<BigEndian>(properties.len() as u32));
+    serialize_properties(properties, bytes)
+}
+
+fn serialize_strict_array(values: &Vec<Amf0Value>, bytes: &mut Vec<u8>) -> Result<(), Amf0SerializationError> {
+    bytes.push(markers::STRICT_ARRAY_MARKER);
+    try!(bytes.write_u32::<BigEndian>(values.len() as u32));
+
+    for value in values {
+        try!(serialize_value(value, bytes));
+    }
+
+    Ok(())
+}
+
+fn serialize_date(unix_time: f64, time_zone: i16, bytes: &mut Vec<u8>) -> Result<(), Amf0SerializationError> {
+    bytes.push(markers::DATE_MARKER);
+    try!(bytes.write_f64::<BigEndian>(unix_time));
+    try!(bytes.write_i16::<BigEndian>(time_zone));
+    Ok(())
+}
+
+fn serialize_properties(properties: &Amf0Properties, bytes: &mut Vec<u8>) -> Result<(), Amf0SerializationError> {
     for (name, value) in properties {
-        // TODO: Add check that property name isn't greater than a u16
-        try!(bytes.write_u16::<BigEndian>(name.len() as u16));
-        bytes.extend(name.as_bytes());
+        try!(serialize_normal_string_value(name, bytes));
         try!(serialize_value(&value, bytes));
     }
 
@@ -71,10 +138,11 @@ fn serialize_object(properties: &HashMap<String, Amf0Value>, bytes: &mut Vec<u8>
 
 #[cfg(test)]
 mod tests {
-    use std::collections::HashMap;
+    use Amf0Properties;
     use super::serialize;
     use super::super::Amf0Value;
     use super::super::errors::Amf0SerializationError;
+    use amf3::Amf3Value;
     use markers;
     use byteorder::{BigEndian, WriteBytesExt};
 
@@ -147,7 +215,7 @@ mod tests {
     fn can_serialize_object() {
         const NUMBER: f64 = 332.0;
 
-        let mut properties = HashMap::new();
+        let mut properties = Amf0Properties::new();
         properties.insert("test".to_string(), Amf0Value::Number(NUMBER));
 
         let input = vec![Amf0Value::Object(properties)];
@@ -166,14 +234,163 @@ mod tests {
     }
 
     #[test]
-    fn error_when_string_length_greater_than_u16() {
+    fn object_properties_serialized_in_insertion_order() {
+        let mut properties = Amf0Properties::new();
+        properties.insert("level".to_string(), Amf0Value::Null);
+        properties.insert("code".to_string(), Amf0Value::Null);
+        properties.insert("description".to_string(), Amf0Value::Null);
+
+        let input = vec![Amf0Value::Object(properties)];
+        let result = serialize(&input).unwrap();
+
+        let mut expected = vec![];
+        expected.push(markers::OBJECT_MARKER);
+        for name in vec!["level", "code", "description"] {
+            expected.write_u16::<BigEndian>(name.len() as u16).unwrap();
+            expected.extend(name.as_bytes());
+            expected.push(markers::NULL_MARKER);
+        }
+
+        expected.write_u16::<BigEndian>(markers::UTF_8_EMPTY_MARKER).unwrap();
+        expected.push(markers::OBJECT_END_MARKER);
+
+        assert_eq!(result, expected);
+    }
+
+    #[test]
+    fn can_serialize_ecma_array() {
+        const NUMBER: f64 = 332.0;
+
+        let mut properties = Amf0Properties::new();
+        properties.insert("test".to_string(), Amf0Value::Number(NUMBER));
+
+        let input = vec![Amf0Value::EcmaArray(properties)];
+        let result = serialize(&input).unwrap();
+
+        let mut expected = vec![];
+        expected.push(markers::ECMA_ARRAY_MARKER);
+        expected.write_u32::<BigEndian>(1).unwrap();
+        expected.write_u16::<BigEndian>(4).unwrap();
+        expected.extend("test".as_bytes());
+        expected.push(markers::NUMBER_MARKER);
+        expected.write_f64::<BigEndian>(NUMBER).unwrap();
+        expected.write_u16::<BigEndian>(markers::UTF_8_EMPTY_MARKER).unwrap();
+        expected.push(markers::OBJECT_END_MARKER);
+
+        assert_eq!(result, expected);
+    }
+
+    #[test]
+    fn can_serialize_strict_array() {
+        let input = vec![Amf0Value::StrictArray(vec![Amf0Value::Number(15.0), Amf0Value::Boolean(true)])];
+        let result = serialize(&input).unwrap();
+
+        let mut expected = vec![];
+        expected.push(markers::STRICT_ARRAY_MARKER);
+        expected.write_u32::<BigEndian>(2).unwrap();
+        expected.push(markers::NUMBER_MARKER);
+        expected.write_f64::<BigEndian>(15.0).unwrap();
+        expected.push(markers::BOOLEAN_MARKER);
+        expected.push(1);
+
+        assert_eq!(result, expected);
+    }
+
+    #[test]
+    fn can_serialize_date() {
+        let input = vec![Amf0Value::Date { unix_time: 1474215410000.0, time_zone: 0 }];
+        let result = serialize(&input).unwrap();
+
+        let mut expected = vec![];
+        expected.push(markers::DATE_MARKER);
+        expected.write_f64::<BigEndian>(1474215410000.0).unwrap();
+        expected.write_i16::<BigEndian>(0).unwrap();
+
+        assert_eq!(result, expected);
+    }
+
+    #[test]
+    fn can_serialize_undefined() {
+        let input = vec![Amf0Value::Undefined];
+        let result = serialize(&input).unwrap();
+
+        let expected = vec![markers::UNDEFINED_MARKER];
+        assert_eq!(result, expected);
+    }
+
+    #[test]
+    fn can_serialize_reference() {
+        let input = vec![Amf0Value::Reference(3)];
+        let result = serialize(&input).unwrap();
+
+        let mut expected = vec![];
+        expected.push(markers::REFERENCE_MARKER);
+        expected.write_u16::<BigEndian>(3).unwrap();
+
+        assert_eq!(result, expected);
+    }
+
+    #[test]
+    fn can_serialize_typed_object() {
+        let mut properties = Amf0Properties::new();
+        properties.insert("test".to_string(), Amf0Value::Undefined);
+
+        let input = vec![Amf0Value::TypedObject { class_name: "Class".to_string(), properties: properties }];
+        let result = serialize(&input).unwrap();
+
+        let mut expected = vec![];
+        expected.push(markers::TYPED_OBJECT_MARKER);
+        expected.write_u16::<BigEndian>(5).unwrap();
+        expected.extend("Class".as_bytes());
+        expected.write_u16::<BigEndian>(4).unwrap();
+        expected.extend("test".as_bytes());
+        expected.push(markers::UNDEFINED_MARKER);
+        expected.write_u16::<BigEndian>(markers::UTF_8_EMPTY_MARKER).unwrap();
+        expected.push(markers::OBJECT_END_MARKER);
+
+        assert_eq!(result, expected);
+    }
+
+    #[test]
+    fn can_serialize_avmplus_value() {
+        let input = vec![Amf0Value::AvmPlus(Amf3Value::Integer(5))];
+        let result = serialize(&input).unwrap();
+
+        let expected = vec![markers::AVMPLUS_OBJECT_MARKER, 0x04, 0x05];
+        assert_eq!(result, expected);
+    }
+
+    #[test]
+    fn long_string_used_when_string_length_greater_than_u16() {
         let mut value = String::new();
         let max = (u16::max_value() as u32) + 1;
         for _ in 0..max {
             value.push('a');
         }
 
-        let input = vec![Amf0Value::Utf8String(value)];
+        let input = vec![Amf0Value::Utf8String(value.clone())];
+        let result = serialize(&input).unwrap();
+
+        let mut expected = vec![];
+        expected.push(markers::LONG_STRING_MARKER);
+        expected.write_u32::<BigEndian>(max).unwrap();
+        expected.extend(value.as_bytes());
+
+        assert_eq!(result, expected);
+    }
+
+    #[test]
+    fn error_when_property_name_length_greater_than_u16() {
+        let mut name = String::new();
+        let max = (u16::max_value() as u32) + 1;
+        for _ in 0..max {
+            name.push('a');
+        }
+
+        let mut properties = Amf0Properties::new();
+        properties.insert(name, Amf0Value::Null);
+
+        let input = vec![Amf0Value::Object(properties)];
         let result = serialize(&input);
 
         assert!(match result { 
@@ -181,4 +398,4 @@ mod tests {
             _ => false}
         );
     }
-}
\ No newline at end of file
+}
@@ -0,0 +1,1153 @@
+//! This module contains functionality to deserialize values from bytes
+//! that were encoded via the AMF3 specification
+//! (http://wwwimages.adobe.com/www.adobe.com/content/dam/Adobe/en/devnet/amf/pdf/amf-file-format-spec.pdf)
+
+use std::io::Read;
+use std::rc::Rc;
+use super::Amf3Value;
+use super::errors::Amf3DeserializationError;
+use limits::Amf3DecodeLimits;
+use markers;
+use byteorder::{BigEndian, ReadBytesExt};
+
+struct Traits {
+    class_name: Option<String>,
+    sealed_names: Vec<String>,
+    is_dynamic: bool,
+    byte_count: usize
+}
+
+/// A value as it is being decoded.  Values that can be referenced are kept in an
+/// `Rc` that the object table shares, so resolving a reference (or storing the
+/// value in the first place) does not copy it.  Copies are only made once the
+/// whole input has been read, when nodes are turned into `Amf3Value`s.
+enum Node {
+    Value(Amf3Value),
+    Shared(Rc<Node>),
+    Array { associative_values: Vec<(String, Node)>, dense_values: Vec<Node> },
+    Object { class_name: Option<String>, sealed_properties: Vec<(String, Node)>, dynamic_properties: Option<Vec<(String, Node)>> },
+    ObjectVector { fixed_length: bool, type_name: String, values: Vec<Node> },
+    Dictionary { weak_keys: bool, entries: Vec<(Node, Node)> },
+}
+
+/// An entry in the object table.  The `first_*` and `start_depth` fields record
+/// the decode progress when the object was reserved, which is turned into how
+/// many values and bytes the object holds, and how deeply nested it is, once it
+/// has been read.  Resolving a reference to the object counts all of them again.
+struct StoredObject {
+    value: Option<Rc<Node>>,
+    first_value: usize,
+    value_count: usize,
+    first_byte: usize,
+    byte_count: usize,
+    start_depth: usize,
+    outer_deepest: usize,
+    height: usize
+}
+
+/// Reference tables that AMF3 values can point back into, along with the
+/// progress towards the decode limits.  Complex values get a slot in the object
+/// table before their children are read, so a value that references itself (or
+/// a parent) finds an empty slot and is rejected.
+struct Context<'a> {
+    strings: Vec<String>,
+    objects: Vec<StoredObject>,
+    traits: Vec<Traits>,
+    limits: &'a Amf3DecodeLimits,
+    depth: usize,
+    deepest: usize,
+    total_values: usize,
+    total_bytes: usize
+}
+
+impl<'a> Context<'a> {
+    fn new(limits: &'a Amf3DecodeLimits) -> Context<'a> {
+        Context {
+            strings: Vec::new(),
+            objects: Vec::new(),
+            traits: Vec::new(),
+            limits: limits,
+            depth: 0,
+            deepest: 0,
+            total_values: 0,
+            total_bytes: 0
+        }
+    }
+
+    fn reserve_object(&mut self) -> usize {
+        self.objects.push(StoredObject {
+            value: None,
+            first_value: self.total_values,
+            value_count: 0,
+            first_byte: self.total_bytes,
+            byte_count: 0,
+            start_depth: self.depth,
+            outer_deepest: self.deepest,
+            height: 0
+        });
+
+        self.deepest = self.depth;
+        self.objects.len() - 1
+    }
+
+    fn store_object(&mut self, index: usize, node: Node) -> Node {
+        let shared = Rc::new(node);
+        let object = &mut self.objects[index];
+
+        // The object itself was counted right before it was reserved
+        object.value_count = self.total_values - object.first_value + 1;
+        object.byte_count = self.total_bytes - object.first_byte;
+        object.height = self.deepest - object.start_depth;
+        object.value = Some(shared.clone());
+
+        if object.outer_deepest > self.deepest {
+            self.deepest = object.outer_deepest;
+        }
+
+        Node::Shared(shared)
+    }
+
+    /// Resolves an object reference.  The reference was already counted as a
+    /// single value, so the rest of the referenced object's values, its bytes and
+    /// its nesting are counted as if it was read again.
+    fn get_object(&mut self, index: u32) -> Result<Node, Amf3DeserializationError> {
+        let (shared, value_count, byte_count, height) = match self.objects.get(index as usize) {
+            Some(&StoredObject { value: Some(ref value), value_count, byte_count, height, .. })
+                => (value.clone(), value_count, byte_count, height),
+
+            _ => return Err(Amf3DeserializationError::InvalidObjectReference(index))
+        };
+
+        if height > self.limits.max_depth - self.depth {
+            return Err(Amf3DeserializationError::MaxDepthExceeded(self.limits.max_depth));
+        }
+
+        try!(self.count_values(value_count - 1));
+        try!(self.count_bytes(byte_count));
+        if self.depth + height > self.deepest {
+            self.deepest = self.depth + height;
+        }
+
+        Ok(Node::Shared(shared))
+    }
+
+    fn count_values(&mut self, count: usize) -> Result<(), Amf3DeserializationError> {
+        if count > self.limits.max_total_values - self.total_values {
+            return Err(Amf3DeserializationError::TooManyValues(self.limits.max_total_values));
+        }
+
+        self.total_values = self.total_values + count;
+        Ok(())
+    }
+
+    fn count_bytes(&mut self, count: usize) -> Result<(), Amf3DeserializationError> {
+        if count > self.limits.max_total_bytes - self.total_bytes {
+            return Err(Amf3DeserializationError::TooManyBytes(self.limits.max_total_bytes));
+        }
+
+        self.total_bytes = self.total_bytes + count;
+        Ok(())
+    }
+
+    fn enter_nested(&mut self) -> Result<(), Amf3DeserializationError> {
+        if self.depth >= self.limits.max_depth {
+            return Err(Amf3DeserializationError::MaxDepthExceeded(self.limits.max_depth));
+        }
+
+        self.depth = self.depth + 1;
+        if self.depth > self.deepest {
+            self.deepest = self.depth;
+        }
+
+        Ok(())
+    }
+
+    fn exit_nested(&mut self) {
+        self.depth = self.depth - 1;
+    }
+
+    /// Checks the length of a string, XML document or byte array against the
+    /// limits, counting it towards the total bytes
+    fn check_length(&mut self, length: u32) -> Result<usize, Amf3DeserializationError> {
+        let length = length as usize;
+        if length > self.limits.max_string_length {
+            return Err(Amf3DeserializationError::StringTooLong { length: length, limit: self.limits.max_string_length });
+        }
+
+        try!(self.count_bytes(length));
+        Ok(length)
+    }
+}
+
+/// Turns any readable byte stream and converts it into an array of AMF3 values.
+/// All values share the same reference tables.  The default `Amf3DecodeLimits`
+/// are enforced.
+pub fn deserialize(bytes: &mut Read) -> Result<Vec<Amf3Value>, Amf3DeserializationError> {
+    deserialize_with_limits(bytes, &Amf3DecodeLimits::default())
+}
+
+/// Turns any readable byte stream and converts it into an array of AMF3 values,
+/// failing as soon as the input goes over one of the specified limits.
+pub fn deserialize_with_limits(bytes: &mut Read, limits: &Amf3DecodeLimits) -> Result<Vec<Amf3Value>, Amf3DeserializationError> {
+    let mut context = Context::new(limits);
+    let mut nodes = vec![];
+
+    loop {
+        let mut buffer: [u8; 1] = [0];
+        let bytes_read = try!(bytes.read(&mut buffer));
+        if bytes_read == 0 {
+            break;
+        }
+
+        let node = try!(read_value_with_marker(buffer[0], bytes, &mut context));
+        nodes.push(node);
+    }
+
+    // Releasing the object table first lets values that were never referenced be
+    // moved into the results instead of copied
+    drop(context);
+    Ok(nodes.into_iter().map(into_value).collect())
+}
+
+/// Reads a single AMF3 value from the byte stream with its own set of reference
+/// tables.  This is used when an AMF0 stream switches to AMF3 for one value.
+/// The default `Amf3DecodeLimits` are enforced.
+pub fn deserialize_value(bytes: &mut Read) -> Result<Amf3Value, Amf3DeserializationError> {
+    deserialize_value_with_limits(bytes, &Amf3DecodeLimits::default()).map(|(value, _)| value)
+}
+
+/// Reads a single AMF3 value with its own set of reference tables, failing as
+/// soon as the input goes over one of the specified limits.  Along with the value,
+/// the number of values it was counted as against `max_total_values` is returned,
+/// so a caller that decodes AMF3 values inside its own limits can keep its
+/// running count.
+pub fn deserialize_value_with_limits(bytes: &mut Read, limits: &Amf3DecodeLimits) -> Result<(Amf3Value, usize), Amf3DeserializationError> {
+    let mut context = Context::new(limits);
+    let node = try!(read_value(bytes, &mut context));
+    let total_values = context.total_values;
+
+    drop(context);
+    Ok((into_value(node), total_values))
+}
+
+/// Turns a decoded node into a value, moving out of shared nodes that nothing
+/// else points to anymore and copying the rest
+fn into_value(node: Node) -> Amf3Value {
+    match node {
+        Node::Value(value) => value,
+        Node::Shared(shared) => match Rc::try_unwrap(shared) {
+            Ok(node) => into_value(node),
+            Err(shared) => to_value(&shared)
+        },
+
+        Node::Array { associative_values, dense_values } => Amf3Value::Array {
+            associative_values: into_pairs(associative_values),
+            dense_values: dense_values.into_iter().map(into_value).collect()
+        },
+
+        Node::Object { class_name, sealed_properties, dynamic_properties } => Amf3Value::Object {
+            class_name: class_name,
+            sealed_properties: into_pairs(sealed_properties),
+            dynamic_properties: dynamic_properties.map(into_pairs)
+        },
+
+        Node::ObjectVector { fixed_length, type_name, values } => Amf3Value::ObjectVector {
+            fixed_length: fixed_length,
+            type_name: type_name,
+            values: values.into_iter().map(into_value).collect()
+        },
+
+        Node::Dictionary { weak_keys, entries } => Amf3Value::Dictionary {
+            weak_keys: weak_keys,
+            entries: entries.into_iter().map(|(key, value)| (into_value(key), into_value(value))).collect()
+        },
+    }
+}
+
+fn into_pairs(pairs: Vec<(String, Node)>) -> Vec<(String, Amf3Value)> {
+    pairs.into_iter().map(|(name, node)| (name, into_value(node))).collect()
+}
+
+/// Copies a node that is still shared into a value
+fn to_value(node: &Node) -> Amf3Value {
+    match *node {
+        Node::Value(ref value) => value.clone(),
+        Node::Shared(ref shared) => to_value(shared),
+        Node::Array { ref associative_values, ref dense_values } => Amf3Value::Array {
+            associative_values: to_pairs(associative_values),
+            dense_values: dense_values.iter().map(to_value).collect()
+        },
+
+        Node::Object { ref class_name, ref sealed_properties, ref dynamic_properties } => Amf3Value::Object {
+            class_name: class_name.clone(),
+            sealed_properties: to_pairs(sealed_properties),
+            dynamic_properties: dynamic_properties.as_ref().map(|pairs| to_pairs(pairs))
+        },
+
+        Node::ObjectVector { fixed_length, ref type_name, ref values } => Amf3Value::ObjectVector {
+            fixed_length: fixed_length,
+            type_name: type_name.clone(),
+            values: values.iter().map(to_value).collect()
+        },
+
+        Node::Dictionary { weak_keys, ref entries } => Amf3Value::Dictionary {
+            weak_keys: weak_keys,
+            entries: entries.iter().map(|&(ref key, ref value)| (to_value(key), to_value(value))).collect()
+        },
+    }
+}
+
+fn to_pairs(pairs: &[(String, Node)]) -> Vec<(String, Amf3Value)> {
+    pairs.iter().map(|&(ref name, ref node)| (name.clone(), to_value(node))).collect()
+}
+
+fn read_value(bytes: &mut Read, context: &mut Context) -> Result<Node, Amf3DeserializationError> {
+    let marker = try!(bytes.read_u8());
+    read_value_with_marker(marker, bytes, context)
+}
+
+fn read_value_with_marker(marker: u8, bytes: &mut Read, context: &mut Context) -> Result<Node, Amf3DeserializationError> {
+    try!(context.count_values(1));
+    match marker {
+        markers::UNDEFINED_MARKER => Ok(Node::Value(Amf3Value::Undefined)),
+        markers::NULL_MARKER => Ok(Node::Value(Amf3Value::Null)),
+        markers::FALSE_MARKER => Ok(Node::Value(Amf3Value::Boolean(false))),
+        markers::TRUE_MARKER => Ok(Node::Value(Amf3Value::Boolean(true))),
+        markers::INTEGER_MARKER => parse_integer(bytes).map(Node::Value),
+        markers::DOUBLE_MARKER => parse_double(bytes).map(Node::Value),
+        markers::STRING_MARKER => read_string(bytes, context).map(|string| Node::Value(Amf3Value::Utf8String(string))),
+        markers::XML_DOCUMENT_MARKER => parse_xml(bytes, context, true),
+        markers::DATE_MARKER => parse_date(bytes, context),
+        markers::ARRAY_MARKER => read_nested(bytes, context, parse_array),
+        markers::OBJECT_MARKER => read_nested(bytes, context, parse_object),
+        markers::XML_MARKER => parse_xml(bytes, context, false),
+        markers::BYTE_ARRAY_MARKER => parse_byte_array(bytes, context),
+        markers::VECTOR_INT_MARKER => parse_vector(bytes, context, marker),
+        markers::VECTOR_UINT_MARKER => parse_vector(bytes, context, marker),
+        markers::VECTOR_DOUBLE_MARKER => parse_vector(bytes, context, marker),
+        markers::VECTOR_OBJECT_MARKER => read_nested(bytes, context, |bytes, context| parse_vector(bytes, context, marker)),
+        markers::DICTIONARY_MARKER => read_nested(bytes, context, parse_dictionary),
+        _ => Err(Amf3DeserializationError::UnknownMarker(marker))
+    }
+}
+
+/// Reads a value that can contain other values, one level deeper
+fn read_nested<F>(bytes: &mut Read, context: &mut Context, parse: F) -> Result<Node, Amf3DeserializationError>
+    where F: FnOnce(&mut Read, &mut Context) -> Result<Node, Amf3DeserializationError> {
+
+    try!(context.enter_nested());
+    let result = try!(parse(bytes, context));
+    context.exit_nested();
+    Ok(result)
+}
+
+fn read_u29(bytes: &mut Read) -> Result<u32, Amf3DeserializationError> {
+    let mut result: u32 = 0;
+    for index in 0..4 {
+        let byte = try!(bytes.read_u8()) as u32;
+
+        // The 4th byte uses all 8 bits, all others use the high bit to signal continuation
+        if index == 3 {
+            result = (result << 8) | byte;
+            break;
+        }
+
+        result = (result << 7) | (byte & 0x7F);
+        if byte & 0x80 == 0 {
+            break;
+        }
+    }
+
+    Ok(result)
+}
+
+/// Reads a U29 value whose low bit signals an inline value (1) or a reference (0).
+/// Returns the remaining bits along with if they represent a reference.
+fn read_u29_reference_flag(bytes: &mut Read) -> Result<(u32, bool), Amf3DeserializationError> {
+    let value = try!(read_u29(bytes));
+    Ok((value >> 1, value & 1 == 0))
+}
+
+fn parse_integer(bytes: &mut Read) -> Result<Amf3Value, Amf3DeserializationError> {
+    let value = try!(read_u29(bytes));
+
+    // Sign extend the 29 bit value
+    let signed = if value & 0x10000000 != 0 {
+        (value as i32) - 0x20000000
+    } else {
+        value as i32
+    };
+
+    Ok(Amf3Value::Integer(signed))
+}
+
+fn parse_double(bytes: &mut Read) -> Result<Amf3Value, Amf3DeserializationError> {
+    let value = try!(bytes.read_f64::<BigEndian>());
+    Ok(Amf3Value::Double(value))
+}
+
+/// Reads exactly `length` bytes.  The buffer grows as bytes arrive instead of
+/// being allocated up front, so a length claimed by the input can't allocate
+/// more memory than the input actually contains.
+fn read_bytes(bytes: &mut Read, length: usize) -> Result<Vec<u8>, Amf3DeserializationError> {
+    let mut buffer = Vec::new();
+    let bytes_read = try!(bytes.take(length as u64).read_to_end(&mut buffer));
+    if bytes_read < length {
+        return Err(Amf3DeserializationError::UnexpectedEof);
+    }
+
+    Ok(buffer)
+}
+
+fn read_utf8(bytes: &mut Read, length: usize) -> Result<String, Amf3DeserializationError> {
+    let buffer = try!(read_bytes(bytes, length));
+    let value = try!(String::from_utf8(buffer));
+    Ok(value)
+}
+
+fn read_string(bytes: &mut Read, context: &mut Context) -> Result<String, Amf3DeserializationError> {
+    let (value, is_reference) = try!(read_u29_reference_flag(bytes));
+    if is_reference {
+        let length = match context.strings.get(value as usize) {
+            Some(string) => string.len(),
+            None => return Err(Amf3DeserializationError::InvalidStringReference(value))
+        };
+
+        // Every copy of a referenced string counts towards the total bytes
+        try!(context.count_bytes(length));
+        return Ok(context.strings[value as usize].clone());
+    }
+
+    let length = try!(context.check_length(value));
+    let string = try!(read_utf8(bytes, length));
+
+    // Empty strings are never sent by reference
+    if string.len() > 0 {
+        context.strings.push(string.clone());
+    }
+
+    Ok(string)
+}
+
+fn parse_xml(bytes: &mut Read, context: &mut Context, is_document: bool) -> Result<Node, Amf3DeserializationError> {
+    let (value, is_reference) = try!(read_u29_reference_flag(bytes));
+    if is_reference {
+        return context.get_object(value);
+    }
+
+    let index = context.reserve_object();
+    let length = try!(context.check_length(value));
+    let xml = try!(read_utf8(bytes, length));
+    let result = if is_document { Amf3Value::XmlDocument(xml) } else { Amf3Value::Xml(xml) };
+
+    Ok(context.store_object(index, Node::Value(result)))
+}
+
+fn parse_date(bytes: &mut Read, context: &mut Context) -> Result<Node, Amf3DeserializationError> {
+    let (value, is_reference) = try!(read_u29_reference_flag(bytes));
+    if is_reference {
+        return context.get_object(value);
+    }
+
+    let unix_time = try!(bytes.read_f64::<BigEndian>());
+    let result = Amf3Value::Date { unix_time: unix_time };
+
+    let index = context.reserve_object();
+    Ok(context.store_object(index, Node::Value(result)))
+}
+
+fn parse_array(bytes: &mut Read, context: &mut Context) -> Result<Node, Amf3DeserializationError> {
+    let (value, is_reference) = try!(read_u29_reference_flag(bytes));
+    if is_reference {
+        return context.get_object(value);
+    }
+
+    let index = context.reserve_object();
+    let associative_values = try!(read_name_value_pairs(bytes, context));
+
+    let mut dense_values = Vec::new();
+    for _ in 0..value {
+        dense_values.push(try!(read_value(bytes, context)));
+    }
+
+    let result = Node::Array { associative_values: associative_values, dense_values: dense_values };
+    Ok(context.store_object(index, result))
+}
+
+fn parse_object(bytes: &mut Read, context: &mut Context) -> Result<Node, Amf3DeserializationError> {
+    let (value, is_reference) = try!(read_u29_reference_flag(bytes));
+    if is_reference {
+        return context.get_object(value);
+    }
+
+    let index = context.reserve_object();
+    let traits_index = try!(read_traits(value, bytes, context));
+
+    let sealed_names = context.traits[traits_index].sealed_names.clone();
+    let mut sealed_properties = Vec::new();
+    for name in sealed_names {
+        let property_value = try!(read_value(bytes, context));
+        sealed_properties.push((name, property_value));
+    }
+
+    let dynamic_properties = if context.traits[traits_index].is_dynamic {
+        Some(try!(read_name_value_pairs(bytes, context)))
+    } else {
+        None
+    };
+
+    let result = Node::Object {
+        class_name: context.traits[traits_index].class_name.clone(),
+        sealed_properties: sealed_properties,
+        dynamic_properties: dynamic_properties
+    };
+
+    Ok(context.store_object(index, result))
+}
+
+/// Resolves the traits for an object, either from the traits table or by reading
+/// them inline.  `value` is the object's U29 header with the object reference flag
+/// already shifted off.
+fn read_traits(value: u32, bytes: &mut Read, context: &mut Context) -> Result<usize, Amf3DeserializationError> {
+    if value & 1 == 0 {
+        let traits_index = value >> 1;
+        let byte_count = match context.traits.get(traits_index as usize) {
+            Some(traits) => traits.byte_count,
+            None => return Err(Amf3DeserializationError::InvalidTraitsReference(traits_index))
+        };
+
+        // The object gets its own copy of the class and property names
+        try!(context.count_bytes(byte_count));
+        return Ok(traits_index as usize);
+    }
+
+    let is_externalizable = value & 0b10 != 0;
+    let is_dynamic = value & 0b100 != 0;
+    let sealed_count = value >> 3;
+
+    let class_name = try!(read_string(bytes, context));
+    if is_externalizable {
+        return Err(Amf3DeserializationError::ExternalizableObjectNotSupported(class_name));
+    }
+
+    let mut sealed_names = Vec::new();
+    for _ in 0..sealed_count {
+        sealed_names.push(try!(read_string(bytes, context)));
+    }
+
+    let byte_count = sealed_names.iter().fold(class_name.len(), |total, name| total + name.len());
+    context.traits.push(Traits {
+        class_name: if class_name.len() > 0 { Some(class_name) } else { None },
+        sealed_names: sealed_names,
+        is_dynamic: is_dynamic,
+        byte_count: byte_count
+    });
+
+    Ok(context.traits.len() - 1)
+}
+
+fn read_name_value_pairs(bytes: &mut Read, context: &mut Context) -> Result<Vec<(String, Node)>, Amf3DeserializationError> {
+    let mut pairs = Vec::new();
+    loop {
+        let name = try!(read_string(bytes, context));
+        if name.len() == 0 {
+            break;
+        }
+
+        let value = try!(read_value(bytes, context));
+        pairs.push((name, value));
+    }
+
+    Ok(pairs)
+}
+
+fn parse_byte_array(bytes: &mut Read, context: &mut Context) -> Result<Node, Amf3DeserializationError> {
+    let (value, is_reference) = try!(read_u29_reference_flag(bytes));
+    if is_reference {
+        return context.get_object(value);
+    }
+
+    let index = context.reserve_object();
+    let length = try!(context.check_length(value));
+    let result = Amf3Value::ByteArray(try!(read_bytes(bytes, length)));
+
+    Ok(context.store_object(index, Node::Value(result)))
+}
+
+fn parse_vector(bytes: &mut Read, context: &mut Context, marker: u8) -> Result<Node, Amf3DeserializationError> {
+    let (count, is_reference) = try!(read_u29_reference_flag(bytes));
+    if is_reference {
+        return context.get_object(count);
+    }
+
+    let index = context.reserve_object();
+    let fixed_length = try!(bytes.read_u8()) != 0;
+
+    let result = match marker {
+        markers::VECTOR_INT_MARKER => {
+            let mut values = Vec::new();
+            for _ in 0..count {
+                try!(context.count_bytes(4));
+                values.push(try!(bytes.read_i32::<BigEndian>()));
+            }
+
+            Node::Value(Amf3Value::IntVector { fixed_length: fixed_length, values: values })
+        },
+
+        markers::VECTOR_UINT_MARKER => {
+            let mut values = Vec::new();
+            for _ in 0..count {
+                try!(context.count_bytes(4));
+                values.push(try!(bytes.read_u32::<BigEndian>()));
+            }
+
+            Node::Value(Amf3Value::UintVector { fixed_length: fixed_length, values: values })
+        },
+
+        markers::VECTOR_DOUBLE_MARKER => {
+            let mut values = Vec::new();
+            for _ in 0..count {
+                try!(context.count_bytes(8));
+                values.push(try!(bytes.read_f64::<BigEndian>()));
+            }
+
+            Node::Value(Amf3Value::DoubleVector { fixed_length: fixed_length, values: values })
+        },
+
+        _ => {
+            let type_name = try!(read_string(bytes, context));
+            let mut values = Vec::new();
+            for _ in 0..count {
+                values.push(try!(read_value(bytes, context)));
+            }
+
+            Node::ObjectVector { fixed_length: fixed_length, type_name: type_name, values: values }
+        }
+    };
+
+    Ok(context.store_object(index, result))
+}
+
+fn parse_dictionary(bytes: &mut Read, context: &mut Context) -> Result<Node, Amf3DeserializationError> {
+    let (count, is_reference) = try!(read_u29_reference_flag(bytes));
+    if is_reference {
+        return context.get_object(count);
+    }
+
+    let index = context.reserve_object();
+    let weak_keys = try!(bytes.read_u8()) != 0;
+
+    let mut entries = Vec::new();
+    for _ in 0..count {
+        let key = try!(read_value(bytes, context));
+        let value = try!(read_value(bytes, context));
+        entries.push((key, value));
+    }
+
+    let result = Node::Dictionary { weak_keys: weak_keys, entries: entries };
+    Ok(context.store_object(index, result))
+}
+
+#[cfg(test)]
+mod tests {
+    use std::io::Cursor;
+    use super::{deserialize, deserialize_value, deserialize_with_limits, deserialize_value_with_limits};
+    use super::super::Amf3Value;
+    use limits::Amf3DecodeLimits;
+    use super::super::errors::Amf3DeserializationError;
+    use markers;
+    use byteorder::{BigEndian, WriteBytesExt};
+
+    #[test]
+    fn can_deserialize_simple_markers() {
+        let vector = vec![markers::UNDEFINED_MARKER, markers::NULL_MARKER, markers::FALSE_MARKER, markers::TRUE_MARKER];
+
+        let mut input = Cursor::new(vector);
+        let result = deserialize(&mut input).unwrap();
+
+        let expected = vec![Amf3Value::Undefined, Amf3Value::Null, Amf3Value::Boolean(false), Amf3Value::Boolean(true)];
+        assert_eq!(result, expected);
+    }
+
+    #[test]
+    fn can_deserialize_u29_integers() {
+        let vector = vec![
+            markers::INTEGER_MARKER, 0x7F,
+            markers::INTEGER_MARKER, 0x81, 0x00,
+            markers::INTEGER_MARKER, 0x81, 0x80, 0x00,
+            markers::INTEGER_MARKER, 0xBF, 0xFF, 0xFF, 0xFF,
+            markers::INTEGER_MARKER, 0xFF, 0xFF, 0xFF, 0xFF,
+        ];
+
+        let mut input = Cursor::new(vector);
+        let result = deserialize(&mut input).unwrap();
+
+        let expected = vec![
+            Amf3Value::Integer(127),
+            Amf3Value::Integer(128),
+            Amf3Value::Integer(16384),
+            Amf3Value::Integer(0x0FFFFFFF),
+            Amf3Value::Integer(-1),
+        ];
+
+        assert_eq!(result, expected);
+    }
+
+    #[test]
+    fn can_deserialize_double() {
+        let mut vector = vec![markers::DOUBLE_MARKER];
+        vector.write_f64::<BigEndian>(332.5).unwrap();
+
+        let mut input = Cursor::new(vector);
+        let result = deserialize(&mut input).unwrap();
+
+        assert_eq!(result, vec![Amf3Value::Double(332.5)]);
+    }
+
+    #[test]
+    fn can_deserialize_string_and_string_reference() {
+        let mut vector = vec![markers::STRING_MARKER, (4 << 1) | 1];
+        vector.extend("test".as_bytes());
+        vector.push(markers::STRING_MARKER);
+        vector.push(0 << 1);
+
+        let mut input = Cursor::new(vector);
+        let result = deserialize(&mut input).unwrap();
+
+        let expected = vec![Amf3Value::Utf8String("test".to_string()), Amf3Value::Utf8String("test".to_string())];
+        assert_eq!(result, expected);
+    }
+
+    #[test]
+    fn error_when_string_reference_out_of_range() {
+        let vector = vec![markers::STRING_MARKER, 2 << 1];
+
+        let mut input = Cursor::new(vector);
+        let result = deserialize(&mut input);
+
+        assert!(match result {
+            Err(Amf3DeserializationError::InvalidStringReference(2)) => true,
+            _ => false}
+        );
+    }
+
+    #[test]
+    fn can_deserialize_anonymous_dynamic_object() {
+        let mut vector = vec![markers::OBJECT_MARKER, 0b1011, 0x01];
+        vector.push((3 << 1) | 1);
+        vector.extend("app".as_bytes());
+        vector.push(markers::INTEGER_MARKER);
+        vector.push(5);
+        vector.push(0x01);
+
+        let mut input = Cursor::new(vector);
+        let result = deserialize_value(&mut input).unwrap();
+
+        let expected = Amf3Value::Object {
+            class_name: None,
+            sealed_properties: vec![],
+            dynamic_properties: Some(vec![("app".to_string(), Amf3Value::Integer(5))])
+        };
+
+        assert_eq!(result, expected);
+    }
+
+    #[test]
+    fn can_deserialize_object_and_trait_references() {
+        // First object defines traits inline with one sealed property
+        let mut vector = vec![markers::OBJECT_MARKER, (1 << 4) | 0b0011, (5 << 1) | 1];
+        vector.extend("Point".as_bytes());
+        vector.push((1 << 1) | 1);
+        vector.extend("x".as_bytes());
+        vector.push(markers::INTEGER_MARKER);
+        vector.push(1);
+
+        // Second object reuses traits 0
+        vector.push(markers::OBJECT_MARKER);
+        vector.push((0 << 2) | 0b01);
+        vector.push(markers::INTEGER_MARKER);
+        vector.push(2);
+
+        // Third value references object 0
+        vector.push(markers::OBJECT_MARKER);
+        vector.push(0 << 1);
+
+        let mut input = Cursor::new(vector);
+        let result = deserialize(&mut input).unwrap();
+
+        let point = |x| Amf3Value::Object {
+            class_name: Some("Point".to_string()),
+            sealed_properties: vec![("x".to_string(), Amf3Value::Integer(x))],
+            dynamic_properties: None
+        };
+
+        assert_eq!(result, vec![point(1), point(2), point(1)]);
+    }
+
+    #[test]
+    fn error_when_object_references_itself() {
+        let vector = vec![markers::ARRAY_MARKER, (1 << 1) | 1, 0x01, markers::ARRAY_MARKER, 0 << 1];
+
+        let mut input = Cursor::new(vector);
+        let result = deserialize(&mut input);
+
+        assert!(match result {
+            Err(Amf3DeserializationError::InvalidObjectReference(0)) => true,
+            _ => false}
+        );
+    }
+
+    #[test]
+    fn error_when_object_is_externalizable() {
+        let mut vector = vec![markers::OBJECT_MARKER, 0b0111, (3 << 1) | 1];
+        vector.extend("abc".as_bytes());
+
+        let mut input = Cursor::new(vector);
+        let result = deserialize(&mut input);
+
+        assert!(match result {
+            Err(Amf3DeserializationError::ExternalizableObjectNotSupported(ref name)) if name == "abc" => true,
+            _ => false}
+        );
+    }
+
+    #[test]
+    fn can_deserialize_array() {
+        let mut vector = vec![markers::ARRAY_MARKER, (2 << 1) | 1];
+        vector.push((1 << 1) | 1);
+        vector.extend("a".as_bytes());
+        vector.push(markers::TRUE_MARKER);
+        vector.push(0x01);
+        vector.push(markers::INTEGER_MARKER);
+        vector.push(1);
+        vector.push(markers::NULL_MARKER);
+
+        let mut input = Cursor::new(vector);
+        let result = deserialize_value(&mut input).unwrap();
+
+        let expected = Amf3Value::Array {
+            associative_values: vec![("a".to_string(), Amf3Value::Boolean(true))],
+            dense_values: vec![Amf3Value::Integer(1), Amf3Value::Null]
+        };
+
+        assert_eq!(result, expected);
+    }
+
+    #[test]
+    fn can_deserialize_byte_array() {
+        let vector = vec![markers::BYTE_ARRAY_MARKER, (3 << 1) | 1, 1, 2, 3];
+
+        let mut input = Cursor::new(vector);
+        let result = deserialize_value(&mut input).unwrap();
+
+        assert_eq!(result, Amf3Value::ByteArray(vec![1, 2, 3]));
+    }
+
+    #[test]
+    fn can_deserialize_vectors() {
+        let mut vector = vec![markers::VECTOR_INT_MARKER, (2 << 1) | 1, 0];
+        vector.write_i32::<BigEndian>(-5).unwrap();
+        vector.write_i32::<BigEndian>(7).unwrap();
+        vector.extend(vec![markers::VECTOR_UINT_MARKER, (1 << 1) | 1, 1]);
+        vector.write_u32::<BigEndian>(9).unwrap();
+        vector.extend(vec![markers::VECTOR_DOUBLE_MARKER, (1 << 1) | 1, 0]);
+        vector.write_f64::<BigEndian>(1.5).unwrap();
+        vector.extend(vec![markers::VECTOR_OBJECT_MARKER, (1 << 1) | 1, 0, (1 << 1) | 1]);
+        vector.extend("*".as_bytes());
+        vector.push(markers::NULL_MARKER);
+
+        let mut input = Cursor::new(vector);
+        let result = deserialize(&mut input).unwrap();
+
+        let expected = vec![
+            Amf3Value::IntVector { fixed_length: false, values: vec![-5, 7] },
+            Amf3Value::UintVector { fixed_length: true, values: vec![9] },
+            Amf3Value::DoubleVector { fixed_length: false, values: vec![1.5] },
+            Amf3Value::ObjectVector { fixed_length: false, type_name: "*".to_string(), values: vec![Amf3Value::Null] },
+        ];
+
+        assert_eq!(result, expected);
+    }
+
+    #[test]
+    fn can_deserialize_dictionary() {
+        let mut vector = vec![markers::DICTIONARY_MARKER, (1 << 1) | 1, 1];
+        vector.push(markers::STRING_MARKER);
+        vector.push((1 << 1) | 1);
+        vector.extend("k".as_bytes());
+        vector.push(markers::INTEGER_MARKER);
+        vector.push(3);
+
+        let mut input = Cursor::new(vector);
+        let result = deserialize_value(&mut input).unwrap();
+
+        let expected = Amf3Value::Dictionary {
+            weak_keys: true,
+            entries: vec![(Amf3Value::Utf8String("k".to_string()), Amf3Value::Integer(3))]
+        };
+
+        assert_eq!(result, expected);
+    }
+
+    #[test]
+    fn can_deserialize_date_and_xml() {
+        let mut vector = vec![markers::DATE_MARKER, 0x01];
+        vector.write_f64::<BigEndian>(1474215410000.0).unwrap();
+        vector.extend(vec![markers::XML_MARKER, (3 << 1) | 1]);
+        vector.extend("<a>".as_bytes());
+        vector.extend(vec![markers::XML_DOCUMENT_MARKER, (3 << 1) | 1]);
+        vector.extend("<b>".as_bytes());
+        vector.extend(vec![markers::DATE_MARKER, 0 << 1]);
+
+        let mut input = Cursor::new(vector);
+        let result = deserialize(&mut input).unwrap();
+
+        let expected = vec![
+            Amf3Value::Date { unix_time: 1474215410000.0 },
+            Amf3Value::Xml("<a>".to_string()),
+            Amf3Value::XmlDocument("<b>".to_string()),
+            Amf3Value::Date { unix_time: 1474215410000.0 },
+        ];
+
+        assert_eq!(result, expected);
+    }
+
+    #[test]
+    fn error_on_unknown_marker() {
+        let vector = vec![0x20];
+
+        let mut input = Cursor::new(vector);
+        let result = deserialize(&mut input);
+
+        assert!(match result {
+            Err(Amf3DeserializationError::UnknownMarker(0x20)) => true,
+            _ => false}
+        );
+    }
+
+    #[test]
+    fn error_when_arrays_nested_too_deep() {
+        // Each array has no associative values and a single dense value
+        let mut vector = Vec::new();
+        for _ in 0..200000 {
+            vector.extend(&[markers::ARRAY_MARKER, (1 << 1) | 1, 0x01]);
+        }
+
+        vector.push(markers::NULL_MARKER);
+
+        let mut input = Cursor::new(vector);
+        let result = deserialize_value(&mut input);
+
+        assert!(match result {
+            Err(Amf3DeserializationError::MaxDepthExceeded(32)) => true,
+            _ => false
+        }, "Unexpected result: {:?}", result);
+    }
+
+    #[test]
+    fn nesting_allowed_up_to_max_depth() {
+        let mut vector = Vec::new();
+        for _ in 0..3 {
+            vector.extend(&[markers::ARRAY_MARKER, (1 << 1) | 1, 0x01]);
+        }
+
+        vector.push(markers::NULL_MARKER);
+
+        let limits = Amf3DecodeLimits { max_depth: 3, .. Amf3DecodeLimits::default() };
+        let mut input = Cursor::new(vector.clone());
+        assert!(deserialize_value_with_limits(&mut input, &limits).is_ok());
+
+        let limits = Amf3DecodeLimits { max_depth: 2, .. Amf3DecodeLimits::default() };
+        let mut input = Cursor::new(vector);
+        assert!(deserialize_value_with_limits(&mut input, &limits).is_err());
+    }
+
+    #[test]
+    fn error_when_string_longer_than_limit() {
+        // U29 length of 0x0FFFFFFF (largest inline string) with only a few bytes of data
+        let vector = vec![markers::STRING_MARKER, 0xFF, 0xFF, 0xFF, 0xFF, b'a', b'b'];
+
+        let mut input = Cursor::new(vector);
+        let result = deserialize(&mut input);
+
+        assert!(match result {
+            Err(Amf3DeserializationError::StringTooLong { length: 0x0FFFFFFF, limit: 1048576 }) => true,
+            _ => false
+        }, "Unexpected result: {:?}", result);
+    }
+
+    #[test]
+    fn huge_lengths_without_limits_fail_when_input_runs_out() {
+        let vectors = vec![
+            vec![markers::STRING_MARKER, 0xFF, 0xFF, 0xFF, 0xFF, b'a', b'b'],
+            vec![markers::BYTE_ARRAY_MARKER, 0xFF, 0xFF, 0xFF, 0xFF, 1, 2],
+        ];
+
+        for vector in vectors {
+            let mut input = Cursor::new(vector);
+            let result = deserialize_with_limits(&mut input, &Amf3DecodeLimits::unlimited());
+
+            assert!(match result {
+                Err(Amf3DeserializationError::UnexpectedEof) => true,
+                _ => false
+            }, "Unexpected result: {:?}", result);
+        }
+    }
+
+    #[test]
+    fn object_references_count_every_referenced_value() {
+        // An array of 4 integers (5 values), then 3 references to it
+        let mut vector = vec![markers::ARRAY_MARKER, (4 << 1) | 1, 0x01];
+        for x in 0..4 {
+            vector.extend(&[markers::INTEGER_MARKER, x]);
+        }
+
+        for _ in 0..3 {
+            vector.extend(&[markers::ARRAY_MARKER, 0 << 1]);
+        }
+
+        let limits = Amf3DecodeLimits { max_total_values: 20, .. Amf3DecodeLimits::default() };
+        let mut input = Cursor::new(vector.clone());
+        assert_eq!(deserialize_with_limits(&mut input, &limits).unwrap().len(), 4);
+
+        let limits = Amf3DecodeLimits { max_total_values: 19, .. Amf3DecodeLimits::default() };
+        let mut input = Cursor::new(vector);
+        let result = deserialize_with_limits(&mut input, &limits);
+
+        assert!(match result {
+            Err(Amf3DeserializationError::TooManyValues(19)) => true,
+            _ => false
+        }, "Unexpected result: {:?}", result);
+    }
+
+    #[test]
+    fn string_references_count_every_copied_byte() {
+        // A dense array holding a 50 byte string followed by 24 references to it
+        let mut vector = vec![markers::ARRAY_MARKER, (25 << 1) | 1, 0x01, markers::STRING_MARKER, (50 << 1) | 1];
+        vector.extend(&[b'a'; 50][..]);
+        for _ in 0..24 {
+            vector.extend(&[markers::STRING_MARKER, 0 << 1]);
+        }
+
+        let limits = Amf3DecodeLimits { max_total_bytes: 1250, .. Amf3DecodeLimits::default() };
+        let mut input = Cursor::new(vector.clone());
+        assert!(deserialize_value_with_limits(&mut input, &limits).is_ok());
+
+        let limits = Amf3DecodeLimits { max_total_bytes: 1000, .. Amf3DecodeLimits::default() };
+        let mut input = Cursor::new(vector);
+        let result = deserialize_value_with_limits(&mut input, &limits);
+
+        assert!(match result {
+            Err(Amf3DeserializationError::TooManyBytes(1000)) => true,
+            _ => false
+        }, "Unexpected result: {:?}", result);
+    }
+
+    #[test]
+    fn property_name_references_count_every_copied_byte() {
+        // A dynamic object whose 50 byte property name is referenced by 24 more properties
+        let mut vector = vec![markers::OBJECT_MARKER, 0b1011, 0x01, (50 << 1) | 1];
+        vector.extend(&[b'a'; 50][..]);
+        vector.push(markers::NULL_MARKER);
+        for _ in 0..24 {
+            vector.extend(&[0 << 1, markers::NULL_MARKER]);
+        }
+
+        vector.push(0x01);
+
+        let limits = Amf3DecodeLimits { max_total_bytes: 1000, .. Amf3DecodeLimits::default() };
+        let mut input = Cursor::new(vector);
+        let result = deserialize_value_with_limits(&mut input, &limits);
+
+        assert!(match result {
+            Err(Amf3DeserializationError::TooManyBytes(1000)) => true,
+            _ => false
+        }, "Unexpected result: {:?}", result);
+    }
+
+    #[test]
+    fn object_references_count_every_copied_byte() {
+        // A 50 byte byte array followed by 24 references to it
+        let mut vector = vec![markers::BYTE_ARRAY_MARKER, (50 << 1) | 1];
+        vector.extend(&[7; 50][..]);
+        for _ in 0..24 {
+            vector.extend(&[markers::BYTE_ARRAY_MARKER, 0 << 1]);
+        }
+
+        let limits = Amf3DecodeLimits { max_total_bytes: 1250, .. Amf3DecodeLimits::default() };
+        let mut input = Cursor::new(vector.clone());
+        let result = deserialize_with_limits(&mut input, &limits).unwrap();
+        assert_eq!(result.len(), 25);
+        assert!(result.iter().all(|value| *value == Amf3Value::ByteArray(vec![7; 50])));
+
+        let limits = Amf3DecodeLimits { max_total_bytes: 1000, .. Amf3DecodeLimits::default() };
+        let mut input = Cursor::new(vector);
+        let result = deserialize_with_limits(&mut input, &limits);
+
+        assert!(match result {
+            Err(Amf3DeserializationError::TooManyBytes(1000)) => true,
+            _ => false
+        }, "Unexpected result: {:?}", result);
+    }
+
+    #[test]
+    fn traits_references_count_every_copied_byte() {
+        // Two objects sharing traits with a 50 byte class name
+        let mut vector = vec![markers::OBJECT_MARKER, 0b0011, (50 << 1) | 1];
+        vector.extend(&[b'a'; 50][..]);
+        vector.extend(&[markers::OBJECT_MARKER, 0b0001]);
+
+        let limits = Amf3DecodeLimits { max_total_bytes: 100, .. Amf3DecodeLimits::default() };
+        let mut input = Cursor::new(vector.clone());
+        assert_eq!(deserialize_with_limits(&mut input, &limits).unwrap().len(), 2);
+
+        let limits = Amf3DecodeLimits { max_total_bytes: 99, .. Amf3DecodeLimits::default() };
+        let mut input = Cursor::new(vector);
+        let result = deserialize_with_limits(&mut input, &limits);
+
+        assert!(match result {
+            Err(Amf3DeserializationError::TooManyBytes(99)) => true,
+            _ => false
+        }, "Unexpected result: {:?}", result);
+    }
+
+    #[test]
+    fn object_references_count_nesting_of_referenced_value() {
+        // Three nested arrays, then an array holding a reference to the outermost one
+        let vector = vec![
+            markers::ARRAY_MARKER, (1 << 1) | 1, 0x01,
+            markers::ARRAY_MARKER, (1 << 1) | 1, 0x01,
+            markers::ARRAY_MARKER, 1, 0x01,
+            markers::ARRAY_MARKER, (1 << 1) | 1, 0x01,
+            markers::ARRAY_MARKER, 0 << 1,
+        ];
+
+        let limits = Amf3DecodeLimits { max_depth: 4, .. Amf3DecodeLimits::default() };
+        let mut input = Cursor::new(vector.clone());
+        assert_eq!(deserialize_with_limits(&mut input, &limits).unwrap().len(), 2);
+
+        let limits = Amf3DecodeLimits { max_depth: 3, .. Amf3DecodeLimits::default() };
+        let mut input = Cursor::new(vector);
+        let result = deserialize_with_limits(&mut input, &limits);
+
+        assert!(match result {
+            Err(Amf3DeserializationError::MaxDepthExceeded(3)) => true,
+            _ => false
+        }, "Unexpected result: {:?}", result);
+    }
+
+    #[test]
+    fn value_count_returned_with_single_value() {
+        let vector = vec![markers::ARRAY_MARKER, (2 << 1) | 1, 0x01, markers::TRUE_MARKER, markers::NULL_MARKER];
+
+        let mut input = Cursor::new(vector);
+        let (value, count) = deserialize_value_with_limits(&mut input, &Amf3DecodeLimits::default()).unwrap();
+
+        assert_eq!(value, Amf3Value::Array {
+            associative_values: vec![],
+            dense_values: vec![Amf3Value::Boolean(true), Amf3Value::Null]
+        });
+
+        assert_eq!(count, 3);
+    }
+}
@@ -0,0 +1,77 @@
+use std::{io, string};
+
+quick_error! {
+    #[derive(Debug)]
+    pub enum Amf3DeserializationError {
+        UnknownMarker(marker: u8) {
+            description("Encountered unknown marker")
+        }
+
+        InvalidStringReference(index: u32) {
+            description("String reference does not point to a previously deserialized string")
+        }
+
+        InvalidObjectReference(index: u32) {
+            description("Object reference does not point to a previously deserialized object")
+        }
+
+        InvalidTraitsReference(index: u32) {
+            description("Traits reference does not point to previously deserialized object traits")
+        }
+
+        ExternalizableObjectNotSupported(class_name: String) {
+            description("Externalizable objects require class specific decoding and are not supported")
+        }
+
+        UnexpectedEof {
+            description("Hit end of the byte buffer but was expecting more data")
+        }
+
+        MaxDepthExceeded(limit: usize) {
+            description("Values were nested deeper than allowed")
+            display("Values were nested deeper than the limit of {}", limit)
+        }
+
+        StringTooLong { length: usize, limit: usize } {
+            description("String length is greater than allowed")
+            display("String length of {} is greater than the limit of {}", length, limit)
+        }
+
+        TooManyValues(limit: usize) {
+            description("Input contains more values than allowed")
+            display("Input contains more than {} values", limit)
+        }
+
+        TooManyBytes(limit: usize) {
+            description("Decoded values contain more bytes than allowed")
+            display("Decoded values contain more than {} bytes", limit)
+        }
+
+        Io(err: io::Error) {
+            cause(err)
+            description(err.description())
+            from()
+        }
+
+        FromUtf8Error(err: string::FromUtf8Error) {
+            cause(err)
+            description(err.description())
+            from()
+        }
+    }
+}
+
+quick_error! {
+    #[derive(Debug)]
+    pub enum Amf3SerializationError {
+        LengthTooLarge {
+            description("Length of a string, array or collection is greater than 268,435,455")
+        }
+
+        Io(err: io::Error) {
+            cause(err)
+            description(err.description())
+            from()
+        }
+    }
+}
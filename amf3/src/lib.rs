@@ -0,0 +1,101 @@
+//! This crate provides functionality for serializing and deserializing data
+//! based on the Adobe AMF3 encoding specification.  (http://wwwimages.adobe.com/www.adobe.com/content/dam/Adobe/en/devnet/amf/pdf/amf-file-format-spec.pdf)
+//!
+//! String, object and trait references are resolved while deserializing, so
+//! callers only ever see fully formed values.  When serializing, repeated strings
+//! and object traits are written as references to keep the output compact.
+//!
+//! # Examples
+//! ```
+//! use std::io::Cursor;
+//! use amf3::{Amf3Value, serialize, deserialize};
+//!
+//! let object = Amf3Value::Object {
+//!     class_name: None,
+//!     sealed_properties: vec![],
+//!     dynamic_properties: Some(vec![
+//!         ("app".to_string(), Amf3Value::Utf8String("live".to_string())),
+//!         ("objectEncoding".to_string(), Amf3Value::Integer(3)),
+//!     ])
+//! };
+//!
+//! let input = vec![Amf3Value::Utf8String("live".to_string()), object, Amf3Value::Boolean(true)];
+//!
+//! // Serialize the values into a vector of bytes
+//! let serialized_data = serialize(&input).unwrap();
+//!
+//! // Deserialize the vector of bytes back into Amf3Value types
+//! let mut serialized_cursor = Cursor::new(serialized_data);
+//! let results = deserialize(&mut serialized_cursor).unwrap();
+//!
+//! assert_eq!(input, results);
+//! ```
+
+#[macro_use] extern crate quick_error;
+extern crate byteorder;
+
+mod serialization;
+mod deserialization;
+mod errors;
+mod limits;
+
+pub use serialization::{serialize, serialize_value};
+pub use deserialization::{deserialize, deserialize_value, deserialize_with_limits, deserialize_value_with_limits};
+pub use errors::{Amf3DeserializationError, Amf3SerializationError};
+pub use limits::Amf3DecodeLimits;
+
+#[derive(PartialEq, Debug, Clone)]
+pub enum Amf3Value {
+    Undefined,
+    Null,
+    Boolean(bool),
+
+    /// Integers outside of the 29 bit signed range AMF3 allows are serialized as doubles
+    Integer(i32),
+    Double(f64),
+    Utf8String(String),
+    XmlDocument(String),
+    Date { unix_time: f64 },
+    Array { associative_values: Vec<(String, Amf3Value)>, dense_values: Vec<Amf3Value> },
+
+    /// Anonymous objects have no class name.  Objects whose traits are not dynamic
+    /// have a `dynamic_properties` value of `None`.
+    Object {
+        class_name: Option<String>,
+        sealed_properties: Vec<(String, Amf3Value)>,
+        dynamic_properties: Option<Vec<(String, Amf3Value)>>
+    },
+
+    Xml(String),
+    ByteArray(Vec<u8>),
+    IntVector { fixed_length: bool, values: Vec<i32> },
+    UintVector { fixed_length: bool, values: Vec<u32> },
+    DoubleVector { fixed_length: bool, values: Vec<f64> },
+    ObjectVector { fixed_length: bool, type_name: String, values: Vec<Amf3Value> },
+    Dictionary { weak_keys: bool, entries: Vec<(Amf3Value, Amf3Value)> },
+}
+
+mod markers {
+    pub const UNDEFINED_MARKER: u8 = 0;
+    pub const NULL_MARKER: u8 = 1;
+    pub const FALSE_MARKER: u8 = 2;
+    pub const TRUE_MARKER: u8 = 3;
+    pub const INTEGER_MARKER: u8 = 4;
+    pub const DOUBLE_MARKER: u8 = 5;
+    pub const STRING_MARKER: u8 = 6;
+    pub const XML_DOCUMENT_MARKER: u8 = 7;
+    pub const DATE_MARKER: u8 = 8;
+    pub const ARRAY_MARKER: u8 = 9;
+    pub const OBJECT_MARKER: u8 = 10;
+    pub const XML_MARKER: u8 = 11;
+    pub const BYTE_ARRAY_MARKER: u8 = 12;
+    pub const VECTOR_INT_MARKER: u8 = 13;
+    pub const VECTOR_UINT_MARKER: u8 = 14;
+    pub const VECTOR_DOUBLE_MARKER: u8 = 15;
+    pub const VECTOR_OBJECT_MARKER: u8 = 16;
+    pub const DICTIONARY_MARKER: u8 = 17;
+
+    pub const INTEGER_MIN: i32 = -0x10000000;
+    pub const INTEGER_MAX: i32 = 0x0FFFFFFF;
+    pub const U29_MAX: u32 = 0x1FFFFFFF;
+}
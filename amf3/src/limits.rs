@@ -0,0 +1,52 @@
+//! Limits applied while deserializing AMF3 values.  Lengths and counts are read
+//! straight from the input, so without limits a single value could claim huge
+//! strings or nest arrays deep enough to exhaust memory or the stack.
+
+use std::usize;
+
+/// Bounds on the values accepted by `deserialize_with_limits`.  Exceeding any of
+/// them aborts deserialization with the matching `Amf3DeserializationError`.
+#[derive(Clone, Debug, PartialEq)]
+pub struct Amf3DecodeLimits {
+    /// How many arrays, objects, object vectors and dictionaries can be nested
+    /// inside each other
+    pub max_depth: usize,
+
+    /// Maximum length in bytes of any string, XML document or byte array
+    pub max_string_length: usize,
+
+    /// Maximum number of values decoded in total, including nested values.
+    /// A resolved object reference counts as every value in the object it
+    /// points to.
+    pub max_total_values: usize,
+
+    /// Maximum number of bytes decoded in total across strings (including
+    /// property and class names), XML documents, byte arrays and number vectors.
+    /// Every copy made when a string, object or traits reference is resolved is
+    /// counted, so a small input can't expand into a huge amount of memory.
+    pub max_total_bytes: usize,
+}
+
+impl Amf3DecodeLimits {
+    /// Limits that accept any input
+    pub fn unlimited() -> Amf3DecodeLimits {
+        Amf3DecodeLimits {
+            max_depth: usize::MAX,
+            max_string_length: usize::MAX,
+            max_total_values: usize::MAX,
+            max_total_bytes: usize::MAX,
+        }
+    }
+}
+
+impl Default for Amf3DecodeLimits {
+    /// The same limits the AMF0 deserializer applies by default
+    fn default() -> Amf3DecodeLimits {
+        Amf3DecodeLimits {
+            max_depth: 32,
+            max_string_length: 1024 * 1024,
+            max_total_values: 16 * 1024,
+            max_total_bytes: 16 * 1024 * 1024,
+        }
+    }
+}
@@ -0,0 +1,426 @@
+//! Module contains functionality for serializing values into
+//! bytes based on the AMF3 specification
+//! (http://wwwimages.adobe.com/www.adobe.com/content/dam/Adobe/en/devnet/amf/pdf/amf-file-format-spec.pdf)
+
+use std::collections::HashMap;
+use Amf3Value;
+use errors::Amf3SerializationError;
+use markers;
+use byteorder::{BigEndian, WriteBytesExt};
+
+#[derive(PartialEq)]
+struct Traits {
+    class_name: String,
+    sealed_names: Vec<String>,
+    is_dynamic: bool
+}
+
+/// Tracks strings and traits that have already been written so they can be sent
+/// by reference.  Object references are never written, every complex value is
+/// serialized inline, but each still takes up an object table slot on the peer.
+struct Context {
+    strings: HashMap<String, u32>,
+    traits: Vec<Traits>
+}
+
+/// Serializes values into an amf3 encoded vector of bytes.  All values share
+/// the same reference tables.
+pub fn serialize(values: &Vec<Amf3Value>) -> Result<Vec<u8>, Amf3SerializationError> {
+    let mut context = Context { strings: HashMap::new(), traits: Vec::new() };
+    let mut bytes = vec![];
+    for value in values {
+        try!(serialize_with_context(value, &mut bytes, &mut context));
+    }
+
+    Ok(bytes)
+}
+
+/// Serializes a single value with its own set of reference tables.  This is
+/// used when an AMF0 stream switches to AMF3 for one value.
+pub fn serialize_value(value: &Amf3Value, bytes: &mut Vec<u8>) -> Result<(), Amf3SerializationError> {
+    let mut context = Context { strings: HashMap::new(), traits: Vec::new() };
+    serialize_with_context(value, bytes, &mut context)
+}
+
+fn serialize_with_context(value: &Amf3Value, bytes: &mut Vec<u8>, context: &mut Context) -> Result<(), Amf3SerializationError> {
+    match *value {
+        Amf3Value::Undefined => Ok(bytes.push(markers::UNDEFINED_MARKER)),
+        Amf3Value::Null => Ok(bytes.push(markers::NULL_MARKER)),
+        Amf3Value::Boolean(false) => Ok(bytes.push(markers::FALSE_MARKER)),
+        Amf3Value::Boolean(true) => Ok(bytes.push(markers::TRUE_MARKER)),
+        Amf3Value::Integer(val) => serialize_integer(val, bytes),
+        Amf3Value::Double(val) => serialize_double(val, bytes),
+        Amf3Value::Utf8String(ref val) => {
+            bytes.push(markers::STRING_MARKER);
+            write_string(val, bytes, context)
+        },
+
+        Amf3Value::XmlDocument(ref val) => serialize_xml(markers::XML_DOCUMENT_MARKER, val, bytes),
+        Amf3Value::Xml(ref val) => serialize_xml(markers::XML_MARKER, val, bytes),
+        Amf3Value::Date { unix_time } => serialize_date(unix_time, bytes),
+        Amf3Value::Array { ref associative_values, ref dense_values } => serialize_array(associative_values, dense_values, bytes, context),
+        Amf3Value::Object { ref class_name, ref sealed_properties, ref dynamic_properties }
+            => serialize_object(class_name, sealed_properties, dynamic_properties, bytes, context),
+
+        Amf3Value::ByteArray(ref val) => serialize_byte_array(val, bytes),
+        Amf3Value::IntVector { fixed_length, ref values } => {
+            try!(write_vector_header(markers::VECTOR_INT_MARKER, values.len(), fixed_length, bytes));
+            for value in values {
+                try!(bytes.write_i32::<BigEndian>(*value));
+            }
+
+            Ok(())
+        },
+
+        Amf3Value::UintVector { fixed_length, ref values } => {
+            try!(write_vector_header(markers::VECTOR_UINT_MARKER, values.len(), fixed_length, bytes));
+            for value in values {
+                try!(bytes.write_u32::<BigEndian>(*value));
+            }
+
+            Ok(())
+        },
+
+        Amf3Value::DoubleVector { fixed_length, ref values } => {
+            try!(write_vector_header(markers::VECTOR_DOUBLE_MARKER, values.len(), fixed_length, bytes));
+            for value in values {
+                try!(bytes.write_f64::<BigEndian>(*value));
+            }
+
+            Ok(())
+        },
+
+        Amf3Value::ObjectVector { fixed_length, ref type_name, ref values } => {
+            try!(write_vector_header(markers::VECTOR_OBJECT_MARKER, values.len(), fixed_length, bytes));
+            try!(write_string(type_name, bytes, context));
+            for value in values {
+                try!(serialize_with_context(value, bytes, context));
+            }
+
+            Ok(())
+        },
+
+        Amf3Value::Dictionary { weak_keys, ref entries } => serialize_dictionary(weak_keys, entries, bytes, context),
+    }
+}
+
+fn write_u29(value: u32, bytes: &mut Vec<u8>) -> Result<(), Amf3SerializationError> {
+    if value < 0x80 {
+        bytes.push(value as u8);
+    } else if value < 0x4000 {
+        bytes.push(((value >> 7) & 0x7F) as u8 | 0x80);
+        bytes.push((value & 0x7F) as u8);
+    } else if value < 0x200000 {
+        bytes.push(((value >> 14) & 0x7F) as u8 | 0x80);
+        bytes.push(((value >> 7) & 0x7F) as u8 | 0x80);
+        bytes.push((value & 0x7F) as u8);
+    } else if value <= markers::U29_MAX {
+        bytes.push(((value >> 22) & 0x7F) as u8 | 0x80);
+        bytes.push(((value >> 15) & 0x7F) as u8 | 0x80);
+        bytes.push(((value >> 8) & 0x7F) as u8 | 0x80);
+        bytes.push((value & 0xFF) as u8);
+    } else {
+        return Err(Amf3SerializationError::LengthTooLarge);
+    }
+
+    Ok(())
+}
+
+/// Writes a length (or other inline value) with the low bit set to mark it as
+/// not being a reference
+fn write_inline_u29(value: usize, bytes: &mut Vec<u8>) -> Result<(), Amf3SerializationError> {
+    if value > (markers::U29_MAX >> 1) as usize {
+        return Err(Amf3SerializationError::LengthTooLarge);
+    }
+
+    write_u29(((value as u32) << 1) | 1, bytes)
+}
+
+fn serialize_integer(value: i32, bytes: &mut Vec<u8>) -> Result<(), Amf3SerializationError> {
+    if value < markers::INTEGER_MIN || value > markers::INTEGER_MAX {
+        return serialize_double(value as f64, bytes);
+    }
+
+    bytes.push(markers::INTEGER_MARKER);
+    write_u29((value as u32) & markers::U29_MAX, bytes)
+}
+
+fn serialize_double(value: f64, bytes: &mut Vec<u8>) -> Result<(), Amf3SerializationError> {
+    bytes.push(markers::DOUBLE_MARKER);
+    try!(bytes.write_f64::<BigEndian>(value));
+    Ok(())
+}
+
+fn write_string(value: &String, bytes: &mut Vec<u8>, context: &mut Context) -> Result<(), Amf3SerializationError> {
+    if let Some(index) = context.strings.get(value) {
+        return write_u29(index << 1, bytes);
+    }
+
+    try!(write_inline_u29(value.len(), bytes));
+    bytes.extend(value.as_bytes());
+
+    // Empty strings are never sent by reference
+    if value.len() > 0 {
+        let index = context.strings.len() as u32;
+        context.strings.insert(value.clone(), index);
+    }
+
+    Ok(())
+}
+
+fn serialize_xml(marker: u8, value: &String, bytes: &mut Vec<u8>) -> Result<(), Amf3SerializationError> {
+    bytes.push(marker);
+    try!(write_inline_u29(value.len(), bytes));
+    bytes.extend(value.as_bytes());
+    Ok(())
+}
+
+fn serialize_date(unix_time: f64, bytes: &mut Vec<u8>) -> Result<(), Amf3SerializationError> {
+    bytes.push(markers::DATE_MARKER);
+    try!(write_u29(1, bytes));
+    try!(bytes.write_f64::<BigEndian>(unix_time));
+    Ok(())
+}
+
+fn serialize_array(associative_values: &Vec<(String, Amf3Value)>,
+                    dense_values: &Vec<Amf3Value>,
+                    bytes: &mut Vec<u8>,
+                    context: &mut Context) -> Result<(), Amf3SerializationError> {
+    bytes.push(markers::ARRAY_MARKER);
+    try!(write_inline_u29(dense_values.len(), bytes));
+    try!(write_name_value_pairs(associative_values, bytes, context));
+
+    for value in dense_values {
+        try!(serialize_with_context(value, bytes, context));
+    }
+
+    Ok(())
+}
+
+fn serialize_object(class_name: &Option<String>,
+                    sealed_properties: &Vec<(String, Amf3Value)>,
+                    dynamic_properties: &Option<Vec<(String, Amf3Value)>>,
+                    bytes: &mut Vec<u8>,
+                    context: &mut Context) -> Result<(), Amf3SerializationError> {
+    bytes.push(markers::OBJECT_MARKER);
+
+    let traits = Traits {
+        class_name: match *class_name { Some(ref name) => name.clone(), None => String::new() },
+        sealed_names: sealed_properties.iter().map(|&(ref name, _)| name.clone()).collect(),
+        is_dynamic: dynamic_properties.is_some()
+    };
+
+    match context.traits.iter().position(|x| *x == traits) {
+        Some(index) => try!(write_u29(((index as u32) << 2) | 0b01, bytes)),
+        None => {
+            if traits.sealed_names.len() > (markers::U29_MAX >> 4) as usize {
+                return Err(Amf3SerializationError::LengthTooLarge);
+            }
+
+            let dynamic_flag = if traits.is_dynamic { 0b1000 } else { 0 };
+            try!(write_u29(((traits.sealed_names.len() as u32) << 4) | dynamic_flag | 0b0011, bytes));
+            try!(write_string(&traits.class_name, bytes, context));
+            for name in &traits.sealed_names {
+                try!(write_string(name, bytes, context));
+            }
+
+            context.traits.push(traits);
+        }
+    }
+
+    for &(_, ref value) in sealed_properties {
+        try!(serialize_with_context(value, bytes, context));
+    }
+
+    if let Some(ref properties) = *dynamic_properties {
+        try!(write_name_value_pairs(properties, bytes, context));
+    }
+
+    Ok(())
+}
+
+fn write_name_value_pairs(pairs: &Vec<(String, Amf3Value)>, bytes: &mut Vec<u8>, context: &mut Context) -> Result<(), Amf3SerializationError> {
+    for &(ref name, ref value) in pairs {
+        try!(write_string(name, bytes, context));
+        try!(serialize_with_context(value, bytes, context));
+    }
+
+    // An empty string marks the end of the name/value pairs
+    write_u29(1, bytes)
+}
+
+fn serialize_byte_array(value: &Vec<u8>, bytes: &mut Vec<u8>) -> Result<(), Amf3SerializationError> {
+    bytes.push(markers::BYTE_ARRAY_MARKER);
+    try!(write_inline_u29(value.len(), bytes));
+    bytes.extend(value);
+    Ok(())
+}
+
+fn write_vector_header(marker: u8, count: usize, fixed_length: bool, bytes: &mut Vec<u8>) -> Result<(), Amf3SerializationError> {
+    bytes.push(marker);
+    try!(write_inline_u29(count, bytes));
+    bytes.push(fixed_length as u8);
+    Ok(())
+}
+
+fn serialize_dictionary(weak_keys: bool,
+                        entries: &Vec<(Amf3Value, Amf3Value)>,
+                        bytes: &mut Vec<u8>,
+                        context: &mut Context) -> Result<(), Amf3SerializationError> {
+    bytes.push(markers::DICTIONARY_MARKER);
+    try!(write_inline_u29(entries.len(), bytes));
+    bytes.push(weak_keys as u8);
+
+    for &(ref key, ref value) in entries {
+        try!(serialize_with_context(key, bytes, context));
+        try!(serialize_with_context(value, bytes, context));
+    }
+
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use std::io::Cursor;
+    use super::serialize;
+    use super::super::Amf3Value;
+    use super::super::deserialize;
+    use markers;
+    use byteorder::{BigEndian, WriteBytesExt};
+
+    #[test]
+    fn can_serialize_simple_markers() {
+        let input = vec![Amf3Value::Undefined, Amf3Value::Null, Amf3Value::Boolean(false), Amf3Value::Boolean(true)];
+        let result = serialize(&input).unwrap();
+
+        let expected = vec![markers::UNDEFINED_MARKER, markers::NULL_MARKER, markers::FALSE_MARKER, markers::TRUE_MARKER];
+        assert_eq!(result, expected);
+    }
+
+    #[test]
+    fn can_serialize_u29_integers() {
+        let input = vec![
+            Amf3Value::Integer(127),
+            Amf3Value::Integer(128),
+            Amf3Value::Integer(16384),
+            Amf3Value::Integer(0x0FFFFFFF),
+            Amf3Value::Integer(-1),
+        ];
+
+        let result = serialize(&input).unwrap();
+
+        let expected = vec![
+            markers::INTEGER_MARKER, 0x7F,
+            markers::INTEGER_MARKER, 0x81, 0x00,
+            markers::INTEGER_MARKER, 0x81, 0x80, 0x00,
+            markers::INTEGER_MARKER, 0xBF, 0xFF, 0xFF, 0xFF,
+            markers::INTEGER_MARKER, 0xFF, 0xFF, 0xFF, 0xFF,
+        ];
+
+        assert_eq!(result, expected);
+    }
+
+    #[test]
+    fn integer_outside_of_u29_range_serialized_as_double() {
+        let input = vec![Amf3Value::Integer(0x10000000)];
+        let result = serialize(&input).unwrap();
+
+        let mut expected = vec![markers::DOUBLE_MARKER];
+        expected.write_f64::<BigEndian>(0x10000000 as f64).unwrap();
+
+        assert_eq!(result, expected);
+    }
+
+    #[test]
+    fn repeated_strings_are_serialized_as_references() {
+        let input = vec![Amf3Value::Utf8String("test".to_string()), Amf3Value::Utf8String("test".to_string())];
+        let result = serialize(&input).unwrap();
+
+        let mut expected = vec![markers::STRING_MARKER, (4 << 1) | 1];
+        expected.extend("test".as_bytes());
+        expected.push(markers::STRING_MARKER);
+        expected.push(0);
+
+        assert_eq!(result, expected);
+    }
+
+    #[test]
+    fn repeated_object_traits_are_serialized_as_references() {
+        let point = |x| Amf3Value::Object {
+            class_name: Some("Point".to_string()),
+            sealed_properties: vec![("x".to_string(), Amf3Value::Integer(x))],
+            dynamic_properties: None
+        };
+
+        let input = vec![point(1), point(2)];
+        let result = serialize(&input).unwrap();
+
+        let mut expected = vec![markers::OBJECT_MARKER, (1 << 4) | 0b0011, (5 << 1) | 1];
+        expected.extend("Point".as_bytes());
+        expected.push((1 << 1) | 1);
+        expected.extend("x".as_bytes());
+        expected.push(markers::INTEGER_MARKER);
+        expected.push(1);
+        expected.push(markers::OBJECT_MARKER);
+        expected.push(0b01);
+        expected.push(markers::INTEGER_MARKER);
+        expected.push(2);
+
+        assert_eq!(result, expected);
+    }
+
+    #[test]
+    fn can_serialize_anonymous_dynamic_object() {
+        let input = vec![Amf3Value::Object {
+            class_name: None,
+            sealed_properties: vec![],
+            dynamic_properties: Some(vec![("app".to_string(), Amf3Value::Integer(5))])
+        }];
+
+        let result = serialize(&input).unwrap();
+
+        let mut expected = vec![markers::OBJECT_MARKER, 0b1011, 0x01, (3 << 1) | 1];
+        expected.extend("app".as_bytes());
+        expected.push(markers::INTEGER_MARKER);
+        expected.push(5);
+        expected.push(0x01);
+
+        assert_eq!(result, expected);
+    }
+
+    #[test]
+    fn can_round_trip_all_value_types() {
+        let input = vec![
+            Amf3Value::Undefined,
+            Amf3Value::Null,
+            Amf3Value::Boolean(true),
+            Amf3Value::Integer(-268435456),
+            Amf3Value::Double(1.25),
+            Amf3Value::Utf8String("".to_string()),
+            Amf3Value::XmlDocument("<a/>".to_string()),
+            Amf3Value::Date { unix_time: 1474215410000.0 },
+            Amf3Value::Array {
+                associative_values: vec![("key".to_string(), Amf3Value::Utf8String("key".to_string()))],
+                dense_values: vec![Amf3Value::Integer(1), Amf3Value::Null]
+            },
+            Amf3Value::Object {
+                class_name: Some("com.example.Test".to_string()),
+                sealed_properties: vec![("a".to_string(), Amf3Value::Boolean(false))],
+                dynamic_properties: Some(vec![("b".to_string(), Amf3Value::Double(2.0))])
+            },
+            Amf3Value::Xml("<b/>".to_string()),
+            Amf3Value::ByteArray(vec![0, 1, 2, 255]),
+            Amf3Value::IntVector { fixed_length: true, values: vec![-1, 0, 1] },
+            Amf3Value::UintVector { fixed_length: false, values: vec![0, 4294967295] },
+            Amf3Value::DoubleVector { fixed_length: false, values: vec![0.5] },
+            Amf3Value::ObjectVector { fixed_length: false, type_name: "*".to_string(), values: vec![Amf3Value::Utf8String("key".to_string())] },
+            Amf3Value::Dictionary { weak_keys: false, entries: vec![(Amf3Value::Integer(1), Amf3Value::Utf8String("one".to_string()))] },
+        ];
+
+        let bytes = serialize(&input).unwrap();
+        let mut cursor = Cursor::new(bytes);
+        let result = deserialize(&mut cursor).unwrap();
+
+        assert_eq!(result, input);
+    }
+}
@@ -0,0 +1,59 @@
+#[macro_use] extern crate criterion;
+extern crate rtmp_chunk_io;
+extern crate rtmp_message;
+extern crate rtmp_time;
+
+use criterion::{Criterion, Throughput};
+use rtmp_chunk_io::serialization::Serializer;
+use rtmp_chunk_io::deserialization::Deserializer;
+use rtmp_message::MessagePayload;
+use rtmp_time::RtmpTimestamp;
+
+const MESSAGE_COUNT: usize = 100;
+const MESSAGE_SIZE: usize = 20_000;
+const READ_SIZE: usize = 4096;
+
+/// Serializes a batch of video sized messages, to be fed to the deserializer in
+/// socket sized reads
+fn create_chunk_bytes(chunk_size: u32) -> Vec<u8> {
+    let mut serializer = Serializer::new();
+    serializer.set_max_chunk_size(chunk_size);
+
+    let mut bytes = Vec::new();
+    for index in 0..MESSAGE_COUNT {
+        let message = MessagePayload {
+            timestamp: RtmpTimestamp::new(index as u32 * 33),
+            type_id: 9,
+            data: vec![(index % 256) as u8; MESSAGE_SIZE],
+            stream_id: 1
+        };
+
+        bytes.extend(serializer.serialize(&message, false).unwrap());
+    }
+
+    bytes
+}
+
+fn deserialize_video(c: &mut Criterion) {
+    let mut group = c.benchmark_group("deserialize_video");
+    for chunk_size in &[128_u32, 4096] {
+        let bytes = create_chunk_bytes(*chunk_size);
+        group.throughput(Throughput::Bytes(bytes.len() as u64));
+        group.bench_function(format!("chunk_size_{}", chunk_size), |b| b.iter(|| {
+            let mut deserializer = Deserializer::new();
+            deserializer.set_max_chunk_size(*chunk_size).unwrap();
+
+            let mut message_count = 0;
+            for read in bytes.chunks(READ_SIZE) {
+                message_count += deserializer.process_bytes(read).unwrap().len();
+            }
+
+            assert_eq!(message_count, MESSAGE_COUNT);
+        }));
+    }
+
+    group.finish();
+}
+
+criterion_group!(benches, deserialize_video);
+criterion_main!(benches);
@@ -1,10 +1,10 @@
 use std::io;
-use std::io::{Cursor, Write};
+use std::usize;
 use std::collections::HashMap;
 use std::mem;
 use std::cmp::min;
 use byteorder::{BigEndian, LittleEndian, ReadBytesExt};
-use rtmp_message::MessagePayload;
+use rtmp_message::{MessagePayload, KnownMessageType};
 
 use chunk_header::{ChunkHeaderFormat, ChunkHeader};
 use utils::read_u24_be;
@@ -16,17 +16,79 @@ use utils::read_u24_be;
 ///
 /// Due to the nature of the RTMP chunking protocol, the same deserializer should
 /// be used for all data that is received from the peer.
+///
+/// Received bytes are parsed in place, with `read_index` marking how far into
+/// the buffer parsing has gotten.  Parsed bytes are only removed from the buffer
+/// once per `process_bytes()` call, so a partial chunk is moved to the front of
+/// the buffer at most once per call instead of after every header field.
 pub struct Deserializer {
     previous_headers: HashMap<u32, ChunkHeader>,
     max_chunk_size: u32,
     buffer: Vec<u8>,
+    read_index: usize,
     current_header: ChunkHeader,
     current_header_format: ChunkHeaderFormat,
     current_stage: ParseStage,
-    current_payload: MessagePayload
+    incomplete_payloads: HashMap<u32, IncompletePayload>,
+    reserved_payload_bytes: usize,
+    limits: DeserializationLimits,
+    total_bytes_received: u64
+}
+
+/// Bounds on what a peer can make the deserializer accept or hold in memory.
+/// Exceeding any of them fails deserialization with the matching
+/// `DeserializationError`, after which the connection should be dropped.
+#[derive(Clone, Debug, PartialEq)]
+pub struct DeserializationLimits {
+    /// Largest message length accepted in a chunk header
+    pub max_message_size: u32,
+
+    /// Largest chunk size the peer can switch to with `set_max_chunk_size()`
+    pub max_chunk_size: u32,
+
+    /// How many chunk streams can have a partially received message at once
+    pub max_open_chunk_streams: usize,
+
+    /// Maximum number of bytes held for unparsed data plus the full length of
+    /// every partially received message
+    pub max_buffered_bytes: usize,
+}
+
+impl DeserializationLimits {
+    /// Limits that accept anything the RTMP chunk format can express
+    pub fn unlimited() -> DeserializationLimits {
+        DeserializationLimits {
+            max_message_size: MAX_MESSAGE_SIZE,
+            max_chunk_size: MAX_CHUNK_SIZE,
+            max_open_chunk_streams: usize::MAX,
+            max_buffered_bytes: usize::MAX,
+        }
+    }
+}
+
+impl Default for DeserializationLimits {
+    fn default() -> DeserializationLimits {
+        DeserializationLimits {
+            max_message_size: 8 * 1024 * 1024,
+            max_chunk_size: MAX_MESSAGE_SIZE,
+            max_open_chunk_streams: 64,
+            max_buffered_bytes: 32 * 1024 * 1024,
+        }
+    }
+}
+
+/// A message whose chunks have not all been received yet, along with how many
+/// bytes were counted against the buffer limit for it
+struct IncompletePayload {
+    payload: MessagePayload,
+    reserved_length: usize
 }
 
 const MAX_INITIAL_TIMESTAMP: u32 = 16777215;
+const MAX_MESSAGE_SIZE: u32 = 16777215;
+const MAX_CHUNK_SIZE: u32 = 0x7FFFFFFF;
+const PROTOCOL_CONTROL_CSID: u32 = 2;
+const ABORT_MESSAGE_TYPE_ID: u8 = 2;
 
 enum ParsedValue<T> {
     NotEnoughBytes,
@@ -56,6 +118,36 @@ quick_error! {
             description("Received non type 0 chunk but no previous chunk has been received on that csid")
         }
 
+        MessageTooLarge { length: u32, limit: u32 } {
+            description("Chunk header specified a message longer than allowed")
+            display("Chunk header specified a message length of {} bytes, but the limit is {}", length, limit)
+        }
+
+        InvalidChunkSize(size: u32) {
+            description("Chunk size must be at least 1")
+            display("Chunk size of {} is invalid, it must be at least 1", size)
+        }
+
+        ChunkSizeTooLarge { size: u32, limit: u32 } {
+            description("Chunk size is larger than allowed")
+            display("Chunk size of {} is larger than the limit of {}", size, limit)
+        }
+
+        TooManyOpenChunkStreams(limit: usize) {
+            description("Too many chunk streams have partially received messages")
+            display("More than {} chunk streams have partially received messages", limit)
+        }
+
+        InvalidAbortMessageLength(length: u32) {
+            description("Abort message payload must contain a 4 byte chunk stream id")
+            display("Abort message has a length of {} bytes, but it must be at least 4", length)
+        }
+
+        TooManyBufferedBytes(limit: usize) {
+            description("Too many bytes are buffered for unparsed data and partially received messages")
+            display("More than {} bytes are buffered for unparsed data and partially received messages", limit)
+        }
+
         Io(err: io::Error) {
             cause(err)
             description(err.description())
@@ -66,26 +158,44 @@ quick_error! {
 
 impl Deserializer {
     pub fn new() -> Deserializer {
+        Deserializer::with_limits(DeserializationLimits::default())
+    }
+
+    pub fn with_limits(limits: DeserializationLimits) -> Deserializer {
         Deserializer {
             previous_headers: HashMap::new(),
             max_chunk_size: 128,
             buffer: Vec::new(),
+            read_index: 0,
             current_header: ChunkHeader::new(),
             current_header_format: ChunkHeaderFormat::Full,
             current_stage: ParseStage::Csid,
-            current_payload: MessagePayload::new(),
+            incomplete_payloads: HashMap::new(),
+            reserved_payload_bytes: 0,
+            limits: limits,
+            total_bytes_received: 0,
         }
     }
 
-    pub fn set_max_chunk_size(&mut self, new_size: u32) {
+    /// Changes the size of chunks expected from the peer, usually in response to
+    /// a SetChunkSize message
+    pub fn set_max_chunk_size(&mut self, new_size: u32) -> Result<(), DeserializationError> {
+        if new_size == 0 {
+            return Err(DeserializationError::InvalidChunkSize(new_size));
+        }
+
+        if new_size > self.limits.max_chunk_size {
+            return Err(DeserializationError::ChunkSizeTooLarge { size: new_size, limit: self.limits.max_chunk_size });
+        }
+
         self.max_chunk_size = new_size;
+        Ok(())
     }
 
-    pub fn process_bytes(&mut self, bytes: &Vec<u8>) -> Result<Vec<MessagePayload>, DeserializationError> {
-        try!(self.buffer.write(bytes));
-        if self.buffer.len() < 1 {
-            return Ok(Vec::new());
-        }
+    pub fn process_bytes(&mut self, bytes: &[u8]) -> Result<Vec<MessagePayload>, DeserializationError> {
+        self.total_bytes_received = self.total_bytes_received + bytes.len() as u64;
+        self.buffer.extend_from_slice(bytes);
+        try!(self.check_buffered_bytes(0));
 
         let mut results = Vec::new();
         loop {
@@ -104,20 +214,60 @@ impl Deserializer {
             }
         }
 
-        Ok(results)        
+        self.remove_parsed_bytes();
+        Ok(results)
     }
 
-    fn form_header(&mut self) -> Result<ParseResult, DeserializationError> {
-        if self.buffer.len() < 1 {
-            return Ok(ParseResult::NotEnoughBytes);
+    /// Total number of bytes passed to `process_bytes()`, including chunk headers
+    /// and bytes of messages that have not been completed yet.  This is the count
+    /// RTMP acknowledgements are based on.
+    pub fn get_total_bytes_received(&self) -> u64 {
+        self.total_bytes_received
+    }
+
+    /// Discards the partially received message on the chunk stream, if any.  The
+    /// chunk stream's last header is kept, so the peer can continue to send
+    /// compressed headers on it.  This is done automatically when an Abort message
+    /// is received.
+    pub fn abort_chunk_stream(&mut self, csid: u32) {
+        if let Some(incomplete) = self.incomplete_payloads.remove(&csid) {
+            self.reserved_payload_bytes -= incomplete.reserved_length;
+        }
+    }
+
+    /// Makes sure unparsed data plus the full length of every partially received
+    /// message, including `additional_bytes` about to be reserved, is within limits
+    fn check_buffered_bytes(&self, additional_bytes: usize) -> Result<(), DeserializationError> {
+        let buffered_bytes = self.unparsed_bytes().len() + self.reserved_payload_bytes + additional_bytes;
+        if buffered_bytes > self.limits.max_buffered_bytes {
+            return Err(DeserializationError::TooManyBufferedBytes(self.limits.max_buffered_bytes));
         }
 
-        self.current_header_format = get_format(&self.buffer[0]);
-        let (csid, next_index) = match get_csid(&self.buffer) {
+        Ok(())
+    }
+
+    fn unparsed_bytes(&self) -> &[u8] {
+        &self.buffer[self.read_index..]
+    }
+
+    fn remove_parsed_bytes(&mut self) {
+        if self.read_index == self.buffer.len() {
+            self.buffer.clear();
+        } else {
+            self.buffer.drain(0..self.read_index);
+        }
+
+        self.read_index = 0;
+    }
+
+    fn form_header(&mut self) -> Result<ParseResult, DeserializationError> {
+        let (csid, next_index) = match get_csid(self.unparsed_bytes()) {
             ParsedValue::NotEnoughBytes => return Ok(ParseResult::NotEnoughBytes),
             ParsedValue::Value{val, next_index} => (val, next_index) 
         };
 
+        self.current_header_format = get_format(&self.buffer[self.read_index]);
+
         self.current_header = match self.current_header_format {
             ChunkHeaderFormat::Full => {
                 let mut new_header = ChunkHeader::new();
@@ -131,7 +281,7 @@ impl Deserializer {
             }
         };
 
-        self.buffer.drain(0..(next_index as usize));
+        self.read_index += next_index as usize;
         self.current_stage = ParseStage::InitialTimestamp;
         Ok(ParseResult::Success)
     }
@@ -143,15 +293,11 @@ impl Deserializer {
             return Ok(ParseResult::Success);
         }
 
-        if self.buffer.len() < 3 {
+        if self.unparsed_bytes().len() < 3 {
             return Ok(ParseResult::NotEnoughBytes);
         }
 
-        let timestamp;
-        {
-            let mut cursor = Cursor::new(&mut self.buffer);
-            timestamp = try!(read_u24_be(&mut cursor));
-        }
+        let timestamp = try!(read_u24_be(&mut self.unparsed_bytes()));
         
         if self.current_header_format == ChunkHeaderFormat::Full {
             self.current_header.timestamp.set(timestamp);
@@ -161,7 +307,7 @@ impl Deserializer {
             self.current_header.timestamp_delta = timestamp;
         }
 
-        self.buffer.drain(0..3);
+        self.read_index += 3;
         self.current_stage = ParseStage::MessageLength;
         Ok(ParseResult::Success)
     }
@@ -172,17 +318,16 @@ impl Deserializer {
             return Ok(ParseResult::Success);
         }
 
-        if self.buffer.len() < 3 {
+        if self.unparsed_bytes().len() < 3 {
             return Ok(ParseResult::NotEnoughBytes);
         }
 
-        let length;
-        {
-            let mut cursor = Cursor::new(&mut self.buffer);
-            length = try!(read_u24_be(&mut cursor));
+        let length = try!(read_u24_be(&mut self.unparsed_bytes()));
+        if length > self.limits.max_message_size {
+            return Err(DeserializationError::MessageTooLarge { length: length, limit: self.limits.max_message_size });
         }
 
-        self.buffer.drain(0..3);
+        self.read_index += 3;
         self.current_header.message_length = length;
         self.current_stage = ParseStage::MessageTypeId;
         Ok(ParseResult::Success)
@@ -194,12 +339,12 @@ impl Deserializer {
             return Ok(ParseResult::Success);
         }
 
-        if self.buffer.len() < 1 {
+        if self.unparsed_bytes().len() < 1 {
             return Ok(ParseResult::NotEnoughBytes);
         }
 
-        self.current_header.message_type_id = self.buffer[0];
-        self.buffer.drain(0..1);
+        self.current_header.message_type_id = self.buffer[self.read_index];
+        self.read_index += 1;
         self.current_stage = ParseStage::MessageStreamId;
         Ok(ParseResult::Success)
     }
@@ -210,17 +355,13 @@ impl Deserializer {
             return Ok(ParseResult::Success);
         }
 
-        if self.buffer.len() < 4 {
+        if self.unparsed_bytes().len() < 4 {
             return Ok(ParseResult::NotEnoughBytes);
         }
 
-        let stream_id;
-        {
-            let mut cursor = Cursor::new(&mut self.buffer);
-            stream_id = try!(cursor.read_u32::<LittleEndian>());
-        }
+        let stream_id = try!(self.unparsed_bytes().read_u32::<LittleEndian>());
 
-        self.buffer.drain(0..4);
+        self.read_index += 4;
         self.current_header.message_stream_id = stream_id;
         self.current_stage = ParseStage::ExtendedTimestamp;
         Ok(ParseResult::Success)
@@ -236,15 +377,11 @@ impl Deserializer {
             return Ok(ParseResult::Success);
         }
 
-        if self.buffer.len() < 4 {
+        if self.unparsed_bytes().len() < 4 {
             return Ok(ParseResult::NotEnoughBytes);
         }
 
-        let timestamp;
-        {
-            let mut cursor = Cursor::new(&mut self.buffer);
-            timestamp = try!(cursor.read_u32::<BigEndian>());
-        }
+        let timestamp = try!(self.unparsed_bytes().read_u32::<BigEndian>());
 
         if self.current_header_format == ChunkHeaderFormat::Full {
             self.current_header.timestamp.set(timestamp);
@@ -255,34 +392,78 @@ impl Deserializer {
             self.current_header.timestamp = self.current_header.timestamp + (MAX_INITIAL_TIMESTAMP - self.current_header.timestamp_delta);
         }
 
-        self.buffer.drain(0..4);
+        self.read_index += 4;
         self.current_stage = ParseStage::MessagePayload;
         Ok(ParseResult::Success)        
     }
 
     fn get_message_data(&mut self, results: &mut Vec<MessagePayload>) -> Result<ParseResult, DeserializationError> {
-        let mut length = self.current_header.message_length as usize;
-        if length > self.max_chunk_size as usize {
-            let current_payload_length = self.current_payload.data.len();
-            let remaining_bytes = length - current_payload_length;
-            length = min(remaining_bytes, self.max_chunk_size as usize);
+        let csid = self.current_header.chunk_stream_id;
+        let message_length = self.current_header.message_length as usize;
+        let received_length = match self.incomplete_payloads.get(&csid) {
+            Some(incomplete) => incomplete.payload.data.len(),
+            None => 0
+        };
+
+        // The aborted csid is read as soon as an Abort completes, so reject one too
+        // short to contain it before any state is changed
+        if csid == PROTOCOL_CONTROL_CSID && self.current_header.message_type_id == ABORT_MESSAGE_TYPE_ID && message_length < 4 {
+            return Err(DeserializationError::InvalidAbortMessageLength(message_length as u32));
+        }
+
+        // A type 1 header in the middle of a message can change its length, so the
+        // bytes reserved for it have to be checked again
+        let reserved_length = self.incomplete_payloads.get(&csid).map(|incomplete| incomplete.reserved_length);
+        if let Some(reserved_length) = reserved_length {
+            if message_length != reserved_length {
+                try!(self.check_buffered_bytes(message_length.saturating_sub(reserved_length)));
+
+                self.reserved_payload_bytes = self.reserved_payload_bytes - reserved_length + message_length;
+                self.incomplete_payloads.get_mut(&csid).unwrap().reserved_length = message_length;
+            }
         }
 
-        if self.buffer.len() < length {
+        // It could also shrink the length below what was already received
+        let length = min(message_length.saturating_sub(received_length), self.max_chunk_size as usize);
+        if self.unparsed_bytes().len() < length {
             return Ok(ParseResult::NotEnoughBytes);
         }
 
-        self.current_payload.timestamp = self.current_header.timestamp;
-        self.current_payload.type_id = self.current_header.message_type_id;
-        self.current_payload.stream_id = self.current_header.message_stream_id;        
+        if !self.incomplete_payloads.contains_key(&csid) {
+            if self.incomplete_payloads.len() >= self.limits.max_open_chunk_streams {
+                return Err(DeserializationError::TooManyOpenChunkStreams(self.limits.max_open_chunk_streams));
+            }
+
+            try!(self.check_buffered_bytes(message_length));
 
-        for byte in self.buffer.drain(0..(length as usize)) {
-            self.current_payload.data.push(byte);
+            // Allocate the whole message up front instead of growing it per chunk
+            let mut payload = MessagePayload::new();
+            payload.data.reserve(message_length);
+            self.incomplete_payloads.insert(csid, IncompletePayload { payload: payload, reserved_length: message_length });
+            self.reserved_payload_bytes += message_length;
         }
 
-        if self.current_payload.data.len() == self.current_header.message_length as usize {
-            let payload = mem::replace(&mut self.current_payload, MessagePayload::new());
-            results.push(payload);
+        let is_complete = {
+            let payload = &mut self.incomplete_payloads.get_mut(&csid).unwrap().payload;
+            payload.timestamp = self.current_header.timestamp;
+            payload.type_id = self.current_header.message_type_id;
+            payload.stream_id = self.current_header.message_stream_id;
+            payload.data.extend_from_slice(&self.buffer[self.read_index..self.read_index + length]);
+            payload.data.len() >= message_length
+        };
+
+        self.read_index += length;
+
+        if is_complete {
+            let incomplete = self.incomplete_payloads.remove(&csid).unwrap();
+            self.reserved_payload_bytes -= incomplete.reserved_length;
+
+            if csid == PROTOCOL_CONTROL_CSID && incomplete.payload.get_message_type() == Some(KnownMessageType::Abort) {
+                let aborted_csid = try!((&incomplete.payload.data[..]).read_u32::<BigEndian>());
+                self.abort_chunk_stream(aborted_csid);
+            }
+
+            results.push(incomplete.payload);
         }
 
         let current_header = mem::replace(&mut self.current_header, ChunkHeader::new());
@@ -308,7 +489,7 @@ fn get_format(byte: &u8) -> ChunkHeaderFormat {
     }
 } 
 
-fn get_csid(buffer: &Vec<u8>) -> ParsedValue<u32> {
+fn get_csid(buffer: &[u8]) -> ParsedValue<u32> {
     const CSID_MASK: u8 = 0b00111111;
 
     if buffer.len() < 1 {
@@ -338,7 +519,7 @@ fn get_csid(buffer: &Vec<u8>) -> ParsedValue<u32> {
 
 #[cfg(test)]
 mod tests {
-    use super::Deserializer;
+    use super::{Deserializer, DeserializationError, DeserializationLimits};
     use std::io::Write;
 
     #[test]
@@ -542,7 +723,7 @@ mod tests {
         chunk_0_bytes[6] = 6;
 
         let mut deserializer = Deserializer::new();
-        deserializer.set_max_chunk_size(3);
+        deserializer.set_max_chunk_size(3).unwrap();
         let result1 = deserializer.process_bytes(&chunk_0_bytes).unwrap();
         let result2 = deserializer.process_bytes(&chunk_3_bytes).unwrap();
 
@@ -555,6 +736,257 @@ mod tests {
 
     }
 
+    #[test]
+    fn can_read_multiple_chunks_received_one_byte_at_a_time() {
+        let mut bytes = get_type_0_chunk(50, 20, 1, 9, vec![1, 2, 3]);
+        bytes.append(&mut get_type_1_chunk(50, 10, 9, vec![4, 5, 6]));
+
+        let mut deserializer = Deserializer::new();
+        let mut results = Vec::new();
+        for byte in bytes.iter() {
+            results.append(&mut deserializer.process_bytes(&[*byte]).unwrap());
+        }
+
+        assert_eq!(2, results.len());
+        assert_eq!(vec![1, 2, 3], results[0].data);
+        assert_eq!(vec![4, 5, 6], results[1].data);
+        assert_eq!(30, results[1].timestamp);
+    }
+
+    #[test]
+    fn can_read_messages_interleaved_across_chunk_streams() {
+        let mut bytes = get_type_0_chunk(4, 10, 1, 9, vec![1, 1, 1]);
+        bytes[6] = 6;
+        let mut audio_bytes = get_type_0_chunk(5, 10, 1, 8, vec![2, 2, 2]);
+        bytes.append(&mut audio_bytes);
+        bytes.append(&mut get_type_3_chunk(4, vec![3, 3, 3]));
+
+        let mut deserializer = Deserializer::new();
+        deserializer.set_max_chunk_size(3).unwrap();
+        let result = deserializer.process_bytes(&bytes).unwrap();
+
+        assert_eq!(2, result.len());
+        assert_eq!(vec![2, 2, 2], result[0].data);
+        assert_eq!(vec![1, 1, 1, 3, 3, 3], result[1].data);
+    }
+
+    #[test]
+    fn error_when_message_larger_than_limit() {
+        let mut limits = DeserializationLimits::default();
+        limits.max_message_size = 1000;
+
+        let mut deserializer = Deserializer::with_limits(limits);
+        match deserializer.process_bytes(&get_type_0_header(50, 1001)) {
+            Err(DeserializationError::MessageTooLarge { length: 1001, limit: 1000 }) => (),
+            Ok(_) => panic!("Expected MessageTooLarge error but got Ok()"),
+            Err(x) => panic!("Expected MessageTooLarge error but got Err({})", x)
+        }
+    }
+
+    #[test]
+    fn error_when_chunk_size_invalid() {
+        let mut limits = DeserializationLimits::default();
+        limits.max_chunk_size = 4096;
+        let mut deserializer = Deserializer::with_limits(limits);
+
+        match deserializer.set_max_chunk_size(0) {
+            Err(DeserializationError::InvalidChunkSize(0)) => (),
+            Ok(_) => panic!("Expected InvalidChunkSize error but got Ok()"),
+            Err(x) => panic!("Expected InvalidChunkSize error but got Err({})", x)
+        }
+
+        match deserializer.set_max_chunk_size(4097) {
+            Err(DeserializationError::ChunkSizeTooLarge { size: 4097, limit: 4096 }) => (),
+            Ok(_) => panic!("Expected ChunkSizeTooLarge error but got Ok()"),
+            Err(x) => panic!("Expected ChunkSizeTooLarge error but got Err({})", x)
+        }
+
+        deserializer.set_max_chunk_size(4096).unwrap();
+    }
+
+    #[test]
+    fn error_when_too_many_chunk_streams_have_partial_messages() {
+        let mut limits = DeserializationLimits::default();
+        limits.max_open_chunk_streams = 2;
+
+        let mut deserializer = Deserializer::with_limits(limits);
+        let mut bytes = Vec::new();
+        for csid in 10..13 {
+            bytes.append(&mut get_type_0_header(csid, 500));
+            bytes.extend_from_slice(&[0; 128]);
+        }
+
+        match deserializer.process_bytes(&bytes) {
+            Err(DeserializationError::TooManyOpenChunkStreams(2)) => (),
+            Ok(_) => panic!("Expected TooManyOpenChunkStreams error but got Ok()"),
+            Err(x) => panic!("Expected TooManyOpenChunkStreams error but got Err({})", x)
+        }
+    }
+
+    #[test]
+    fn completed_messages_do_not_count_as_open_chunk_streams() {
+        let mut limits = DeserializationLimits::default();
+        limits.max_open_chunk_streams = 1;
+
+        let mut deserializer = Deserializer::with_limits(limits);
+        let mut bytes = get_type_0_chunk(10, 0, 1, 9, vec![1, 2, 3]);
+        bytes.append(&mut get_type_0_chunk(11, 0, 1, 9, vec![1, 2, 3]));
+
+        let result = deserializer.process_bytes(&bytes).unwrap();
+        assert_eq!(2, result.len());
+    }
+
+    #[test]
+    fn error_when_partial_messages_exceed_buffer_limit() {
+        let mut limits = DeserializationLimits::default();
+        limits.max_buffered_bytes = 1000;
+
+        let mut deserializer = Deserializer::with_limits(limits);
+        let mut bytes = get_type_0_header(10, 600);
+        bytes.extend_from_slice(&[0; 128]);
+        bytes.append(&mut get_type_0_header(11, 600));
+        bytes.extend_from_slice(&[0; 128]);
+
+        match deserializer.process_bytes(&bytes) {
+            Err(DeserializationError::TooManyBufferedBytes(1000)) => (),
+            Ok(_) => panic!("Expected TooManyBufferedBytes error but got Ok()"),
+            Err(x) => panic!("Expected TooManyBufferedBytes error but got Err({})", x)
+        }
+    }
+
+    #[test]
+    fn error_when_type_1_header_grows_partial_message_past_buffer_limit() {
+        let mut limits = DeserializationLimits::default();
+        limits.max_buffered_bytes = 10000;
+
+        let mut deserializer = Deserializer::with_limits(limits);
+        let mut bytes = get_type_0_header(3, 200);
+        bytes.extend_from_slice(&[0; 128]);
+        bytes.extend_from_slice(&[0b01000000 | 3, 0, 0, 0, 0x0F, 0x42, 0x40, 9]);
+        bytes.extend_from_slice(&[0; 128]);
+        for _ in 0..10 {
+            bytes.append(&mut get_type_3_chunk(3, vec![0; 128]));
+        }
+
+        match deserializer.process_bytes(&bytes) {
+            Err(DeserializationError::TooManyBufferedBytes(10000)) => (),
+            Ok(_) => panic!("Expected TooManyBufferedBytes error but got Ok()"),
+            Err(x) => panic!("Expected TooManyBufferedBytes error but got Err({})", x)
+        }
+    }
+
+    #[test]
+    fn reserved_bytes_follow_message_length_changed_by_type_1_header() {
+        let mut deserializer = Deserializer::new();
+        let mut bytes = get_type_0_header(3, 200);
+        bytes.extend_from_slice(&[0; 128]);
+        bytes.extend_from_slice(&[0b01000000 | 3, 0, 0, 0, 0, 0x01, 0x2C, 9]);
+        bytes.extend_from_slice(&[0; 128]);
+
+        deserializer.process_bytes(&bytes).unwrap();
+        assert_eq!(deserializer.reserved_payload_bytes, 300);
+
+        let result = deserializer.process_bytes(&get_type_3_chunk(3, vec![0; 44])).unwrap();
+        assert_eq!(1, result.len());
+        assert_eq!(300, result[0].data.len());
+        assert_eq!(deserializer.reserved_payload_bytes, 0);
+    }
+
+    #[test]
+    fn error_when_unparsed_bytes_exceed_buffer_limit() {
+        let mut limits = DeserializationLimits::default();
+        limits.max_buffered_bytes = 1000;
+
+        let mut deserializer = Deserializer::with_limits(limits);
+        let mut bytes = get_type_0_header(10, 100);
+        bytes.extend_from_slice(&[0; 1000]);
+
+        match deserializer.process_bytes(&bytes) {
+            Err(DeserializationError::TooManyBufferedBytes(1000)) => (),
+            Ok(_) => panic!("Expected TooManyBufferedBytes error but got Ok()"),
+            Err(x) => panic!("Expected TooManyBufferedBytes error but got Err({})", x)
+        }
+    }
+
+    #[test]
+    fn aborted_chunk_stream_discards_partial_message() {
+        let mut first_message = get_type_0_chunk(50, 10, 1, 9, vec![1, 1, 1]);
+        first_message[6] = 6;
+        let second_message = get_type_0_chunk(50, 20, 1, 9, vec![2, 2, 2]);
+
+        let mut deserializer = Deserializer::new();
+        deserializer.set_max_chunk_size(3).unwrap();
+        deserializer.process_bytes(&first_message).unwrap();
+        deserializer.abort_chunk_stream(50);
+
+        let result = deserializer.process_bytes(&second_message).unwrap();
+        assert_eq!(1, result.len());
+        assert_eq!(vec![2, 2, 2], result[0].data);
+    }
+
+    #[test]
+    fn abort_message_on_csid_2_aborts_chunk_stream() {
+        let mut bytes = get_type_0_chunk(50, 10, 1, 9, vec![1, 1, 1, 1]);
+        bytes[6] = 8;
+        bytes.append(&mut get_type_0_chunk(2, 0, 0, 2, vec![0, 0, 0, 50]));
+        bytes.append(&mut get_type_3_chunk(50, vec![2, 2, 2, 2]));
+
+        let mut deserializer = Deserializer::new();
+        deserializer.set_max_chunk_size(4).unwrap();
+
+        // The type 3 chunk now starts a new message instead of finishing the
+        // aborted one
+        let result = deserializer.process_bytes(&bytes).unwrap();
+        assert_eq!(1, result.len());
+        assert_eq!(2, result[0].type_id);
+
+        let result = deserializer.process_bytes(&get_type_3_chunk(50, vec![3, 3, 3, 3])).unwrap();
+        assert_eq!(1, result.len());
+        assert_eq!(vec![2, 2, 2, 2, 3, 3, 3, 3], result[0].data);
+    }
+
+    #[test]
+    fn error_when_abort_message_is_too_short() {
+        let mut bytes = get_type_0_chunk(50, 10, 1, 9, vec![1, 1, 1, 1]);
+        bytes[6] = 8;
+        bytes.append(&mut get_type_0_chunk(2, 0, 0, 2, vec![0, 0, 50]));
+
+        let mut deserializer = Deserializer::new();
+        deserializer.set_max_chunk_size(4).unwrap();
+
+        match deserializer.process_bytes(&bytes) {
+            Err(DeserializationError::InvalidAbortMessageLength(3)) => (),
+            Ok(_) => panic!("Expected InvalidAbortMessageLength error but got Ok()"),
+            Err(x) => panic!("Expected InvalidAbortMessageLength error but got Err({})", x)
+        }
+
+        // Nothing was aborted
+        assert!(deserializer.incomplete_payloads.contains_key(&50));
+        assert_eq!(deserializer.reserved_payload_bytes, 8);
+    }
+
+    #[test]
+    fn counts_all_bytes_received() {
+        let mut bytes = get_type_0_chunk(50, 10, 1, 9, vec![1, 2, 3, 4]);
+        bytes[6] = 8;
+        let first_length = bytes.len();
+        bytes.append(&mut get_type_3_chunk(50, vec![5, 6, 7, 8]));
+
+        let mut deserializer = Deserializer::new();
+        deserializer.set_max_chunk_size(4).unwrap();
+        let results = deserializer.process_bytes(&bytes[..first_length + 2]).unwrap();
+        assert_eq!(results.len(), 0, "Unexpected number of messages returned");
+        assert_eq!(deserializer.get_total_bytes_received(), first_length as u64 + 2);
+
+        let results = deserializer.process_bytes(&bytes[first_length + 2..]).unwrap();
+        assert_eq!(results.len(), 1, "Unexpected number of messages returned");
+        assert_eq!(deserializer.get_total_bytes_received(), bytes.len() as u64);
+    }
+
+    fn get_type_0_header(csid: u8, length: u32) -> Vec<u8> {
+        vec![csid, 0, 0, 0, (length >> 16) as u8, (length >> 8) as u8, length as u8, 9, 1, 0, 0, 0]
+    }
+
     fn get_type_0_chunk(csid: u8, timestamp: u32, message_stream_id: u32, type_id: u8, payload: Vec<u8>) -> Vec<u8> {
         let mut bytes = vec![csid, 0, 0, timestamp as u8, 0, 0, payload.len() as u8, type_id, message_stream_id as u8, 0, 0, 0];
         bytes.write(&payload).unwrap();
@@ -9,5 +9,6 @@ extern crate rtmp_message;
 
 pub mod serialization;
 pub mod deserialization;
+pub mod scheduler;
 mod chunk_header;
 mod utils;
\ No newline at end of file
@@ -0,0 +1,346 @@
+//! Schedules outgoing messages chunk by chunk, so a large video frame does not
+//! hold up audio and control messages queued after it.
+//!
+//! Messages are queued per chunk stream, since the chunks of a message must be
+//! sent in order and before the next message on the same chunk stream.  Chunks
+//! from different chunk streams are interleaved, always picking the chunk stream
+//! with the highest priority message and falling back to the oldest message when
+//! priorities are equal.
+
+use std::collections::{HashMap, HashSet, VecDeque};
+use std::io::Write;
+use rtmp_message::MessagePayload;
+
+use serialization::{Serializer, SerializationError};
+
+/// Priority of a queued message, from most to least important
+#[derive(Clone, Copy, Eq, PartialEq, Ord, PartialOrd, Debug)]
+pub enum MessagePriority {
+    /// Protocol control, user control, command and data messages
+    Control,
+    Audio,
+    Video,
+}
+
+impl MessagePriority {
+    pub fn from_type_id(type_id: u8) -> MessagePriority {
+        match type_id {
+            8 => MessagePriority::Audio,
+            9 => MessagePriority::Video,
+            _ => MessagePriority::Control
+        }
+    }
+}
+
+/// Interleaves the chunks of queued messages by priority, serializing each
+/// chunk only when it is written.  Since header compression is based on the
+/// chunks actually written, queued messages can be dropped without affecting
+/// what the peer expects.
+pub struct ChunkScheduler {
+    serializer: Serializer,
+    chunk_streams: HashMap<u32, VecDeque<QueuedMessage>>,
+    next_sequence: u64,
+    queued_bytes: usize,
+    max_queued_bytes: Option<usize>,
+    streams_waiting_for_keyframe: HashSet<u32>,
+}
+
+struct QueuedMessage {
+    message: MessagePayload,
+    priority: MessagePriority,
+    sequence: u64,
+    bytes_sent: usize,
+    force_uncompressed: bool,
+}
+
+impl ChunkScheduler {
+    pub fn new(serializer: Serializer) -> ChunkScheduler {
+        ChunkScheduler {
+            serializer: serializer,
+            chunk_streams: HashMap::new(),
+            next_sequence: 0,
+            queued_bytes: 0,
+            max_queued_bytes: None,
+            streams_waiting_for_keyframe: HashSet::new(),
+        }
+    }
+
+    /// The serializer chunks are written with, e.g. to change its chunk size
+    pub fn serializer_mut(&mut self) -> &mut Serializer {
+        &mut self.serializer
+    }
+
+    /// When the payloads of queued messages add up to more than this many bytes
+    /// (e.g. because the peer is not reading fast enough), queued video frames
+    /// that are not keyframes are dropped.  `None` disables dropping.
+    pub fn set_max_queued_bytes(&mut self, max_queued_bytes: Option<usize>) {
+        self.max_queued_bytes = max_queued_bytes;
+    }
+
+    /// Number of payload bytes that have not been written yet
+    pub fn queued_bytes(&self) -> usize {
+        self.queued_bytes
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.chunk_streams.values().all(|queue| queue.is_empty())
+    }
+
+    /// Queues the message to be sent on the chunk stream picked by the serializer.
+    /// Returns false if the message was dropped because it is a video frame that
+    /// depends on an already dropped frame.
+    pub fn queue(&mut self, message: MessagePayload, force_uncompressed: bool) -> Result<bool, SerializationError> {
+        if message.data.len() > 16777215 {
+            return Err(SerializationError::MessageTooLong);
+        }
+
+        let priority = MessagePriority::from_type_id(message.type_id);
+        if priority == MessagePriority::Video {
+            // Frames after a dropped frame can't be decoded until the next keyframe
+            if is_keyframe(&message) {
+                self.streams_waiting_for_keyframe.remove(&message.stream_id);
+            } else if self.streams_waiting_for_keyframe.contains(&message.stream_id) {
+                return Ok(false);
+            }
+        }
+
+        let csid = self.serializer.allocate_csid(&message);
+        let queued_message = QueuedMessage {
+            priority: priority,
+            sequence: self.next_sequence,
+            bytes_sent: 0,
+            force_uncompressed: force_uncompressed,
+            message: message,
+        };
+
+        self.next_sequence = self.next_sequence + 1;
+        self.queued_bytes = self.queued_bytes + queued_message.message.data.len();
+        self.chunk_streams.entry(csid).or_insert_with(VecDeque::new).push_back(queued_message);
+
+        match self.max_queued_bytes {
+            Some(max) if self.queued_bytes > max => { self.drop_non_keyframe_video(); },
+            _ => (),
+        };
+
+        Ok(true)
+    }
+
+    /// Writes the next chunk by priority.  Returns false if there was nothing to
+    /// write.
+    pub fn write_next_chunk(&mut self, output: &mut Write) -> Result<bool, SerializationError> {
+        let csid = match self.next_chunk_stream() {
+            Some(csid) => csid,
+            None => return Ok(false)
+        };
+
+        let queue = self.chunk_streams.get_mut(&csid).unwrap();
+        let is_complete = {
+            let queued = queue.front_mut().unwrap();
+            let written = try!(self.serializer.serialize_chunk(&queued.message, csid, queued.bytes_sent,
+                                                               queued.force_uncompressed, output));
+
+            queued.bytes_sent = queued.bytes_sent + written;
+            self.queued_bytes = self.queued_bytes - written;
+            queued.bytes_sent >= queued.message.data.len()
+        };
+
+        if is_complete {
+            queue.pop_front();
+        }
+
+        Ok(true)
+    }
+
+    /// Writes chunks until no queued messages remain
+    pub fn write_all_chunks(&mut self, output: &mut Write) -> Result<(), SerializationError> {
+        while try!(self.write_next_chunk(output)) {}
+        Ok(())
+    }
+
+    /// Drops every queued video frame that is not a keyframe and has not started
+    /// to be written, returning how many were dropped.  Later frames for the
+    /// affected streams are dropped as they are queued until a keyframe arrives.
+    pub fn drop_non_keyframe_video(&mut self) -> usize {
+        let mut dropped_count = 0;
+        for queue in self.chunk_streams.values_mut() {
+            let mut kept = VecDeque::with_capacity(queue.len());
+            for queued in queue.drain(..) {
+                let can_drop = queued.priority == MessagePriority::Video
+                    && queued.bytes_sent == 0
+                    && !is_keyframe(&queued.message);
+
+                if can_drop {
+                    self.queued_bytes = self.queued_bytes - queued.message.data.len();
+                    self.streams_waiting_for_keyframe.insert(queued.message.stream_id);
+                    dropped_count = dropped_count + 1;
+                } else {
+                    kept.push_back(queued);
+                }
+            }
+
+            *queue = kept;
+        }
+
+        dropped_count
+    }
+
+    fn next_chunk_stream(&self) -> Option<u32> {
+        let mut next: Option<(u32, MessagePriority, u64)> = None;
+        for (csid, queue) in self.chunk_streams.iter() {
+            let queued = match queue.front() {
+                Some(queued) => queued,
+                None => continue
+            };
+
+            let is_better = match next {
+                None => true,
+                Some((_, priority, sequence)) => (queued.priority, queued.sequence) < (priority, sequence)
+            };
+
+            if is_better {
+                next = Some((*csid, queued.priority, queued.sequence));
+            }
+        }
+
+        next.map(|(csid, _, _)| csid)
+    }
+}
+
+/// FLV video tags store the frame type in the upper 4 bits of the first byte,
+/// with 1 meaning a keyframe (which includes AVC sequence headers)
+fn is_keyframe(message: &MessagePayload) -> bool {
+    match message.data.first() {
+        Some(byte) => byte >> 4 == 1,
+        None => false
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use rtmp_time::RtmpTimestamp;
+    use rtmp_message::MessagePayload;
+    use serialization::Serializer;
+    use deserialization::Deserializer;
+
+    #[test]
+    fn single_message_is_written_same_as_serializer() {
+        let message = create_message(9, 1, 0x17, 300);
+        let mut expected_serializer = Serializer::new();
+        let expected = expected_serializer.serialize(&message, false).unwrap();
+
+        let mut scheduler = ChunkScheduler::new(Serializer::new());
+        scheduler.queue(message, false).unwrap();
+
+        let mut output = Vec::new();
+        scheduler.write_all_chunks(&mut output).unwrap();
+
+        assert_eq!(output, expected);
+        assert!(scheduler.is_empty(), "Scheduler still has queued messages");
+        assert_eq!(scheduler.queued_bytes(), 0);
+    }
+
+    #[test]
+    fn audio_is_interleaved_between_video_chunks() {
+        let mut scheduler = ChunkScheduler::new(Serializer::new());
+        scheduler.queue(create_message(9, 1, 0x17, 300), false).unwrap();
+
+        // Send the first chunk of the keyframe, then audio shows up
+        let mut output = Vec::new();
+        scheduler.write_next_chunk(&mut output).unwrap();
+        scheduler.queue(create_message(8, 1, 0xaf, 10), false).unwrap();
+        scheduler.write_all_chunks(&mut output).unwrap();
+
+        let mut deserializer = Deserializer::new();
+        let results = deserializer.process_bytes(&output).unwrap();
+
+        assert_eq!(results.len(), 2);
+        assert_eq!(results[0].type_id, 8);
+        assert_eq!(results[1].type_id, 9);
+        assert_eq!(results[1].data.len(), 300);
+    }
+
+    #[test]
+    fn higher_priority_messages_are_written_first() {
+        let mut scheduler = ChunkScheduler::new(Serializer::new());
+        scheduler.queue(create_message(9, 1, 0x27, 10), false).unwrap();
+        scheduler.queue(create_message(8, 1, 0xaf, 10), false).unwrap();
+        scheduler.queue(create_message(20, 0, 2, 10), false).unwrap();
+
+        let mut output = Vec::new();
+        scheduler.write_all_chunks(&mut output).unwrap();
+
+        let mut deserializer = Deserializer::new();
+        let type_ids: Vec<u8> = deserializer.process_bytes(&output).unwrap().iter().map(|x| x.type_id).collect();
+        assert_eq!(type_ids, vec![20, 8, 9]);
+    }
+
+    #[test]
+    fn messages_on_same_chunk_stream_keep_their_order() {
+        let mut scheduler = ChunkScheduler::new(Serializer::new());
+        scheduler.queue(create_message(9, 1, 0x17, 200), false).unwrap();
+        scheduler.queue(create_message(9, 1, 0x27, 10), false).unwrap();
+
+        let mut output = Vec::new();
+        scheduler.write_all_chunks(&mut output).unwrap();
+
+        let mut deserializer = Deserializer::new();
+        let results = deserializer.process_bytes(&output).unwrap();
+        assert_eq!(results.len(), 2);
+        assert_eq!(results[0].data.len(), 200);
+        assert_eq!(results[1].data.len(), 10);
+    }
+
+    #[test]
+    fn non_keyframes_dropped_when_over_max_queued_bytes() {
+        let mut scheduler = ChunkScheduler::new(Serializer::new());
+        scheduler.set_max_queued_bytes(Some(250));
+
+        scheduler.queue(create_message(9, 1, 0x17, 100), false).unwrap();
+        scheduler.queue(create_message(9, 1, 0x27, 100), false).unwrap();
+        scheduler.queue(create_message(8, 1, 0xaf, 10), false).unwrap();
+        scheduler.queue(create_message(9, 1, 0x27, 100), false).unwrap();
+
+        // Frames that depend on the dropped ones are dropped until a keyframe
+        assert_eq!(scheduler.queue(create_message(9, 1, 0x27, 10), false).unwrap(), false);
+        assert_eq!(scheduler.queue(create_message(9, 1, 0x17, 10), false).unwrap(), true);
+        assert_eq!(scheduler.queued_bytes(), 120);
+
+        let mut output = Vec::new();
+        scheduler.write_all_chunks(&mut output).unwrap();
+
+        let mut deserializer = Deserializer::new();
+        let results = deserializer.process_bytes(&output).unwrap();
+        let first_bytes: Vec<u8> = results.iter().map(|x| x.data[0]).collect();
+        assert_eq!(first_bytes, vec![0xaf, 0x17, 0x17]);
+    }
+
+    #[test]
+    fn partially_written_frames_are_not_dropped() {
+        let mut scheduler = ChunkScheduler::new(Serializer::new());
+        scheduler.queue(create_message(9, 1, 0x27, 300), false).unwrap();
+
+        let mut output = Vec::new();
+        scheduler.write_next_chunk(&mut output).unwrap();
+
+        assert_eq!(scheduler.drop_non_keyframe_video(), 0);
+        scheduler.write_all_chunks(&mut output).unwrap();
+
+        let mut deserializer = Deserializer::new();
+        let results = deserializer.process_bytes(&output).unwrap();
+        assert_eq!(results.len(), 1);
+        assert_eq!(results[0].data.len(), 300);
+    }
+
+    fn create_message(type_id: u8, stream_id: u32, first_byte: u8, length: usize) -> MessagePayload {
+        let mut data = vec![0; length];
+        data[0] = first_byte;
+
+        MessagePayload {
+            timestamp: RtmpTimestamp::new(0),
+            type_id: type_id,
+            stream_id: stream_id,
+            data: data
+        }
+    }
+}
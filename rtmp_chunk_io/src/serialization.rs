@@ -1,7 +1,8 @@
 use std::cmp::min;
 use std::io;
-use std::io::{Cursor, Write};
+use std::io::{IoSlice, Write};
 use std::collections::HashMap;
+use std::sync::Arc;
 use byteorder::{BigEndian, LittleEndian, WriteBytesExt};
 use rtmp_time::RtmpTimestamp;
 use rtmp_message::MessagePayload;
@@ -10,6 +11,24 @@ use chunk_header::{ChunkHeader, ChunkHeaderFormat};
 use utils::{write_u24_be};
 
 const MAX_INITIAL_TIMESTAMP: u32 = 16777215;
+const MIN_CSID: u32 = 2;
+const MAX_CSID: u32 = 65599;
+const PROTOCOL_CONTROL_CSID: u32 = 2;
+const FIRST_ALLOCATED_CSID: u32 = 3;
+
+/// How the serializer picks the chunk stream id for messages that are
+/// serialized without an explicit csid
+#[derive(Clone, Copy, Eq, PartialEq, Debug)]
+pub enum ChunkStreamAllocation {
+    /// A fixed csid per kind of message (protocol control, data, video, audio,
+    /// everything else), regardless of which message stream it belongs to
+    ByMessageType,
+
+    /// Protocol control messages stay on csid 2, while every other kind of message
+    /// gets its own csid per message stream.  This keeps header compression
+    /// working when media for several streams is sent over one connection.
+    ByStreamAndMessageType,
+}
 
 /// Allows serializing message payloads into bytes representing rtmp chunks.
 /// Note that it all operations against the Serializer are mutable due to
@@ -18,9 +37,15 @@ const MAX_INITIAL_TIMESTAMP: u32 = 16777215;
 ///
 /// Due to the nature of the RTMP chunking protocol, the same serializer should
 /// be used for all messages that need to be sent to the same peer.
+#[derive(Clone)]
 pub struct Serializer {
     previous_headers: HashMap<u32, ChunkHeader>,
-    max_chunk_size: u32
+    max_chunk_size: u32,
+    csid_allocation: ChunkStreamAllocation,
+    allocated_csids: HashMap<(u32, u32), u32>,
+    released_csids: Vec<u32>,
+    next_csid: u32,
+    total_bytes_serialized: u64
 }
 
 quick_error! {
@@ -30,6 +55,11 @@ quick_error! {
             description("An individaul RTMP message can not be larger than 16777215 bytes")
         }
 
+        InvalidChunkStreamId(csid: u32) {
+            description("Chunk stream ids must be between 2 and 65599")
+            display("Chunk stream id {} is outside the allowed range of 2 to 65599", csid)
+        }
+
         Io(err: io::Error) {
             cause(err)
             description(err.description())
@@ -43,7 +73,12 @@ impl Serializer {
     pub fn new() -> Serializer {
         Serializer {
             previous_headers: HashMap::new(),
-            max_chunk_size: 128
+            max_chunk_size: 128,
+            csid_allocation: ChunkStreamAllocation::ByMessageType,
+            allocated_csids: HashMap::new(),
+            released_csids: Vec::new(),
+            next_csid: FIRST_ALLOCATED_CSID,
+            total_bytes_serialized: 0
         }
     }
 
@@ -51,43 +86,263 @@ impl Serializer {
         self.max_chunk_size = max_chunk_size;
     }
 
+    pub fn set_csid_allocation(&mut self, allocation: ChunkStreamAllocation) {
+        self.csid_allocation = allocation;
+    }
+
+    /// Total number of bytes serialized, including chunk headers.  When every
+    /// serialized byte is sent to the same peer this is the count the peer's
+    /// acknowledgements are compared against.
+    pub fn get_total_bytes_serialized(&self) -> u64 {
+        self.total_bytes_serialized
+    }
+
+    /// Gets the chunk stream the message should be sent on, based on the
+    /// serializer's `ChunkStreamAllocation`
+    pub fn allocate_csid(&mut self, message: &MessagePayload) -> u32 {
+        let type_csid = get_csid_for_message_type(message.type_id);
+        if self.csid_allocation == ChunkStreamAllocation::ByMessageType || type_csid == PROTOCOL_CONTROL_CSID {
+            return type_csid;
+        }
+
+        let key = (message.stream_id, type_csid);
+        if let Some(csid) = self.allocated_csids.get(&key) {
+            return *csid;
+        }
+
+        let csid = match self.released_csids.pop() {
+            Some(csid) => csid,
+            None => {
+                if self.next_csid > MAX_CSID {
+                    // Every csid has been handed out, so share them by message type
+                    return type_csid;
+                }
+
+                let csid = self.next_csid;
+                self.next_csid = self.next_csid + 1;
+                csid
+            }
+        };
+
+        self.allocated_csids.insert(key, csid);
+        csid
+    }
+
+    /// Releases the chunk streams allocated to the message stream (e.g. once it
+    /// has been deleted), so they can be allocated to other message streams.  The
+    /// first message sent on a released chunk stream gets a full header.
+    pub fn release_stream_csids(&mut self, stream_id: u32) {
+        let keys: Vec<(u32, u32)> = self.allocated_csids.keys()
+            .filter(|&&(allocated_stream_id, _)| allocated_stream_id == stream_id)
+            .cloned()
+            .collect();
+
+        for key in keys {
+            if let Some(csid) = self.allocated_csids.remove(&key) {
+                self.previous_headers.remove(&csid);
+                self.released_csids.push(csid);
+            }
+        }
+
+        // Hand out the lowest csids first, since they have the smallest headers
+        self.released_csids.sort_by(|a, b| b.cmp(a));
+    }
+
+    /// Serializes the message on the chunk stream picked by the serializer's
+    /// `ChunkStreamAllocation`
     pub fn serialize(&mut self, message: &MessagePayload, force_uncompressed: bool) -> Result<Vec<u8>, SerializationError> {
+        let csid = self.allocate_csid(message);
+        self.serialize_on_chunk_stream(message, csid, force_uncompressed)
+    }
+
+    /// Serializes the message on the specified chunk stream.  Headers are
+    /// compressed against the last message sent on that chunk stream.
+    pub fn serialize_on_chunk_stream(&mut self, message: &MessagePayload, csid: u32, force_uncompressed: bool) -> Result<Vec<u8>, SerializationError> {
+        let mut bytes = Vec::new();
+        try!(self.serialize_into_on_chunk_stream(message, csid, force_uncompressed, &mut bytes));
+        Ok(bytes)
+    }
+
+    /// Writes the chunks for the message directly into `output`, on the chunk
+    /// stream picked by the serializer's `ChunkStreamAllocation`
+    pub fn serialize_into(&mut self, message: &MessagePayload, force_uncompressed: bool, output: &mut Write) -> Result<(), SerializationError> {
+        let csid = self.allocate_csid(message);
+        self.serialize_into_on_chunk_stream(message, csid, force_uncompressed, output)
+    }
+
+    /// Writes the chunks for the message directly into `output`, on the specified
+    /// chunk stream
+    pub fn serialize_into_on_chunk_stream(&mut self, message: &MessagePayload, csid: u32, force_uncompressed: bool,
+                                          output: &mut Write) -> Result<(), SerializationError> {
         if message.data.len() > 16777215 {
             return Err(SerializationError::MessageTooLong);
         }
 
-        let mut bytes = Cursor::new(Vec::new());
-
         // Since a message may have a payload greater than one chunk allows, we must
         // split the payload into slices that don't exceed the max chunk length
-        let mut slices = Vec::<&[u8]>::new();
-        let mut iteration = 0;
-        loop {
-            let start_index = iteration * self.max_chunk_size as usize;
-            if start_index >= message.data.len() {
-                break;
-            }
+        let mut output = CountingWriter { inner: output, count: 0 };
+        for slice in message.data.chunks(self.max_chunk_size as usize) {
+            try!(add_chunk_header(self, &mut output, force_uncompressed, message, csid));
+            try!(add_message_payload(&mut output, slice));
+        }
 
-            let remaining_length = message.data.len() - start_index;
-            let end_index = min(start_index + self.max_chunk_size as usize, start_index + remaining_length);
+        self.total_bytes_serialized = self.total_bytes_serialized + output.count as u64;
+        Ok(())
+    }
 
-            slices.push(&message.data[start_index..end_index]);
+    /// Writes a single chunk of the message, containing the payload starting at
+    /// `offset`, and returns how many payload bytes the chunk contained.  This
+    /// allows chunks of messages on different chunk streams to be interleaved, as
+    /// long as each message's chunks are written in order.
+    pub fn serialize_chunk(&mut self, message: &MessagePayload, csid: u32, offset: usize, force_uncompressed: bool,
+                           output: &mut Write) -> Result<usize, SerializationError> {
+        if message.data.len() > 16777215 {
+            return Err(SerializationError::MessageTooLong);
+        }
+
+        let end = min(offset + self.max_chunk_size as usize, message.data.len());
+        let slice = &message.data[offset..end];
+        let mut output = CountingWriter { inner: output, count: 0 };
+        try!(add_chunk_header(self, &mut output, force_uncompressed, message, csid));
+        try!(add_message_payload(&mut output, slice));
+
+        self.total_bytes_serialized = self.total_bytes_serialized + output.count as u64;
+        Ok(slice.len())
+    }
 
-            iteration = iteration + 1;
+    /// Serializes the chunk headers for the message without copying its payload.
+    /// The returned chunks borrow the payload from the message, and can be sent
+    /// with a single vectored write via `VectoredChunks::io_slices()`.
+    pub fn serialize_vectored<'a>(&mut self, message: &'a MessagePayload, force_uncompressed: bool) -> Result<VectoredChunks<'a>, SerializationError> {
+        if message.data.len() > 16777215 {
+            return Err(SerializationError::MessageTooLong);
         }
 
-        for slice in slices.into_iter() {
-            try!(add_chunk(self, &mut bytes, force_uncompressed, message, slice));
+        let csid = self.allocate_csid(message);
+        let mut chunks = VectoredChunks {
+            header_bytes: Vec::new(),
+            chunks: Vec::new(),
+        };
+
+        for slice in message.data.chunks(self.max_chunk_size as usize) {
+            let header_start = chunks.header_bytes.len();
+            try!(add_chunk_header(self, &mut chunks.header_bytes, force_uncompressed, message, csid));
+
+            let header_end = chunks.header_bytes.len();
+            chunks.chunks.push((header_start, header_end, slice));
         }
-        
-        Ok(bytes.into_inner())
+
+        self.total_bytes_serialized = self.total_bytes_serialized + chunks.len() as u64;
+        Ok(chunks)
+    }
+
+    /// Creates an Abort message telling the peer to discard whatever it has
+    /// received of the message currently being sent on the chunk stream.  This
+    /// is for when only some of a message's chunks were actually sent, e.g. when
+    /// dropping data for a slow peer.  The next message on the chunk stream is
+    /// sent with a full header.
+    pub fn abort_chunk_stream(&mut self, csid: u32) -> Result<Vec<u8>, SerializationError> {
+        self.previous_headers.remove(&csid);
+
+        let mut data = Vec::new();
+        try!(data.write_u32::<BigEndian>(csid));
+
+        let abort = MessagePayload {
+            timestamp: RtmpTimestamp::new(0),
+            type_id: 2,
+            stream_id: 0,
+            data: data
+        };
+
+        self.serialize_on_chunk_stream(&abort, PROTOCOL_CONTROL_CSID, false)
     }
+
+    /// Creates an Abort message for the chunk stream the message was serialized on
+    /// by `serialize()`
+    pub fn abort_message(&mut self, message: &MessagePayload) -> Result<Vec<u8>, SerializationError> {
+        let csid = self.allocate_csid(message);
+        self.abort_chunk_stream(csid)
+    }
+
+    /// Serializes the message once so the same bytes can be sent to multiple
+    /// peers.  Which peers can be sent the bytes depends on `force_uncompressed`:
+    ///
+    /// * When set, every chunk has a full header, so the bytes do not depend on
+    /// anything previously sent and are valid for any peer expecting chunks of
+    /// this serializer's max chunk size.
+    /// * Otherwise headers are compressed against the chunks this serializer sent
+    /// before, so the bytes are only valid for peers that were sent exactly those
+    /// chunks (e.g. viewers of a stream that received every previous message from
+    /// this serializer).  Other peers would decode the wrong header values or fail
+    /// with `NoPreviousChunkOnStream`.
+    pub fn serialize_shared(&mut self, message: &MessagePayload, force_uncompressed: bool) -> Result<Arc<Vec<u8>>, SerializationError> {
+        let bytes = try!(self.serialize(message, force_uncompressed));
+        Ok(Arc::new(bytes))
+    }
+}
+
+/// Chunks of a serialized message whose headers are stored separately from the
+/// payload they describe, to allow vectored writes without copying the payload
+pub struct VectoredChunks<'a> {
+    header_bytes: Vec<u8>,
+    chunks: Vec<(usize, usize, &'a [u8])>,
 }
 
-fn add_chunk(serializer: &mut Serializer, bytes: &mut Cursor<Vec<u8>>, force_uncompressed: bool, 
-                message: &MessagePayload, data_to_write: &[u8]) -> Result<(), SerializationError> {
+impl<'a> VectoredChunks<'a> {
+    /// Slices alternating between each chunk's header and its part of the payload,
+    /// in the order they must be sent
+    pub fn io_slices(&self) -> Vec<IoSlice> {
+        let mut slices = Vec::with_capacity(self.chunks.len() * 2);
+        for &(header_start, header_end, payload) in self.chunks.iter() {
+            slices.push(IoSlice::new(&self.header_bytes[header_start..header_end]));
+            slices.push(IoSlice::new(payload));
+        }
+
+        slices
+    }
+
+    /// Total number of bytes across all headers and payloads
+    pub fn len(&self) -> usize {
+        self.chunks.iter().fold(self.header_bytes.len(), |total, &(_, _, payload)| total + payload.len())
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    /// Copies the chunks into a single buffer
+    pub fn to_vec(&self) -> Vec<u8> {
+        let mut bytes = Vec::with_capacity(self.len());
+        for slice in self.io_slices() {
+            bytes.extend_from_slice(&slice);
+        }
+
+        bytes
+    }
+}
+
+/// Passes writes through while counting how many bytes were written
+struct CountingWriter<'a> {
+    inner: &'a mut Write,
+    count: usize,
+}
+
+impl<'a> Write for CountingWriter<'a> {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        let written = try!(self.inner.write(buf));
+        self.count = self.count + written;
+        Ok(written)
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        self.inner.flush()
+    }
+}
+
+fn add_chunk_header(serializer: &mut Serializer, bytes: &mut Write, force_uncompressed: bool,
+                    message: &MessagePayload, csid: u32) -> Result<(), SerializationError> {
     let mut header = ChunkHeader { 
-        chunk_stream_id: get_csid_for_message_type(message.type_id),
+        chunk_stream_id: csid,
         timestamp: message.timestamp,
         timestamp_delta: 0,
         message_type_id: message.type_id,
@@ -108,8 +363,7 @@ fn add_chunk(serializer: &mut Serializer, bytes: &mut Cursor<Vec<u8>>, force_unc
             .and_then(|_| add_initial_timestamp(bytes, &header_format, header.timestamp))
             .and_then(|_| add_message_length_and_type_id(bytes, &header_format, header.message_length, header.message_type_id))
             .and_then(|_| add_message_stream_id(bytes, &header_format, header.message_stream_id))
-            .and_then(|_| add_extended_timestamp(bytes, &header_format, header.timestamp))
-            .and_then(|_| add_message_payload(bytes, data_to_write)));
+            .and_then(|_| add_extended_timestamp(bytes, &header_format, header.timestamp)));
     
     serializer.previous_headers.insert(header.chunk_stream_id, header);
     Ok(())
@@ -119,7 +373,7 @@ fn get_csid_for_message_type(message_type_id: u8) -> u32 {
     // Naive resolution, purpose (afaik) is to allow repeated messages
     // to utilize header compression by spreading them across chunk streams
     match message_type_id {
-        1 | 2 | 3 | 4 | 5 | 6 => 2,
+        1 | 2 | 3 | 4 | 5 | 6 => PROTOCOL_CONTROL_CSID,
         18 | 19               => 3,
         9                     => 4,
         8                     => 5,
@@ -128,13 +382,11 @@ fn get_csid_for_message_type(message_type_id: u8) -> u32 {
 }
 
 fn add_basic_header(bytes: &mut Write, format: &ChunkHeaderFormat, csid: u32) -> Result<(), SerializationError> {
-    if csid <= 1 || csid >= 65600 {
-        error!("Attempted to serialize an RTMP chunk with a csid of {}, but only csids between 2 and 65600 are allowed", csid);
+    if csid < MIN_CSID || csid > MAX_CSID {
+        error!("Attempted to serialize an RTMP chunk with a csid of {}, but only csids between 2 and 65599 are allowed", csid);
+        return Err(SerializationError::InvalidChunkStreamId(csid));
     }
 
-    debug_assert!(csid > 1, "csid cannot be 0 or 1");
-    debug_assert!(csid < 65600, "csid {} is above the max of 65599", csid);
-    
     let format_mask = match *format {
         ChunkHeaderFormat::Full                            => 0b00000000,
         ChunkHeaderFormat::TimeDeltaWithoutMessageStreamId => 0b01000000,
@@ -142,20 +394,28 @@ fn add_basic_header(bytes: &mut Write, format: &ChunkHeaderFormat, csid: u32) ->
         ChunkHeaderFormat::Empty                           => 0b11000000
     };
 
-    let mut first_byte = match csid {
-        x if x <= 63             => x as u8, 
-        x if x >= 64 && x <= 319 => 0,
-        _                        => 1
-    };
-
-    first_byte = first_byte | format_mask;
-    try!(bytes.write_u8(first_byte));
+    // csids 2-63 fit in the first byte.  Larger csids are written as an offset from
+    // 64, in one extra byte (for csids up to 319) or two extra little endian bytes.
+    match csid {
+        x if x <= 63 => {
+            try!(bytes.write_u8(x as u8 | format_mask));
+        },
+
+        x if x <= 319 => {
+            try!(bytes.write_u8(format_mask));
+            try!(bytes.write_u8((x - 64) as u8));
+        },
+
+        x => {
+            try!(bytes.write_u8(1 | format_mask));
+            try!(bytes.write_u16::<LittleEndian>((x - 64) as u16));
+        }
+    }
 
-    // TODO: Add bytes for 2 and 3 byte formats
     Ok(())
 }
 
-fn add_initial_timestamp(bytes: &mut Cursor<Vec<u8>>, format: &ChunkHeaderFormat, timestamp: RtmpTimestamp) -> Result<(), SerializationError> {
+fn add_initial_timestamp(bytes: &mut Write, format: &ChunkHeaderFormat, timestamp: RtmpTimestamp) -> Result<(), SerializationError> {
     if *format == ChunkHeaderFormat::Empty {
         return Ok(());
     }
@@ -166,7 +426,7 @@ fn add_initial_timestamp(bytes: &mut Cursor<Vec<u8>>, format: &ChunkHeaderFormat
     Ok(())
 }
 
-fn add_message_length_and_type_id(bytes: &mut Cursor<Vec<u8>>, format: &ChunkHeaderFormat, length: u32, type_id: u8) -> Result<(), SerializationError> {
+fn add_message_length_and_type_id(bytes: &mut Write, format: &ChunkHeaderFormat, length: u32, type_id: u8) -> Result<(), SerializationError> {
     if *format == ChunkHeaderFormat::Empty || *format == ChunkHeaderFormat::TimeDeltaOnly {
         return Ok(());
     }
@@ -199,7 +459,7 @@ fn add_extended_timestamp(bytes: &mut Write, format: &ChunkHeaderFormat, timesta
 }
 
 fn add_message_payload(bytes: &mut Write, data: &[u8]) -> Result<(), SerializationError> {
-    try!(bytes.write(data));
+    try!(bytes.write_all(data));
     Ok(())
 }
 
@@ -226,7 +486,10 @@ fn get_header_format(current_header: &mut ChunkHeader, previous_header: &ChunkHe
 #[cfg(test)]
 mod tests {
     use super::*;
-    use super::get_csid_for_message_type;
+    use super::{get_csid_for_message_type, add_basic_header};
+    use chunk_header::ChunkHeaderFormat;
+    use deserialization::{Deserializer, DeserializationError};
+    use std::io::Write;
     use rtmp_time::RtmpTimestamp;
     use rtmp_message::MessagePayload;
 
@@ -405,4 +668,313 @@ mod tests {
 
         assert_eq!(result, expected);
     }
-}
\ No newline at end of file
+
+    #[test]
+    fn csids_up_to_63_use_one_byte_basic_header() {
+        let mut bytes = Vec::new();
+        add_basic_header(&mut bytes, &ChunkHeaderFormat::TimeDeltaOnly, 63).unwrap();
+
+        assert_eq!(bytes, vec![0b10000000 | 63]);
+    }
+
+    #[test]
+    fn csids_from_64_to_319_use_two_byte_basic_header() {
+        let mut bytes = Vec::new();
+        add_basic_header(&mut bytes, &ChunkHeaderFormat::TimeDeltaWithoutMessageStreamId, 64).unwrap();
+        add_basic_header(&mut bytes, &ChunkHeaderFormat::Full, 319).unwrap();
+
+        assert_eq!(bytes, vec![0b01000000, 0, 0, 255]);
+    }
+
+    #[test]
+    fn csids_from_320_use_three_byte_basic_header() {
+        let mut bytes = Vec::new();
+        add_basic_header(&mut bytes, &ChunkHeaderFormat::Empty, 320).unwrap();
+        add_basic_header(&mut bytes, &ChunkHeaderFormat::Full, 65599).unwrap();
+
+        assert_eq!(bytes, vec![0b11000001, 0, 1, 1, 255, 255]);
+    }
+
+    #[test]
+    fn error_when_csid_out_of_range() {
+        for csid in &[0, 1, 65600] {
+            let mut bytes = Vec::new();
+            match add_basic_header(&mut bytes, &ChunkHeaderFormat::Full, *csid) {
+                Err(SerializationError::InvalidChunkStreamId(x)) => assert_eq!(x, *csid),
+                Ok(_) => panic!("Expected InvalidChunkStreamId error for csid {} but got Ok()", csid),
+                Err(x) => panic!("Expected InvalidChunkStreamId error for csid {} but got Err({})", csid, x)
+            }
+        }
+    }
+
+    #[test]
+    fn all_valid_csids_round_trip_through_deserializer() {
+        let mut serializer = Serializer::new();
+        let mut deserializer = Deserializer::new();
+
+        for csid in 2..65600 {
+            let message = MessagePayload {
+                timestamp: RtmpTimestamp::new(csid),
+                type_id: 9,
+                data: vec![(csid % 256) as u8, 1, 2],
+                stream_id: 1
+            };
+
+            // Serialize twice so compressed headers are covered as well
+            let mut bytes = serializer.serialize_on_chunk_stream(&message, csid, false).unwrap();
+            bytes.extend(serializer.serialize_on_chunk_stream(&message, csid, false).unwrap());
+
+            let results = deserializer.process_bytes(&bytes).unwrap();
+            assert_eq!(results.len(), 2, "Incorrect number of messages for csid {}", csid);
+            for result in results {
+                assert_eq!(result.timestamp, message.timestamp, "Incorrect timestamp for csid {}", csid);
+                assert_eq!(result.type_id, message.type_id, "Incorrect type id for csid {}", csid);
+                assert_eq!(result.stream_id, message.stream_id, "Incorrect stream id for csid {}", csid);
+                assert_eq!(result.data, message.data, "Incorrect data for csid {}", csid);
+            }
+        }
+    }
+
+    #[test]
+    fn stream_and_type_allocation_gives_each_stream_its_own_csid() {
+        let mut serializer = Serializer::new();
+        serializer.set_csid_allocation(ChunkStreamAllocation::ByStreamAndMessageType);
+
+        let video1 = serializer.allocate_csid(&create_message(9, 1));
+        let audio1 = serializer.allocate_csid(&create_message(8, 1));
+        let video2 = serializer.allocate_csid(&create_message(9, 2));
+
+        assert_eq!(video1, 3);
+        assert_eq!(audio1, 4);
+        assert_eq!(video2, 5);
+        assert_eq!(serializer.allocate_csid(&create_message(9, 1)), video1);
+        assert_eq!(serializer.allocate_csid(&create_message(5, 2)), 2);
+    }
+
+    #[test]
+    fn released_stream_csids_are_allocated_to_other_streams() {
+        let mut serializer = Serializer::new();
+        serializer.set_csid_allocation(ChunkStreamAllocation::ByStreamAndMessageType);
+
+        let video1 = serializer.allocate_csid(&create_message(9, 1));
+        let audio1 = serializer.allocate_csid(&create_message(8, 1));
+        let video2 = serializer.allocate_csid(&create_message(9, 2));
+        serializer.release_stream_csids(1);
+
+        assert_eq!(serializer.allocate_csid(&create_message(8, 3)), video1);
+        assert_eq!(serializer.allocate_csid(&create_message(9, 3)), audio1);
+        assert_eq!(serializer.allocate_csid(&create_message(9, 2)), video2);
+        assert_eq!(serializer.allocate_csid(&create_message(9, 1)), 6);
+    }
+
+    #[test]
+    fn first_message_on_released_csid_has_full_header() {
+        let mut serializer = Serializer::new();
+        serializer.set_csid_allocation(ChunkStreamAllocation::ByStreamAndMessageType);
+
+        serializer.serialize(&create_message(9, 1), false).unwrap();
+        serializer.release_stream_csids(1);
+
+        let result = serializer.serialize(&create_message(9, 3), false).unwrap();
+        assert_eq!(result[0], 3);
+        assert_eq!(result.len(), 12 + 3);
+    }
+
+    #[test]
+    fn media_for_different_streams_keeps_headers_compressed() {
+        let mut serializer = Serializer::new();
+        serializer.set_csid_allocation(ChunkStreamAllocation::ByStreamAndMessageType);
+
+        serializer.serialize(&create_message(9, 1), false).unwrap();
+        serializer.serialize(&create_message(9, 2), false).unwrap();
+
+        // Stream 2's message did not reset stream 1's header, so only a type 3
+        // header is needed
+        let result = serializer.serialize(&create_message(9, 1), false).unwrap();
+        assert_eq!(result, vec![0b11000000 | 3, 1, 2, 3]);
+    }
+
+    #[test]
+    fn message_type_allocation_is_the_default() {
+        let mut serializer = Serializer::new();
+
+        assert_eq!(serializer.allocate_csid(&create_message(9, 1)), get_csid_for_message_type(9));
+        assert_eq!(serializer.allocate_csid(&create_message(9, 2)), get_csid_for_message_type(9));
+    }
+
+    #[test]
+    fn can_serialize_on_caller_chosen_chunk_stream() {
+        let message = create_message(9, 1);
+        let mut serializer = Serializer::new();
+        let result = serializer.serialize_on_chunk_stream(&message, 40, false).unwrap();
+
+        assert_eq!(result[0], 40);
+    }
+
+    #[test]
+    fn serialize_into_writes_same_bytes_as_serialize() {
+        let message = create_large_message();
+        let mut serializer1 = Serializer::new();
+        let mut serializer2 = Serializer::new();
+
+        let mut output = vec![99];
+        for _ in 0..2 {
+            serializer1.serialize_into(&message, false, &mut output).unwrap();
+        }
+
+        let mut expected = vec![99];
+        for _ in 0..2 {
+            expected.extend(serializer2.serialize(&message, false).unwrap());
+        }
+
+        assert_eq!(output, expected);
+    }
+
+    #[test]
+    fn vectored_chunks_match_serialized_bytes() {
+        let message = create_large_message();
+        let mut serializer = Serializer::new();
+        let mut expected_serializer = serializer.clone();
+
+        let chunks = serializer.serialize_vectored(&message, false).unwrap();
+        let expected = expected_serializer.serialize(&message, false).unwrap();
+
+        assert_eq!(chunks.io_slices().len(), 6);
+        assert_eq!(chunks.len(), expected.len());
+        assert_eq!(chunks.to_vec(), expected);
+    }
+
+    #[test]
+    fn vectored_chunks_can_be_written_with_write_vectored() {
+        let message = create_large_message();
+        let mut serializer = Serializer::new();
+        let mut expected_serializer = Serializer::new();
+
+        let chunks = serializer.serialize_vectored(&message, false).unwrap();
+        let mut output = Vec::new();
+        let written = output.write_vectored(&chunks.io_slices()).unwrap();
+
+        assert_eq!(written, chunks.len());
+        assert_eq!(output, expected_serializer.serialize(&message, false).unwrap());
+    }
+
+    #[test]
+    fn shared_bytes_can_be_deserialized_by_multiple_peers() {
+        let message1 = create_large_message();
+        let message2 = create_large_message();
+        let mut serializer = Serializer::new();
+        let mut deserializer1 = Deserializer::new();
+        let mut deserializer2 = Deserializer::new();
+
+        for message in &[message1, message2] {
+            let bytes = serializer.serialize_shared(message, false).unwrap();
+            let results1 = deserializer1.process_bytes(&bytes).unwrap();
+            let results2 = deserializer2.process_bytes(&bytes).unwrap();
+
+            assert_eq!(results1.len(), 1);
+            assert_eq!(results2.len(), 1);
+            assert_eq!(results1[0].data, message.data);
+            assert_eq!(results2[0].data, message.data);
+        }
+    }
+
+    #[test]
+    fn uncompressed_shared_bytes_are_valid_for_peers_with_different_chunk_state() {
+        let mut earlier_message = create_message(9, 1);
+        earlier_message.timestamp = RtmpTimestamp::new(5000);
+
+        // The first peer has already received a message on the same chunk stream
+        // from a different serializer, the second peer has received nothing
+        let mut deserializer1 = Deserializer::new();
+        let mut deserializer2 = Deserializer::new();
+        let earlier_bytes = Serializer::new().serialize(&earlier_message, false).unwrap();
+        deserializer1.process_bytes(&earlier_bytes).unwrap();
+
+        let mut serializer = Serializer::new();
+        serializer.serialize(&create_large_message(), false).unwrap();
+
+        let message = create_large_message();
+        let bytes = serializer.serialize_shared(&message, true).unwrap();
+        for deserializer in &mut [deserializer1, deserializer2] {
+            let results = deserializer.process_bytes(&bytes).unwrap();
+
+            assert_eq!(results.len(), 1);
+            assert_eq!(results[0].timestamp, message.timestamp);
+            assert_eq!(results[0].data, message.data);
+        }
+    }
+
+    #[test]
+    fn compressed_shared_bytes_are_not_valid_for_peers_that_missed_earlier_chunks() {
+        let mut serializer = Serializer::new();
+        serializer.serialize(&create_large_message(), false).unwrap();
+
+        let bytes = serializer.serialize_shared(&create_large_message(), false).unwrap();
+        let mut deserializer = Deserializer::new();
+
+        match deserializer.process_bytes(&bytes) {
+            Err(DeserializationError::NoPreviousChunkOnStream(_)) => (),
+            x => panic!("Expected NoPreviousChunkOnStream error, instead received {:?}", x)
+        }
+    }
+
+    #[test]
+    fn aborting_message_sends_abort_and_resets_chunk_stream() {
+        let message = create_large_message();
+        let mut serializer = Serializer::new();
+        let mut deserializer = Deserializer::new();
+
+        // Only the first chunk of the message reaches the peer
+        let bytes = serializer.serialize(&message, false).unwrap();
+        deserializer.process_bytes(&bytes[..140]).unwrap();
+
+        let abort = serializer.abort_message(&message).unwrap();
+        let expected_csid = get_csid_for_message_type(message.type_id);
+        assert_eq!(abort, vec![2, 0, 0, 0, 0, 0, 4, 2, 0, 0, 0, 0, 0, 0, 0, expected_csid as u8]);
+
+        let results = deserializer.process_bytes(&abort).unwrap();
+        assert_eq!(results.len(), 1);
+        assert_eq!(results[0].type_id, 2);
+
+        let bytes = serializer.serialize(&message, false).unwrap();
+        assert_eq!(bytes[0], expected_csid as u8);
+
+        let results = deserializer.process_bytes(&bytes).unwrap();
+        assert_eq!(results.len(), 1);
+        assert_eq!(results[0].data, message.data);
+    }
+
+    #[test]
+    fn counts_all_bytes_serialized() {
+        let message = create_large_message();
+        let mut serializer = Serializer::new();
+
+        let bytes = serializer.serialize(&message, false).unwrap();
+        assert_eq!(serializer.get_total_bytes_serialized(), bytes.len() as u64);
+
+        let mut written = Vec::new();
+        serializer.serialize_into(&message, false, &mut written).unwrap();
+        assert_eq!(serializer.get_total_bytes_serialized(), (bytes.len() + written.len()) as u64);
+
+        let chunks = serializer.serialize_vectored(&message, false).unwrap();
+        assert_eq!(serializer.get_total_bytes_serialized(), (bytes.len() + written.len() + chunks.len()) as u64);
+    }
+
+    fn create_large_message() -> MessagePayload {
+        MessagePayload {
+            timestamp: RtmpTimestamp::new(10),
+            type_id: 9,
+            data: (0..300).map(|x| x as u8).collect(),
+            stream_id: 1
+        }
+    }
+
+    fn create_message(type_id: u8, stream_id: u32) -> MessagePayload {
+        MessagePayload {
+            timestamp: RtmpTimestamp::new(10),
+            type_id: type_id,
+            data: vec![1, 2, 3],
+            stream_id: stream_id
+        }
+    }
+}
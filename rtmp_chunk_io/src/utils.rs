@@ -1,19 +1,11 @@
-use std::io::{Read, Cursor, Result, Seek, SeekFrom};
-use byteorder::{BigEndian, WriteBytesExt, ReadBytesExt};
+use std::io::{Read, Write, Result};
+use byteorder::ReadBytesExt;
 
-pub fn write_u24_be(cursor: &mut Cursor<Vec<u8>>, value: u32) -> Result<()> {
+pub fn write_u24_be<W: Write + ?Sized>(output: &mut W, value: u32) -> Result<()> {
     debug_assert!(value <= 16777215, "Value is greater than what can fit in 3 bytes");
 
-    try!(cursor.write_u32::<BigEndian>(value));
-    
-    {
-        let mut inner = cursor.get_mut();
-        let index_to_remove = inner.len() - 1 - 3;
-        inner.remove(index_to_remove);
-    }
-
-    try!(cursor.seek(SeekFrom::End(0)));
-    Ok(())
+    let bytes = [(value >> 16) as u8, (value >> 8) as u8, value as u8];
+    output.write_all(&bytes)
 }
 
 pub fn read_u24_be<R: Read>(cursor: &mut R) -> Result<u32> {
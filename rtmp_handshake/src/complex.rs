@@ -0,0 +1,231 @@
+//! Digest based ("complex") handshake used by Flash Player 9+ and FMS.
+//!
+//! Packet 1 is made of the time and version fields followed by two 764 byte
+//! blocks, a key block and a digest block.  The order of the blocks depends on
+//! the schema (schema 0 is key first, schema 1 is digest first).  The first 4
+//! bytes of the digest block determine where in the block the 32 byte
+//! HMAC-SHA256 digest of the rest of the packet is placed.
+//!
+//! Packet 2 is random data whose last 32 bytes are a digest of the rest of the
+//! packet, keyed by a digest of the peer's packet 1 digest.
+
+use hmac::{Hmac, Mac};
+use sha2::Sha256;
+
+pub const DIGEST_SIZE: usize = 32;
+
+const BLOCK_SIZE: usize = 764;
+const DIGEST_OFFSET_RANGE: usize = BLOCK_SIZE - DIGEST_SIZE - 4;
+
+/// Versions sent in the second time field of packet 1.  Peers only use the
+/// complex handshake if this is not zero.
+pub const SERVER_VERSION: u32 = 0x04050001;
+pub const CLIENT_VERSION: u32 = 0x80000702;
+
+/// "Genuine Adobe Flash Media Server 001" followed by 32 fixed bytes.  The first
+/// 36 bytes sign the server's packet 1, the whole key is used for packet 2.
+pub static GENUINE_FMS_KEY: [u8; 68] = [
+    0x47, 0x65, 0x6e, 0x75, 0x69, 0x6e, 0x65, 0x20, 0x41, 0x64, 0x6f, 0x62,
+    0x65, 0x20, 0x46, 0x6c, 0x61, 0x73, 0x68, 0x20, 0x4d, 0x65, 0x64, 0x69,
+    0x61, 0x20, 0x53, 0x65, 0x72, 0x76, 0x65, 0x72, 0x20, 0x30, 0x30, 0x31,
+    0xf0, 0xee, 0xc2, 0x4a, 0x80, 0x68, 0xbe, 0xe8, 0x2e, 0x00, 0xd0, 0xd1,
+    0x02, 0x9e, 0x7e, 0x57, 0x6e, 0xec, 0x5d, 0x2d, 0x29, 0x80, 0x6f, 0xab,
+    0x93, 0xb8, 0xe6, 0x36, 0xcf, 0xeb, 0x31, 0xae,
+];
+
+/// "Genuine Adobe Flash Player 001" followed by 32 fixed bytes.  The first 30
+/// bytes sign the client's packet 1, the whole key is used for packet 2.
+pub static GENUINE_FP_KEY: [u8; 62] = [
+    0x47, 0x65, 0x6e, 0x75, 0x69, 0x6e, 0x65, 0x20, 0x41, 0x64, 0x6f, 0x62,
+    0x65, 0x20, 0x46, 0x6c, 0x61, 0x73, 0x68, 0x20, 0x50, 0x6c, 0x61, 0x79,
+    0x65, 0x72, 0x20, 0x30, 0x30, 0x31,
+    0xf0, 0xee, 0xc2, 0x4a, 0x80, 0x68, 0xbe, 0xe8, 0x2e, 0x00, 0xd0, 0xd1,
+    0x02, 0x9e, 0x7e, 0x57, 0x6e, 0xec, 0x5d, 0x2d, 0x29, 0x80, 0x6f, 0xab,
+    0x93, 0xb8, 0xe6, 0x36, 0xcf, 0xeb, 0x31, 0xae,
+];
+
+pub const FMS_PACKET_1_KEY_LENGTH: usize = 36;
+pub const FP_PACKET_1_KEY_LENGTH: usize = 30;
+
+/// Keys one side of the connection signs its packets with
+pub struct SigningKeys {
+    pub packet_1: &'static [u8],
+    pub packet_2: &'static [u8],
+}
+
+pub fn server_keys() -> SigningKeys {
+    SigningKeys {
+        packet_1: &GENUINE_FMS_KEY[..FMS_PACKET_1_KEY_LENGTH],
+        packet_2: &GENUINE_FMS_KEY,
+    }
+}
+
+pub fn client_keys() -> SigningKeys {
+    SigningKeys {
+        packet_1: &GENUINE_FP_KEY[..FP_PACKET_1_KEY_LENGTH],
+        packet_2: &GENUINE_FP_KEY,
+    }
+}
+
+#[derive(Clone, Copy, Eq, PartialEq, Debug)]
+pub enum DigestSchema {
+    /// Key block first, then the digest block
+    Schema0,
+
+    /// Digest block first, then the key block
+    Schema1,
+}
+
+/// Finds where the digest is located in packet 1 for the specified schema
+pub fn get_digest_offset(packet: &[u8], schema: DigestSchema) -> usize {
+    let block_start = match schema {
+        DigestSchema::Schema0 => 8 + BLOCK_SIZE,
+        DigestSchema::Schema1 => 8,
+    };
+
+    let sum = packet[block_start] as usize
+        + packet[block_start + 1] as usize
+        + packet[block_start + 2] as usize
+        + packet[block_start + 3] as usize;
+
+    block_start + 4 + (sum % DIGEST_OFFSET_RANGE)
+}
+
+/// Calculates the digest of the packet, skipping the 32 bytes reserved for the
+/// digest itself
+pub fn calculate_digest(packet: &[u8], digest_offset: usize, key: &[u8]) -> [u8; DIGEST_SIZE] {
+    hmac_sha256(key, &[&packet[..digest_offset], &packet[digest_offset + DIGEST_SIZE..]])
+}
+
+/// Looks for a valid digest in the peer's packet 1, trying both schemas.  If
+/// neither validates the peer is using the simple handshake.
+pub fn find_digest(packet: &[u8], key: &[u8]) -> Option<(DigestSchema, [u8; DIGEST_SIZE])> {
+    for schema in &[DigestSchema::Schema0, DigestSchema::Schema1] {
+        let offset = get_digest_offset(packet, *schema);
+        let expected = calculate_digest(packet, offset, key);
+        if packet[offset..offset + DIGEST_SIZE] == expected[..] {
+            return Some((*schema, expected));
+        }
+    }
+
+    None
+}
+
+/// Writes the digest into packet 1 (which should already contain its time,
+/// version and random data) and returns the digest
+pub fn sign_packet_1(packet: &mut [u8], schema: DigestSchema, key: &[u8]) -> [u8; DIGEST_SIZE] {
+    let offset = get_digest_offset(packet, schema);
+    let digest = calculate_digest(packet, offset, key);
+    packet[offset..offset + DIGEST_SIZE].copy_from_slice(&digest);
+    digest
+}
+
+/// Replaces the last 32 bytes of packet 2 with a digest of the rest of the
+/// packet, keyed from the peer's packet 1 digest
+pub fn sign_packet_2(packet: &mut [u8], peer_digest: &[u8], key: &[u8]) {
+    let digest = calculate_packet_2_digest(packet, peer_digest, key);
+    let length = packet.len();
+    packet[length - DIGEST_SIZE..].copy_from_slice(&digest);
+}
+
+/// Checks that the peer's packet 2 was signed with a key derived from our
+/// packet 1 digest
+pub fn is_packet_2_valid(packet: &[u8], my_digest: &[u8], key: &[u8]) -> bool {
+    let digest = calculate_packet_2_digest(packet, my_digest, key);
+    packet[packet.len() - DIGEST_SIZE..] == digest[..]
+}
+
+fn calculate_packet_2_digest(packet: &[u8], packet_1_digest: &[u8], key: &[u8]) -> [u8; DIGEST_SIZE] {
+    let packet_key = hmac_sha256(key, &[packet_1_digest]);
+    hmac_sha256(&packet_key, &[&packet[..packet.len() - DIGEST_SIZE]])
+}
+
+fn hmac_sha256(key: &[u8], parts: &[&[u8]]) -> [u8; DIGEST_SIZE] {
+    let mut mac = Hmac::<Sha256>::new_from_slice(key).expect("HMAC accepts keys of any length");
+    for part in parts {
+        mac.update(part);
+    }
+
+    let mut result = [0_u8; DIGEST_SIZE];
+    result.copy_from_slice(&mac.finalize().into_bytes());
+    result
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // Handshakes captured from librtmp 2.3 (the rtmpdump library OBS's RTMP
+    // output was forked from) connecting to this crate's server handshake.  The
+    // digest handshake was captured with SWF verification enabled, which makes
+    // librtmp use the Flash Player 9 handshake.  Expected digests are the values
+    // librtmp logged while performing the handshake.
+
+    static DIGEST_C0_C1: &'static [u8] = include_bytes!("../test_data/librtmp_digest_c0_c1.bin");
+    static DIGEST_S0_S1_S2: &'static [u8] = include_bytes!("../test_data/librtmp_digest_s0_s1_s2.bin");
+    static DIGEST_C2: &'static [u8] = include_bytes!("../test_data/librtmp_digest_c2.bin");
+    static SIMPLE_C0_C1: &'static [u8] = include_bytes!("../test_data/librtmp_simple_c0_c1.bin");
+
+    const LIBRTMP_C1_DIGEST_OFFSET: usize = 430;
+    const LIBRTMP_C1_DIGEST: &'static str = "845a9adb3dfb2f6a3173eef3fb3c9d18f5e7a63dac8af3a1c2132990f8f50e67";
+    const LIBRTMP_C2_SIGNATURE: &'static str = "54c4f33ce9bad9e473296decd0b7fdff29dc9fdc2ff9f43ed9555ec8ca8ede41";
+    const LIBRTMP_EXPECTED_S2_SIGNATURE: &'static str = "852702e4a3fd4c03956acb9f8bc3a8ab7c3da8af4c01ecc00b1ca2c8e5b3e784";
+
+    #[test]
+    fn finds_digest_in_librtmp_packet_1() {
+        let c1 = &DIGEST_C0_C1[1..];
+        let client_key = &GENUINE_FP_KEY[..FP_PACKET_1_KEY_LENGTH];
+
+        assert_eq!(get_digest_offset(c1, DigestSchema::Schema1), LIBRTMP_C1_DIGEST_OFFSET);
+
+        let (schema, digest) = find_digest(c1, client_key).unwrap();
+        assert_eq!(schema, DigestSchema::Schema1);
+        assert_eq!(to_hex(&digest), LIBRTMP_C1_DIGEST);
+    }
+
+    #[test]
+    fn librtmp_packet_2_is_signed_with_server_packet_1_digest() {
+        let s1 = &DIGEST_S0_S1_S2[1..1537];
+        let (_, s1_digest) = find_digest(s1, &GENUINE_FMS_KEY[..FMS_PACKET_1_KEY_LENGTH]).unwrap();
+
+        assert_eq!(to_hex(&DIGEST_C2[DIGEST_C2.len() - DIGEST_SIZE..]), LIBRTMP_C2_SIGNATURE);
+        assert!(is_packet_2_valid(DIGEST_C2, &s1_digest, &GENUINE_FP_KEY), "C2 digest not valid");
+        assert!(!is_packet_2_valid(DIGEST_C2, &from_hex(LIBRTMP_C1_DIGEST), &GENUINE_FP_KEY));
+    }
+
+    #[test]
+    fn server_packet_2_matches_signature_librtmp_expected() {
+        let s2 = &DIGEST_S0_S1_S2[1537..];
+        let mut packet = s2.to_vec();
+        sign_packet_2(&mut packet, &from_hex(LIBRTMP_C1_DIGEST), &GENUINE_FMS_KEY);
+
+        assert_eq!(to_hex(&packet[packet.len() - DIGEST_SIZE..]), LIBRTMP_EXPECTED_S2_SIGNATURE);
+        assert!(is_packet_2_valid(s2, &from_hex(LIBRTMP_C1_DIGEST), &GENUINE_FMS_KEY), "S2 digest not valid");
+    }
+
+    #[test]
+    fn no_digest_found_in_librtmp_simple_packet_1() {
+        let result = find_digest(&SIMPLE_C0_C1[1..], &GENUINE_FP_KEY[..FP_PACKET_1_KEY_LENGTH]);
+
+        assert_eq!(result, None);
+    }
+
+    #[test]
+    fn finds_schema_of_signed_packet() {
+        for schema in &[DigestSchema::Schema0, DigestSchema::Schema1] {
+            let mut packet = SIMPLE_C0_C1[1..].to_vec();
+            let digest = sign_packet_1(&mut packet, *schema, &GENUINE_FP_KEY[..FP_PACKET_1_KEY_LENGTH]);
+
+            let result = find_digest(&packet, &GENUINE_FP_KEY[..FP_PACKET_1_KEY_LENGTH]);
+            assert_eq!(result, Some((*schema, digest)));
+        }
+    }
+
+    fn to_hex(bytes: &[u8]) -> String {
+        bytes.iter().map(|x| format!("{:02x}", x)).collect()
+    }
+
+    fn from_hex(hex: &str) -> Vec<u8> {
+        (0..hex.len() / 2).map(|i| u8::from_str_radix(&hex[i * 2..i * 2 + 2], 16).unwrap()).collect()
+    }
+}
@@ -0,0 +1,49 @@
+use std::time::Instant;
+
+/// How strictly the peer's packet 2 is checked against the packet 1 we sent
+#[derive(Clone, Copy, Eq, PartialEq, Debug)]
+pub enum Packet2Validation {
+    /// Any mismatch in the echoed time, random data or digest fails the handshake
+    Strict,
+
+    /// Mismatches are recorded as warnings on the handshake and it completes
+    /// anyway.  Some older encoders do not echo packet 1 back exactly.
+    Lenient
+}
+
+#[derive(Clone, Debug)]
+pub struct HandshakeConfig {
+    /// Defaults to `Lenient`, since encoders in the wild do not always echo
+    /// packet 1 back exactly
+    pub packet_2_validation: Packet2Validation,
+
+    /// Values accepted in the peer's packet 0.  Version 6 (RTMPE) is always
+    /// rejected with `HandshakeError::EncryptedHandshakeNotSupported`, since
+    /// encrypted handshakes are not implemented.
+    pub supported_versions: Vec<u8>,
+
+    /// When this side started.  The time field of our packet 1 is the number of
+    /// milliseconds elapsed since then, so this should be shared by all
+    /// connections (e.g. when the server started) rather than created for each
+    /// handshake.
+    pub start_time: Instant,
+}
+
+impl HandshakeConfig {
+    /// Creates a config that validates packet 2 leniently and only accepts
+    /// version 3, with an epoch measured from the specified start time
+    pub fn new(start_time: Instant) -> HandshakeConfig {
+        HandshakeConfig {
+            packet_2_validation: Packet2Validation::Lenient,
+            supported_versions: vec![3],
+            start_time: start_time,
+        }
+    }
+
+    /// Milliseconds since `start_time`, wrapping the way RTMP timestamps do
+    pub fn epoch(&self) -> u32 {
+        let elapsed = self.start_time.elapsed();
+        let milliseconds = elapsed.as_secs().wrapping_mul(1000) + (elapsed.subsec_nanos() / 1_000_000) as u64;
+        milliseconds as u32
+    }
+}
@@ -7,8 +7,8 @@ quick_error! {
             description("First byte of the handshake did not start with a 3")
         }
 
-        NonZeroedTimeInPacket1 {
-            description("Packet 1's 2nd time field was expected to be empty, but wasn't")
+        EncryptedHandshakeNotSupported {
+            description("Peer requested an encrypted (RTMPE) handshake, which is not supported")
         }
 
         IncorrectPeerTime {
@@ -19,10 +19,22 @@ quick_error! {
             description("Peer did not send the correct random data back")
         }
 
+        IncorrectPacket2Digest {
+            description("Peer's packet 2 was not signed with the digest of our packet 1")
+        }
+
         Io(err: io::Error) {
             cause(err)
             description(err.description())
             from()
         }
     }
-}
\ No newline at end of file
+}
+/// Problems with the peer's handshake that were tolerated because lenient
+/// packet 2 validation is enabled
+#[derive(Clone, Copy, Eq, PartialEq, Debug)]
+pub enum HandshakeWarning {
+    IncorrectPeerTime,
+    IncorrectRandomData,
+    IncorrectPacket2Digest,
+}
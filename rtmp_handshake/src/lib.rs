@@ -1,15 +1,28 @@
 #[macro_use] extern crate quick_error;
 extern crate byteorder;
 extern crate rand;
+extern crate hmac;
+extern crate sha2;
 
 mod errors;
+mod complex;
+mod config;
 
 use std::io::{Cursor, Read, Write};
+use std::mem;
+use std::time::Instant;
 use byteorder::{BigEndian, ReadBytesExt, WriteBytesExt};
 use rand::Rng;
-use errors::HandshakeError;
+use complex::{DIGEST_SIZE, SigningKeys};
 
-#[derive(Eq,PartialEq, Debug)]
+pub use errors::{HandshakeError, HandshakeWarning};
+pub use complex::DigestSchema;
+pub use config::{HandshakeConfig, Packet2Validation};
+
+/// Version byte sent by peers requesting an encrypted handshake
+const RTMPE_VERSION: u8 = 6;
+
+#[derive(Clone, Copy, Eq, PartialEq, Debug)]
 enum State {
     WaitingForPacket0,
     WaitingForPacket1,
@@ -23,57 +36,116 @@ enum ParsedPacket{
 }
 
 #[derive(PartialEq, Eq, Debug)]
-pub struct Response(Vec<u8>);
+pub struct Response(pub Vec<u8>);
+
+#[derive(PartialEq, Eq, Debug)]
+pub enum HandshakeResult {
+    /// More bytes are needed from the peer.  The response should be sent to the
+    /// peer (and may be empty).
+    InProgress { response: Response },
+
+    /// The handshake finished.  The response should still be sent to the peer, and
+    /// any bytes received after the peer's last handshake packet are returned so
+    /// they can be passed on as RTMP chunks.
+    Completed { response: Response, remaining_bytes: Vec<u8> }
+}
+
+/// Which side of the connection the handshake is being performed for
+#[derive(Clone, Copy, Eq, PartialEq, Debug)]
+pub enum PeerType {
+    Server,
+    Client
+}
 
 pub struct Handshake {
     pub my_epoch: u32,
     pub their_epoch: u32,
     pub is_completed: bool,
 
+    /// Schema of the digest found in the peer's packet 1, or `None` if the
+    /// peer is using the simple handshake
+    pub peer_digest_schema: Option<DigestSchema>,
+
+    /// Problems with the peer's packet 2 that were accepted in lenient mode
+    pub warnings: Vec<HandshakeWarning>,
+
+    config: HandshakeConfig,
+    peer_type: PeerType,
     current_state: State,
     my_random: [u8; 1528],
+    my_digest: [u8; DIGEST_SIZE],
     their_random: [u8; 1528],
     buffer: Vec<u8>
 }
 
 impl Handshake {
-    pub fn new() -> Result<(Handshake, Response), HandshakeError> {
-        let my_epoch = 0;
-        let random_data = create_random_data();
-        let mut response_bytes = vec![3_u8];
-        let mut packet1 = try!(create_packet_bytes(my_epoch, 0, &random_data));
-        response_bytes.append(&mut packet1);
-
-        let handshake = Handshake {
+    /// Starts a handshake for the specified side of the connection.  Clients get
+    /// back C0 and C1, with C1 signed for the complex handshake (which simple
+    /// handshake servers treat as random data).  Servers get back an empty response,
+    /// since S1 has to be signed with the same schema as the client's C1.  S0 and S1
+    /// are sent along with S2 once C1 has been received.
+    ///
+    /// The epoch sent in packet 1 is the number of milliseconds since
+    /// `start_time`, which should be when the server or client started.
+    pub fn new(peer_type: PeerType, start_time: Instant) -> Result<(Handshake, Response), HandshakeError> {
+        Handshake::with_config(peer_type, HandshakeConfig::new(start_time))
+    }
+
+    /// Starts a handshake the same way as `new()`, using the specified config
+    pub fn with_config(peer_type: PeerType, config: HandshakeConfig) -> Result<(Handshake, Response), HandshakeError> {
+        let my_epoch = config.epoch();
+        let mut handshake = Handshake {
             is_completed: false,
+            peer_digest_schema: None,
+            warnings: Vec::new(),
+            config: config,
+            peer_type: peer_type,
             current_state: State::WaitingForPacket0,
             my_epoch: my_epoch,
             their_epoch: 0,
-            my_random: random_data,
+            my_random: create_random_data(),
+            my_digest: [0; DIGEST_SIZE],
             their_random: [0; 1528],
             buffer: Vec::new()
         };
 
+        let response_bytes = match peer_type {
+            PeerType::Server => Vec::new(),
+            PeerType::Client => try!(create_packet_0_and_1(&mut handshake, Some(DigestSchema::Schema1))),
+        };
+
         Ok((handshake, Response(response_bytes)))
     }
 
-    pub fn process_bytes(&mut self, data: &[u8]) -> Result<Response, HandshakeError> {
-        for index in 0..data.len() {
-            self.buffer.push(data[index].clone());
+    /// Processes bytes received from the peer, handling as many handshake packets
+    /// as are available.  Once the handshake has completed, any extra bytes are
+    /// handed back instead of being buffered.
+    pub fn process_bytes(&mut self, data: &[u8]) -> Result<HandshakeResult, HandshakeError> {
+        self.buffer.extend_from_slice(data);
+
+        let mut response_bytes = Vec::new();
+        loop {
+            let previous_state = self.current_state;
+            let Response(mut bytes) = match self.current_state {
+                State::WaitingForPacket0 => try!(process_packet_0(self)),
+                State::WaitingForPacket1 => try!(process_packet_1(self)),
+                State::WaitingForPacket2 => try!(process_packet_2(self)),
+                State::Successful => break
+            };
+
+            response_bytes.append(&mut bytes);
+
+            // No progress means we are waiting on more bytes from the peer
+            if self.current_state == previous_state {
+                break;
+            }
         }
 
-        let result = match self.current_state {
-            State::WaitingForPacket0 => process_packet_0(self),
-            State::WaitingForPacket1 => process_packet_1(self),
-            State::WaitingForPacket2 => process_packet_2(self),
-            State::Successful => Ok(Response(vec![]))
-        };
-
-        if result.is_ok() && self.buffer.len() >= 1528 {
-            self.process_bytes(&[])
-        }
-        else {
-            result
+        if self.current_state == State::Successful {
+            let remaining_bytes = mem::replace(&mut self.buffer, Vec::new());
+            Ok(HandshakeResult::Completed { response: Response(response_bytes), remaining_bytes: remaining_bytes })
+        } else {
+            Ok(HandshakeResult::InProgress { response: Response(response_bytes) })
         }
     }
 }
@@ -83,7 +155,12 @@ fn process_packet_0(handshake: &mut Handshake) -> Result<Response, HandshakeErro
         return Ok(Response(vec![]));
     }
 
-    if handshake.buffer[0] != 3 {
+    let version = handshake.buffer[0];
+    if version == RTMPE_VERSION {
+        return Err(HandshakeError::EncryptedHandshakeNotSupported)
+    }
+
+    if !handshake.config.supported_versions.contains(&version) {
         return Err(HandshakeError::BadVersionId)
     }
 
@@ -92,6 +169,26 @@ fn process_packet_0(handshake: &mut Handshake) -> Result<Response, HandshakeErro
     Ok(Response(vec![]))
 }
 
+/// Forms our packets 0 and 1, signing packet 1 with the specified schema
+fn create_packet_0_and_1(handshake: &mut Handshake, schema: Option<DigestSchema>) -> Result<Vec<u8>, HandshakeError> {
+    let version = match handshake.peer_type {
+        PeerType::Server => complex::SERVER_VERSION,
+        PeerType::Client => complex::CLIENT_VERSION,
+    };
+
+    let mut packet1 = try!(create_packet_bytes(handshake.my_epoch, version, &handshake.my_random));
+    if let Some(schema) = schema {
+        handshake.my_digest = complex::sign_packet_1(&mut packet1, schema, my_keys(handshake.peer_type).packet_1);
+
+        // Keep the signed random data, since simple handshake peers echo it back
+        handshake.my_random.copy_from_slice(&packet1[8..]);
+    }
+
+    let mut response_bytes = vec![3_u8];
+    response_bytes.append(&mut packet1);
+    Ok(response_bytes)
+}
+
 fn create_random_data() -> [u8; 1528] {
     let mut random_data = [0_u8;1528];     
     let mut rng = rand::thread_rng();
@@ -108,17 +205,38 @@ fn process_packet_1(handshake: &mut Handshake) -> Result<Response, HandshakeErro
     match packet {
         ParsedPacket::Incomplete => Ok(Response(vec![])),
         ParsedPacket::Valid{time, time2, random} => {
-            if time2 != 0 {
-                return Err(HandshakeError::NonZeroedTimeInPacket1)
-            }
+            // A valid digest means the peer wants the complex handshake, otherwise
+            // fall back to the simple handshake.  Peers often fill in the version
+            // field without signing the packet, so it is not required to be zero.
+            let full_packet = try!(create_packet_bytes(time, time2, &random));
+            let peer_digest = complex::find_digest(&full_packet, peer_keys(handshake.peer_type).packet_1);
 
             handshake.their_epoch = time;
             handshake.their_random = random;
             handshake.current_state = State::WaitingForPacket2;
 
+            // Servers send S1 signed with the schema the client used, or unsigned
+            // for simple handshake clients
+            let mut response_bytes = match handshake.peer_type {
+                PeerType::Server => try!(create_packet_0_and_1(handshake, peer_digest.map(|(schema, _)| schema))),
+                PeerType::Client => Vec::new(),
+            };
+
             // Form outgoing packet 2
-            let data = try!(create_packet_bytes(handshake.their_epoch, handshake.my_epoch, &random));
-            Ok(Response(data))
+            let mut data = match peer_digest {
+                Some((schema, digest)) => {
+                    handshake.peer_digest_schema = Some(schema);
+
+                    let mut data = try!(create_packet_bytes(handshake.their_epoch, handshake.my_epoch, &create_random_data()));
+                    complex::sign_packet_2(&mut data, &digest, my_keys(handshake.peer_type).packet_2);
+                    data
+                },
+
+                None => try!(create_packet_bytes(handshake.their_epoch, handshake.my_epoch, &random))
+            };
+
+            response_bytes.append(&mut data);
+            Ok(Response(response_bytes))
         }
     }
 }
@@ -127,15 +245,19 @@ fn process_packet_2(handshake: &mut Handshake) -> Result<Response, HandshakeErro
     let packet = try!(parse_packet(&mut handshake.buffer));
     match packet {
         ParsedPacket::Incomplete => Ok(Response(vec![])),
-        ParsedPacket::Valid{time, time2: _, random} => {
-            if time != handshake.my_epoch {
-                return Err(HandshakeError::IncorrectPeerTime)
-            }
+        ParsedPacket::Valid{time, time2, random} => {
+            if handshake.peer_digest_schema.is_some() {
+                let full_packet = try!(create_packet_bytes(time, time2, &random));
+                if !complex::is_packet_2_valid(&full_packet, &handshake.my_digest, peer_keys(handshake.peer_type).packet_2) {
+                    try!(handle_packet_2_mismatch(handshake, HandshakeWarning::IncorrectPacket2Digest));
+                }
+            } else {
+                if time != handshake.my_epoch {
+                    try!(handle_packet_2_mismatch(handshake, HandshakeWarning::IncorrectPeerTime));
+                }
 
-            for index in 0..1528 {
-                if random[index] != handshake.my_random[index] {
-                    println!("Index {} expects {} found {}", index, random[index], handshake.my_random[index]);
-                    return Err(HandshakeError::IncorrectRandomData);
+                if random[..] != handshake.my_random[..] {
+                    try!(handle_packet_2_mismatch(handshake, HandshakeWarning::IncorrectRandomData));
                 }
             }
 
@@ -146,6 +268,37 @@ fn process_packet_2(handshake: &mut Handshake) -> Result<Response, HandshakeErro
     }
 }
 
+/// Fails the handshake in strict mode, otherwise records the problem and lets
+/// the handshake continue
+fn handle_packet_2_mismatch(handshake: &mut Handshake, warning: HandshakeWarning) -> Result<(), HandshakeError> {
+    match handshake.config.packet_2_validation {
+        Packet2Validation::Lenient => {
+            handshake.warnings.push(warning);
+            Ok(())
+        },
+
+        Packet2Validation::Strict => Err(match warning {
+            HandshakeWarning::IncorrectPeerTime => HandshakeError::IncorrectPeerTime,
+            HandshakeWarning::IncorrectRandomData => HandshakeError::IncorrectRandomData,
+            HandshakeWarning::IncorrectPacket2Digest => HandshakeError::IncorrectPacket2Digest,
+        })
+    }
+}
+
+fn my_keys(peer_type: PeerType) -> SigningKeys {
+    match peer_type {
+        PeerType::Server => complex::server_keys(),
+        PeerType::Client => complex::client_keys(),
+    }
+}
+
+fn peer_keys(peer_type: PeerType) -> SigningKeys {
+    match peer_type {
+        PeerType::Server => complex::client_keys(),
+        PeerType::Client => complex::server_keys(),
+    }
+}
+
 fn create_packet_bytes(time1: u32, time2: u32, random: &[u8; 1528]) -> Result<Vec<u8>, HandshakeError> {
     let mut response_bytes = Cursor::new(Vec::new());
     try!(response_bytes.write_u32::<BigEndian>(time1));
@@ -178,7 +331,9 @@ fn parse_packet(bytes: &mut Vec<u8>) -> Result<ParsedPacket, HandshakeError> {
 mod tests {
     use super::*;
     use super::{State};
+    use complex;
     use std::io::{Cursor, Read, Write};
+    use std::time::{Duration, Instant};
     use byteorder::{BigEndian, ReadBytesExt, WriteBytesExt};
     use rand;
     use rand::Rng;
@@ -186,26 +341,34 @@ mod tests {
 
     #[test]
     fn starts_in_waitingforpacket0_stage() {
-        let (handshake, _) = Handshake::new().unwrap();
+        let (handshake, _) = Handshake::new(PeerType::Server, Instant::now()).unwrap();
         assert_eq!(handshake.current_state, State::WaitingForPacket0);
     }
 
     #[test]
-    fn returns_valid_packet_0_and_1_response() {
-        let (handshake, Response(data)) = Handshake::new().unwrap();
-        assert_eq!(data.len(), 1537);
+    fn server_waits_for_peer_packet_1_before_sending_packet_0_and_1() {
+        let (mut handshake, Response(initial_bytes)) = Handshake::new(PeerType::Server, Instant::now()).unwrap();
+        assert_eq!(initial_bytes.len(), 0);
 
-        let mut cursor = Cursor::new(data);
+        let (p1, _) = create_packet_1(15);
+        let mut data = create_packet_0(3);
+        data.extend(p1);
+
+        let mut bytes = get_response_bytes(handshake.process_bytes(&data).unwrap());
+        assert_eq!(bytes.len(), 1537 + 1536);
+        bytes.truncate(1537);
+
+        let mut cursor = Cursor::new(bytes);
         let version = cursor.read_u8().unwrap();
         let time = cursor.read_u32::<BigEndian>().unwrap();
-        let zeros = cursor.read_u32::<BigEndian>().unwrap();
+        let server_version = cursor.read_u32::<BigEndian>().unwrap();
 
         let mut random = [0_u8; 1528];
         cursor.read(&mut random).unwrap();
 
         assert_eq!(version, 3);
         assert_eq!(time, handshake.my_epoch);
-        assert_eq!(zeros, 0);
+        assert_eq!(server_version, complex::SERVER_VERSION);
 
         for index in 0..1528 {
             assert_eq!(random[index], handshake.my_random[index]);
@@ -214,9 +377,9 @@ mod tests {
 
     #[test]
     fn accepts_packet_0_if_its_value_is_3() {
-        let (mut handshake, _) = Handshake::new().unwrap();
+        let (mut handshake, _) = Handshake::new(PeerType::Server, Instant::now()).unwrap();
         let data = create_packet_0(3);
-        let Response(bytes) = handshake.process_bytes(&data).unwrap();
+        let bytes = get_response_bytes(handshake.process_bytes(&data).unwrap());
 
         assert_eq!(handshake.current_state, State::WaitingForPacket1);
         assert_eq!(bytes, vec![]);
@@ -224,7 +387,7 @@ mod tests {
 
     #[test]
     fn returns_error_if_invalid_version_value_processed() {
-        let (mut handshake, _) = Handshake::new().unwrap();
+        let (mut handshake, _) = Handshake::new(PeerType::Server, Instant::now()).unwrap();
         let data = create_packet_0(4);
         match handshake.process_bytes(&data) {
             Err(HandshakeError::BadVersionId) => assert!(true),
@@ -236,12 +399,14 @@ mod tests {
     #[test]
     fn accepts_valid_packet_1_and_returns_packet_2_response() {
         let epoch = 15;
-        let (mut handshake, _) = Handshake::new().unwrap();
+        let (mut handshake, _) = Handshake::new(PeerType::Server, Instant::now()).unwrap();
         let p0 = create_packet_0(3);
         let (p1, local_random) = create_packet_1(epoch);
 
         handshake.process_bytes(&p0).unwrap();
-        let Response(mut bytes) = handshake.process_bytes(&p1).unwrap();
+        let mut bytes = get_response_bytes(handshake.process_bytes(&p1).unwrap());
+        bytes.drain(0..1537);
+
         let packet = super::parse_packet(&mut bytes).unwrap();
         let (time, time2, random) = match packet {
             super::ParsedPacket::Incomplete => panic!("Incomplete packet parsed"),
@@ -262,7 +427,7 @@ mod tests {
     #[test]
     fn accepts_valid_handshake_packets() {
         let epoch = 15;
-        let (mut handshake, _) = Handshake::new().unwrap();
+        let (mut handshake, _) = Handshake::new(PeerType::Server, Instant::now()).unwrap();
         let p0 = create_packet_0(3);
         let (p1, local_random) = create_packet_1(epoch);
         let p2 = create_packet_2(handshake.my_epoch, epoch, &handshake.my_random);
@@ -271,7 +436,9 @@ mod tests {
         handshake.process_bytes(&p0).unwrap();
 
         // Packet 1 test
-        let Response(mut bytes) = handshake.process_bytes(&p1).unwrap();
+        let mut bytes = get_response_bytes(handshake.process_bytes(&p1).unwrap());
+        bytes.drain(0..1537);
+
         let packet = super::parse_packet(&mut bytes).unwrap();
         let (time, time2, random) = match packet {
             super::ParsedPacket::Incomplete => panic!("Incomplete packet parsed"),
@@ -289,7 +456,7 @@ mod tests {
         }
 
         // Packet 2 test
-        let Response(bytes) = handshake.process_bytes(&p2).unwrap();
+        let bytes = get_response_bytes(handshake.process_bytes(&p2).unwrap());
         assert_eq!(0, bytes.len());
         assert!(handshake.is_completed, "Handshake not marked as completed");
     }
@@ -297,7 +464,7 @@ mod tests {
     #[test]
     fn error_when_bad_time_in_packet_2() {
         let epoch = 15;
-        let (mut handshake, _) = Handshake::new().unwrap();
+        let (mut handshake, _) = Handshake::with_config(PeerType::Server, create_strict_config()).unwrap();
         let p0 = create_packet_0(3);
         let (p1, _) = create_packet_1(epoch);
         let p2 = create_packet_2(handshake.my_epoch + 1, epoch, &handshake.my_random);
@@ -314,13 +481,13 @@ mod tests {
     #[test]
     fn error_when_bad_random_data_in_packet_2() {
         let epoch = 15;
-        let (mut handshake, _) = Handshake::new().unwrap();
+        let (mut handshake, _) = Handshake::with_config(PeerType::Server, create_strict_config()).unwrap();
         let mut random_copy = [0_u8; 1528];
         for index in 0..1528 {
             random_copy[index] = handshake.my_random[index]
         }
 
-        random_copy[0] = random_copy[0] + 1;
+        random_copy[0] = random_copy[0].wrapping_add(1);
 
         let p0 = create_packet_0(3);
         let (p1, _) = create_packet_1(epoch);
@@ -336,18 +503,409 @@ mod tests {
     }
 
     #[test]
-    fn two_handshake_instances_can_successfully_complete_against_each_other() {
-        let (mut server, Response(s0_and_s1)) = Handshake::new().unwrap();
-        let (mut client, Response(c0_and_c1)) = Handshake::new().unwrap();
+    fn packet_1_is_signed_with_fms_key_using_schema_of_peer_packet_1() {
+        for schema in &[DigestSchema::Schema0, DigestSchema::Schema1] {
+            let (mut handshake, _) = Handshake::new(PeerType::Server, Instant::now()).unwrap();
+            let (c1, _) = create_complex_packet_1(15, *schema);
 
-        let Response(s2) = server.process_bytes(&c0_and_c1).unwrap();
-        let Response(c2) = client.process_bytes(&s0_and_s1).unwrap();
+            handshake.process_bytes(&create_packet_0(3)).unwrap();
+            let data = get_response_bytes(handshake.process_bytes(&c1).unwrap());
+            let digest = complex::find_digest(&data[1..1537], &complex::GENUINE_FMS_KEY[..complex::FMS_PACKET_1_KEY_LENGTH]);
+
+            assert_eq!(digest, Some((*schema, handshake.my_digest)));
+        }
+    }
+
+    #[test]
+    fn packet_1_is_not_signed_for_simple_handshake_peer() {
+        let (mut handshake, _) = Handshake::new(PeerType::Server, Instant::now()).unwrap();
+        let random = handshake.my_random;
+        let (p1, _) = create_packet_1(15);
+
+        handshake.process_bytes(&create_packet_0(3)).unwrap();
+        let data = get_response_bytes(handshake.process_bytes(&p1).unwrap());
+
+        assert_eq!(&data[9..1537], &random[..]);
+    }
+
+    #[test]
+    fn complex_handshake_completes_when_packet_1_has_valid_digest() {
+        for schema in &[DigestSchema::Schema0, DigestSchema::Schema1] {
+            let (mut handshake, _) = Handshake::with_config(PeerType::Server, create_strict_config()).unwrap();
+            let (c1, c1_digest) = create_complex_packet_1(15, *schema);
+
+            handshake.process_bytes(&create_packet_0(3)).unwrap();
+            let mut s2 = get_response_bytes(handshake.process_bytes(&c1).unwrap());
+            let s0_and_s1: Vec<u8> = s2.drain(0..1537).collect();
+
+            assert_eq!(handshake.peer_digest_schema, Some(*schema));
+            assert!(complex::is_packet_2_valid(&s2, &c1_digest, &complex::GENUINE_FMS_KEY), "S2 digest not valid");
+
+            // Sign C2 the way a client does, with the digest of S1
+            let s1 = &s0_and_s1[1..];
+            let (_, s1_digest) = complex::find_digest(s1, &complex::GENUINE_FMS_KEY[..complex::FMS_PACKET_1_KEY_LENGTH]).unwrap();
+            let mut c2 = create_packet_2(0, 0, &[0_u8; 1528]);
+            complex::sign_packet_2(&mut c2, &s1_digest, &complex::GENUINE_FP_KEY);
+
+            let bytes = get_response_bytes(handshake.process_bytes(&c2).unwrap());
+            assert_eq!(bytes.len(), 0);
+            assert!(handshake.is_completed, "Handshake not marked as completed");
+        }
+    }
+
+    #[test]
+    fn error_when_complex_packet_2_has_bad_digest() {
+        let (mut handshake, _) = Handshake::with_config(PeerType::Server, create_strict_config()).unwrap();
+        let (c1, _) = create_complex_packet_1(15, DigestSchema::Schema0);
+        let c2 = create_packet_2(handshake.my_epoch, 15, &handshake.my_random);
+
+        handshake.process_bytes(&create_packet_0(3)).unwrap();
+        handshake.process_bytes(&c1).unwrap();
+        match handshake.process_bytes(&c2) {
+            Err(HandshakeError::IncorrectPacket2Digest) => (),
+            Ok(_) => panic!("Expected HandshakeError::IncorrectPacket2Digest but got Ok()"),
+            Err(x) => panic!("Expected HandshakeError::IncorrectPacket2Digest but got Err({})", x)
+        }
+    }
+
+    #[test]
+    fn falls_back_to_simple_handshake_when_no_digest_validates() {
+        let (mut handshake, _) = Handshake::new(PeerType::Server, Instant::now()).unwrap();
+        let (p1, _) = create_packet_1(15);
+
+        handshake.process_bytes(&create_packet_0(3)).unwrap();
+        handshake.process_bytes(&p1).unwrap();
+
+        assert_eq!(handshake.peer_digest_schema, None);
+    }
+
+    #[test]
+    fn simple_handshake_completes_when_unsigned_packet_1_has_version() {
+        let (mut handshake, _) = Handshake::with_config(PeerType::Server, create_strict_config()).unwrap();
+        let (mut p1, _) = create_packet_1(15);
+        p1[4..8].copy_from_slice(&[0x80, 0x00, 0x07, 0x02]);
+
+        handshake.process_bytes(&create_packet_0(3)).unwrap();
+        let response = get_response_bytes(handshake.process_bytes(&p1).unwrap());
+        assert_eq!(handshake.peer_digest_schema, None);
+        assert_eq!(&response[1537 + 8..], &p1[8..]);
+
+        // Simple handshake peers echo packet 1 back
+        handshake.process_bytes(&response[1..1537]).unwrap();
+        assert!(handshake.is_completed, "Handshake not marked as completed");
+    }
+
+    #[test]
+    fn client_and_server_can_complete_against_each_other() {
+        let (mut server, _) = Handshake::with_config(PeerType::Server, create_strict_config()).unwrap();
+        let (mut client, Response(c0_and_c1)) = Handshake::with_config(PeerType::Client, create_strict_config()).unwrap();
+
+        let s0_s1_and_s2 = get_response_bytes(server.process_bytes(&c0_and_c1).unwrap());
+        let c2 = get_response_bytes(client.process_bytes(&s0_s1_and_s2).unwrap());
 
         server.process_bytes(&c2).unwrap();
-        client.process_bytes(&s2).unwrap();
 
         assert!(server.is_completed, "server not completed");
         assert!(client.is_completed, "client not completed");
+        assert_eq!(server.peer_digest_schema, Some(DigestSchema::Schema1));
+        assert_eq!(client.peer_digest_schema, Some(DigestSchema::Schema1));
+    }
+
+    #[test]
+    fn client_and_server_can_complete_when_bytes_arrive_in_pieces() {
+        let (mut server, _) = Handshake::with_config(PeerType::Server, create_strict_config()).unwrap();
+        let (mut client, Response(c0_and_c1)) = Handshake::with_config(PeerType::Client, create_strict_config()).unwrap();
+
+        let mut s0_s1_and_s2 = Vec::new();
+        for piece in c0_and_c1.chunks(100) {
+            let mut bytes = get_response_bytes(server.process_bytes(piece).unwrap());
+            s0_s1_and_s2.append(&mut bytes);
+        }
+
+        let mut c2 = Vec::new();
+        for piece in s0_s1_and_s2.chunks(100) {
+            let mut bytes = get_response_bytes(client.process_bytes(piece).unwrap());
+            c2.append(&mut bytes);
+        }
+
+        for piece in c2.chunks(100) {
+            server.process_bytes(piece).unwrap();
+        }
+
+        assert!(server.is_completed, "server not completed");
+        assert!(client.is_completed, "client not completed");
+    }
+
+    #[test]
+    fn client_returns_valid_packet_0_and_1() {
+        let (handshake, Response(data)) = Handshake::new(PeerType::Client, Instant::now()).unwrap();
+        assert_eq!(data.len(), 1537);
+        assert_eq!(data[0], 3);
+
+        let digest = complex::find_digest(&data[1..], &complex::GENUINE_FP_KEY[..complex::FP_PACKET_1_KEY_LENGTH]);
+        assert_eq!(digest, Some((DigestSchema::Schema1, handshake.my_digest)));
+    }
+
+    #[test]
+    fn client_falls_back_to_simple_handshake_with_simple_server() {
+        let (mut client, Response(c0_and_c1)) = Handshake::new(PeerType::Client, Instant::now()).unwrap();
+        let (s1, server_random) = create_packet_1(20);
+
+        // A simple server echoes C1 back as S2
+        let mut c1_random = [0_u8; 1528];
+        c1_random.copy_from_slice(&c0_and_c1[9..]);
+        let s2 = create_packet_2(client.my_epoch, 20, &c1_random);
+
+        client.process_bytes(&create_packet_0(3)).unwrap();
+        let c2 = get_response_bytes(client.process_bytes(&s1).unwrap());
+        client.process_bytes(&s2).unwrap();
+
+        assert_eq!(client.peer_digest_schema, None);
+        assert_eq!(&c2[8..], &server_random[..]);
+        assert!(client.is_completed, "client not completed");
+    }
+
+    #[test]
+    fn client_returns_error_when_server_version_is_invalid() {
+        let (mut client, _) = Handshake::new(PeerType::Client, Instant::now()).unwrap();
+        match client.process_bytes(&create_packet_0(4)) {
+            Err(HandshakeError::BadVersionId) => (),
+            Ok(_) => panic!("Expected HandshakeError::BadVersionId but got Ok()"),
+            Err(x) => panic!("Expected HandshakeError::BadVersionId but got Err({})", x)
+        }
+    }
+
+    #[test]
+    fn client_returns_error_when_server_packet_2_has_bad_digest() {
+        let (mut server, _) = Handshake::new(PeerType::Server, Instant::now()).unwrap();
+        let (mut client, Response(c0_and_c1)) = Handshake::with_config(PeerType::Client, create_strict_config()).unwrap();
+
+        let mut s0_s1_and_s2 = get_response_bytes(server.process_bytes(&c0_and_c1).unwrap());
+        s0_s1_and_s2[1537] = s0_s1_and_s2[1537].wrapping_add(1);
+
+        match client.process_bytes(&s0_s1_and_s2) {
+            Err(HandshakeError::IncorrectPacket2Digest) => (),
+            Ok(_) => panic!("Expected HandshakeError::IncorrectPacket2Digest but got Ok()"),
+            Err(x) => panic!("Expected HandshakeError::IncorrectPacket2Digest but got Err({})", x)
+        }
+    }
+
+    #[test]
+    fn bytes_after_packet_2_are_returned_on_completion() {
+        let (mut server, _) = Handshake::new(PeerType::Server, Instant::now()).unwrap();
+        let (mut client, Response(c0_and_c1)) = Handshake::new(PeerType::Client, Instant::now()).unwrap();
+
+        let s0_s1_and_s2 = get_response_bytes(server.process_bytes(&c0_and_c1).unwrap());
+        let mut c2 = get_response_bytes(client.process_bytes(&s0_s1_and_s2).unwrap());
+        c2.extend_from_slice(&[1, 2, 3, 4]);
+
+        match server.process_bytes(&c2).unwrap() {
+            HandshakeResult::Completed { response: Response(bytes), remaining_bytes } => {
+                assert_eq!(bytes.len(), 0);
+                assert_eq!(remaining_bytes, vec![1, 2, 3, 4]);
+            },
+
+            x => panic!("Expected completed result, instead received {:?}", x)
+        }
+    }
+
+    #[test]
+    fn all_packets_in_one_read_returns_response_and_remaining_bytes() {
+        let (mut handshake, _) = Handshake::with_config(PeerType::Server, create_strict_config()).unwrap();
+        let (p1, _) = create_packet_1(15);
+        let s1_random = handshake.my_random;
+
+        let mut data = create_packet_0(3);
+        data.extend(p1);
+        data.extend(create_packet_2(handshake.my_epoch, 15, &s1_random));
+        data.extend_from_slice(&[5, 6]);
+
+        match handshake.process_bytes(&data).unwrap() {
+            HandshakeResult::Completed { response: Response(bytes), remaining_bytes } => {
+                assert_eq!(bytes.len(), 1537 + 1536);
+                assert_eq!(remaining_bytes, vec![5, 6]);
+            },
+
+            x => panic!("Expected completed result, instead received {:?}", x)
+        }
+    }
+
+    #[test]
+    fn bytes_received_after_completion_are_passed_through() {
+        let (mut server, _) = Handshake::new(PeerType::Server, Instant::now()).unwrap();
+        let (mut client, Response(c0_and_c1)) = Handshake::new(PeerType::Client, Instant::now()).unwrap();
+
+        let s0_s1_and_s2 = get_response_bytes(server.process_bytes(&c0_and_c1).unwrap());
+        let c2 = get_response_bytes(client.process_bytes(&s0_s1_and_s2).unwrap());
+        server.process_bytes(&c2).unwrap();
+
+        let result = server.process_bytes(&[7, 8, 9]).unwrap();
+        assert_eq!(result, HandshakeResult::Completed { response: Response(vec![]), remaining_bytes: vec![7, 8, 9] });
+    }
+
+    #[test]
+    fn error_when_peer_requests_encrypted_handshake() {
+        let (mut handshake, _) = Handshake::new(PeerType::Server, Instant::now()).unwrap();
+        match handshake.process_bytes(&create_packet_0(6)) {
+            Err(HandshakeError::EncryptedHandshakeNotSupported) => (),
+            Ok(_) => panic!("Expected HandshakeError::EncryptedHandshakeNotSupported but got Ok()"),
+            Err(x) => panic!("Expected HandshakeError::EncryptedHandshakeNotSupported but got Err({})", x)
+        }
+    }
+
+    #[test]
+    fn accepts_additional_configured_versions() {
+        let mut config = HandshakeConfig::new(Instant::now());
+        config.supported_versions = vec![3, 4];
+
+        let (mut handshake, _) = Handshake::with_config(PeerType::Server, config).unwrap();
+        handshake.process_bytes(&create_packet_0(4)).unwrap();
+
+        assert_eq!(handshake.current_state, State::WaitingForPacket1);
+    }
+
+    #[test]
+    fn packet_1_time_is_milliseconds_since_start_time() {
+        let mut config = HandshakeConfig::new(Instant::now());
+        config.start_time = Instant::now() - Duration::from_secs(5);
+
+        let (handshake, Response(data)) = Handshake::with_config(PeerType::Client, config).unwrap();
+        let time = Cursor::new(&data[1..5]).read_u32::<BigEndian>().unwrap();
+
+        assert!(handshake.my_epoch >= 5000 && handshake.my_epoch < 60000, "Unexpected epoch of {}", handshake.my_epoch);
+        assert_eq!(time, handshake.my_epoch);
+    }
+
+    #[test]
+    fn epoch_is_measured_from_start_time_passed_in() {
+        let start_time = Instant::now() - Duration::from_secs(30);
+        let (first, _) = Handshake::new(PeerType::Server, start_time).unwrap();
+        let (second, _) = Handshake::new(PeerType::Client, start_time).unwrap();
+
+        assert!(first.my_epoch >= 30000, "Unexpected epoch of {}", first.my_epoch);
+        assert!(second.my_epoch >= first.my_epoch, "Epoch went backwards from {} to {}", first.my_epoch, second.my_epoch);
+    }
+
+    #[test]
+    fn lenient_mode_records_warnings_for_bad_simple_packet_2() {
+        assert_eq!(HandshakeConfig::new(Instant::now()).packet_2_validation, Packet2Validation::Lenient);
+
+        let (mut handshake, _) = Handshake::new(PeerType::Server, Instant::now()).unwrap();
+        let mut random_copy = handshake.my_random;
+        random_copy[10] = random_copy[10].wrapping_add(1);
+
+        let (p1, _) = create_packet_1(15);
+        let p2 = create_packet_2(handshake.my_epoch.wrapping_add(1), 15, &random_copy);
+
+        handshake.process_bytes(&create_packet_0(3)).unwrap();
+        handshake.process_bytes(&p1).unwrap();
+        handshake.process_bytes(&p2).unwrap();
+
+        assert!(handshake.is_completed, "Handshake not marked as completed");
+        assert_eq!(handshake.warnings, vec![HandshakeWarning::IncorrectPeerTime, HandshakeWarning::IncorrectRandomData]);
+    }
+
+    #[test]
+    fn lenient_mode_records_warning_for_bad_complex_packet_2() {
+        let mut config = HandshakeConfig::new(Instant::now());
+        config.packet_2_validation = Packet2Validation::Lenient;
+
+        let (mut handshake, _) = Handshake::with_config(PeerType::Server, config).unwrap();
+        let (c1, _) = create_complex_packet_1(15, DigestSchema::Schema0);
+        let c2 = create_packet_2(handshake.my_epoch, 15, &handshake.my_random);
+
+        handshake.process_bytes(&create_packet_0(3)).unwrap();
+        handshake.process_bytes(&c1).unwrap();
+        handshake.process_bytes(&c2).unwrap();
+
+        assert!(handshake.is_completed, "Handshake not marked as completed");
+        assert_eq!(handshake.warnings, vec![HandshakeWarning::IncorrectPacket2Digest]);
+    }
+
+    #[test]
+    fn valid_handshake_has_no_warnings() {
+        let (mut server, _) = Handshake::new(PeerType::Server, Instant::now()).unwrap();
+        let (mut client, Response(c0_and_c1)) = Handshake::new(PeerType::Client, Instant::now()).unwrap();
+
+        let s0_s1_and_s2 = get_response_bytes(server.process_bytes(&c0_and_c1).unwrap());
+        let c2 = get_response_bytes(client.process_bytes(&s0_s1_and_s2).unwrap());
+        server.process_bytes(&c2).unwrap();
+
+        assert!(server.is_completed, "server not completed");
+        assert_eq!(server.warnings, vec![]);
+    }
+
+    #[test]
+    fn server_answers_digest_packet_1_captured_from_librtmp() {
+        let c0_and_c1 = include_bytes!("../test_data/librtmp_digest_c0_c1.bin");
+        let client_key = &complex::GENUINE_FP_KEY[..complex::FP_PACKET_1_KEY_LENGTH];
+        let (_, c1_digest) = complex::find_digest(&c0_and_c1[1..], client_key).unwrap();
+
+        let (mut server, _) = Handshake::new(PeerType::Server, Instant::now()).unwrap();
+        let response = get_response_bytes(server.process_bytes(&c0_and_c1[..]).unwrap());
+
+        let server_key = &complex::GENUINE_FMS_KEY[..complex::FMS_PACKET_1_KEY_LENGTH];
+        let s1_digest = complex::find_digest(&response[1..1537], server_key);
+
+        assert_eq!(server.peer_digest_schema, Some(DigestSchema::Schema1));
+        assert_eq!(s1_digest, Some((DigestSchema::Schema1, server.my_digest)));
+        assert!(complex::is_packet_2_valid(&response[1537..], &c1_digest, &complex::GENUINE_FMS_KEY), "S2 digest not valid");
+    }
+
+    #[test]
+    fn server_completes_simple_handshake_captured_from_librtmp() {
+        let c0_and_c1 = include_bytes!("../test_data/librtmp_simple_c0_c1.bin");
+
+        let (mut server, _) = Handshake::with_config(PeerType::Server, create_strict_config()).unwrap();
+        let response = get_response_bytes(server.process_bytes(&c0_and_c1[..]).unwrap());
+
+        assert_eq!(server.peer_digest_schema, None);
+        assert_eq!(&response[1537 + 8..], &c0_and_c1[9..]);
+
+        // The captured C2 answers the S1 librtmp was sent during the capture
+        let captured_s1 = &include_bytes!("../test_data/librtmp_simple_s0_s1_s2.bin")[1..1537];
+        server.my_epoch = (&captured_s1[0..4]).read_u32::<BigEndian>().unwrap();
+        server.my_random.copy_from_slice(&captured_s1[8..]);
+
+        let c2 = include_bytes!("../test_data/librtmp_simple_c2.bin");
+        server.process_bytes(&c2[..]).unwrap();
+
+        assert!(server.is_completed, "Handshake not marked as completed");
+        assert_eq!(server.warnings, vec![]);
+    }
+
+    #[test]
+    fn client_completes_simple_handshake_with_unsigned_packet_1_captured_from_server() {
+        // The captured S1 is unsigned but has the server version filled in, and
+        // its S2 echoes the captured C1
+        let c0_and_c1 = include_bytes!("../test_data/librtmp_simple_c0_c1.bin");
+        let s0_s1_and_s2 = include_bytes!("../test_data/librtmp_simple_s0_s1_s2.bin");
+        assert!((&s0_s1_and_s2[5..9]).read_u32::<BigEndian>().unwrap() != 0, "S1 version is zero");
+
+        let (mut client, _) = Handshake::with_config(PeerType::Client, create_strict_config()).unwrap();
+        client.my_epoch = (&c0_and_c1[1..5]).read_u32::<BigEndian>().unwrap();
+        client.my_random.copy_from_slice(&c0_and_c1[9..]);
+
+        let c2 = get_response_bytes(client.process_bytes(&s0_s1_and_s2[..]).unwrap());
+
+        assert!(client.is_completed, "Handshake not marked as completed");
+        assert_eq!(client.peer_digest_schema, None);
+        assert_eq!(client.warnings, vec![]);
+        assert_eq!(&c2[..4], &s0_s1_and_s2[1..5]);
+        assert_eq!(&c2[8..], &s0_s1_and_s2[9..1537]);
+    }
+
+    fn create_strict_config() -> HandshakeConfig {
+        let mut config = HandshakeConfig::new(Instant::now());
+        config.packet_2_validation = Packet2Validation::Strict;
+        config
+    }
+
+    fn get_response_bytes(result: HandshakeResult) -> Vec<u8> {
+        match result {
+            HandshakeResult::InProgress { response: Response(bytes) } => bytes,
+            HandshakeResult::Completed { response: Response(bytes), .. } => bytes
+        }
     }
 
     fn create_packet_0(version_id: u8) -> Vec<u8> {
@@ -370,6 +928,14 @@ mod tests {
         (bytes.into_inner(), random_data)
     }
 
+    fn create_complex_packet_1(epoch: u32, schema: DigestSchema) -> (Vec<u8>, [u8; 32]) {
+        let (mut packet, _) = create_packet_1(epoch);
+        packet[4..8].copy_from_slice(&[0x80, 0x00, 0x07, 0x02]);
+
+        let digest = complex::sign_packet_1(&mut packet, schema, &complex::GENUINE_FP_KEY[..complex::FP_PACKET_1_KEY_LENGTH]);
+        (packet, digest)
+    }
+
     fn create_packet_2(epoch: u32, epoch2: u32, random: &[u8; 1528]) -> Vec<u8> {
         let mut bytes = Cursor::new(Vec::new());
         bytes.write_u32::<BigEndian>(epoch).unwrap();
@@ -1,5 +1,5 @@
 use std::io::Cursor;
-use amf0::Amf0Value;
+use amf0::{Amf0Value, Amf0DecodeLimits};
 use amf0;
 
 use errors::{MessageDeserializationError, MessageSerializationError};
@@ -24,9 +24,9 @@ pub fn serialize(command_name: String,
     })
 }
 
-pub fn deserialize(data: Vec<u8>) -> Result<RtmpMessage, MessageDeserializationError> {
+pub fn deserialize(data: Vec<u8>, limits: &Amf0DecodeLimits) -> Result<RtmpMessage, MessageDeserializationError> {
     let mut cursor = Cursor::new(data);
-        let mut values = try!(amf0::deserialize(&mut cursor));
+        let mut values = try!(amf0::deserialize_with_limits(&mut cursor, limits));
         if values.len() < 3 {
             return Err(MessageDeserializationError::InvalidMessageFormat);
         }
@@ -67,19 +67,19 @@ pub fn deserialize(data: Vec<u8>) -> Result<RtmpMessage, MessageDeserializationE
 #[cfg(test)]
 mod tests {
     use std::io::Cursor;
-    use std::collections::HashMap;
-    use amf0::Amf0Value;
+    use amf0::{Amf0Value, Amf0Properties, Amf0DecodeLimits, Amf0DeserializationError};
     use amf0;
 
+    use errors::MessageDeserializationError;
     use rtmp_message::RtmpMessage;
 
     #[test]
     fn can_serialize_message() {
-        let mut properties1 = HashMap::new();
+        let mut properties1 = Amf0Properties::new();
         properties1.insert("prop1".to_string(), Amf0Value::Utf8String("abc".to_string()));
         properties1.insert("prop2".to_string(), Amf0Value::Null);
 
-        let mut properties2 = HashMap::new();
+        let mut properties2 = Amf0Properties::new();
         properties2.insert("prop1".to_string(), Amf0Value::Utf8String("abc".to_string()));
         properties2.insert("prop2".to_string(), Amf0Value::Null);  
 
@@ -108,11 +108,11 @@ mod tests {
 
     #[test]
     fn can_deserialize_message() {
-        let mut properties1 = HashMap::new();
+        let mut properties1 = Amf0Properties::new();
         properties1.insert("prop1".to_string(), Amf0Value::Utf8String("abc".to_string()));
         properties1.insert("prop2".to_string(), Amf0Value::Null);
 
-        let mut properties2 = HashMap::new();
+        let mut properties2 = Amf0Properties::new();
         properties2.insert("prop1".to_string(), Amf0Value::Utf8String("abc".to_string()));
         properties2.insert("prop2".to_string(), Amf0Value::Null);        
 
@@ -137,4 +137,28 @@ mod tests {
 
         assert_eq!(expected, result);
     }
-}
\ No newline at end of file
+
+    #[test]
+    fn error_when_command_goes_over_amf0_limits() {
+        let mut inner_properties = Amf0Properties::new();
+        inner_properties.insert("prop1".to_string(), Amf0Value::Null);
+
+        let mut properties = Amf0Properties::new();
+        properties.insert("inner".to_string(), Amf0Value::Object(inner_properties));
+
+        let values = vec![
+            Amf0Value::Utf8String("test".to_string()),
+            Amf0Value::Number(23.0),
+            Amf0Value::Object(properties),
+        ];
+
+        let bytes = amf0::serialize(&values).unwrap();
+        let limits = Amf0DecodeLimits { max_depth: 1, .. Amf0DecodeLimits::default() };
+        let result = RtmpMessage::deserialize_with_limits(bytes, 20, &limits);
+
+        match result {
+            Err(MessageDeserializationError::Amf0DeserializationError(Amf0DeserializationError::MaxDepthExceeded(1))) => (),
+            x => panic!("Expected Amf0DeserializationError, instead received {:?}", x)
+        }
+    }
+}
@@ -1,6 +1,6 @@
 use std::io::Cursor;
 use amf0;
-use amf0::Amf0Value;
+use amf0::{Amf0Value, Amf0DecodeLimits};
 
 use errors::{MessageDeserializationError, MessageSerializationError};
 use rtmp_message::{RtmpMessage, RawRtmpMessage};
@@ -14,9 +14,9 @@ pub fn serialize(values: Vec<Amf0Value>) -> Result<RawRtmpMessage, MessageSerial
     })
 }
 
-pub fn deserialize(data: Vec<u8>) -> Result<RtmpMessage, MessageDeserializationError> {
+pub fn deserialize(data: Vec<u8>, limits: &Amf0DecodeLimits) -> Result<RtmpMessage, MessageDeserializationError> {
     let mut cursor = Cursor::new(data);
-    let values = try!(amf0::deserialize(&mut cursor));
+    let values = try!(amf0::deserialize_with_limits(&mut cursor, limits));
 
     Ok(RtmpMessage::Amf0Data {
         values: values
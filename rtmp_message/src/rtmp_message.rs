@@ -1,5 +1,5 @@
 use rtmp_time::RtmpTimestamp;
-use amf0::Amf0Value;
+use amf0::{Amf0Value, Amf0DecodeLimits};
 
 use errors::{MessageDeserializationError, MessageSerializationError};
 use messages;
@@ -77,7 +77,15 @@ impl RtmpMessage {
         }
     }
 
+    /// Deserializes the message, applying the default AMF0 decode limits to
+    /// any AMF0 encoded values
     pub fn deserialize(bytes: Vec<u8>, type_id: u8) -> Result<Self, MessageDeserializationError> {
+        RtmpMessage::deserialize_with_limits(bytes, type_id, &Amf0DecodeLimits::default())
+    }
+
+    /// Deserializes the message, failing if any AMF0 encoded values go over the
+    /// specified limits
+    pub fn deserialize_with_limits(bytes: Vec<u8>, type_id: u8, amf0_limits: &Amf0DecodeLimits) -> Result<Self, MessageDeserializationError> {
         match type_id {
             1 => messages::set_chunk_size::deserialize(bytes),
             2 => messages::abort::deserialize(bytes),
@@ -87,8 +95,8 @@ impl RtmpMessage {
             6 => messages::set_peer_bandwidth::deserialize(bytes),
             8 => messages::audio_data::deserialize(bytes),
             9 => messages::video_data::deserialize(bytes),
-            18 => messages::amf0_data::deserialize(bytes),
-            20 => messages::amf0_command::deserialize(bytes),
+            18 => messages::amf0_data::deserialize(bytes, amf0_limits),
+            20 => messages::amf0_command::deserialize(bytes, amf0_limits),
             _ => Ok(RtmpMessage::Unknown { type_id: type_id, data: bytes })
         }
     }
@@ -1,5 +1,6 @@
 use std::mem;
 use rtmp_time::RtmpTimestamp;
+use amf0::Amf0DecodeLimits;
 
 use errors::{MessageDeserializationError, MessageSerializationError};
 use MessagePayload;
@@ -13,9 +14,15 @@ pub struct RtmpMessageDetails {
 }
 
 impl RtmpMessageDetails {
-    pub fn from_payload(mut payload: MessagePayload) -> Result<Self, MessageDeserializationError> {
+    pub fn from_payload(payload: MessagePayload) -> Result<Self, MessageDeserializationError> {
+        RtmpMessageDetails::from_payload_with_limits(payload, &Amf0DecodeLimits::default())
+    }
+
+    /// Creates the message details from the payload, failing if any AMF0 encoded
+    /// values go over the specified limits
+    pub fn from_payload_with_limits(mut payload: MessagePayload, amf0_limits: &Amf0DecodeLimits) -> Result<Self, MessageDeserializationError> {
         let data = mem::replace(&mut payload.data, vec![]);
-        let message = try!(RtmpMessage::deserialize(data, payload.type_id));
+        let message = try!(RtmpMessage::deserialize_with_limits(data, payload.type_id, amf0_limits));
 
         Ok(RtmpMessageDetails {
             rtmp_timestamp: payload.timestamp,
@@ -8,5 +8,10 @@ quick_error! {
         AllRequestIdsInUse {
             description("All request ids values (u32) are currently marked as outstanding")
         }
+
+        StreamNotPlaying(stream_id: u32) {
+            description("Media can only be sent to a stream that is playing")
+            display("Stream id {} is not playing", stream_id)
+        }
     }
 }
@@ -1,4 +1,7 @@
+use rtmp_time::RtmpTimestamp;
+
 use metadata::StreamMetadata;
+use stream::PublishMode;
 
 #[derive(PartialEq, Debug)]
 pub enum ProcessorEvent {
@@ -6,9 +9,15 @@ pub enum ProcessorEvent {
     SelfChunkSizeChanged { new_chunk_size: u32 },
     ConnectionRequested { request_id: u32, application_name: String },
     ReleaseStreamRequested { request_id: u32, application_name: String, stream_key: String },
-    PublishStreamRequested { request_id: u32, application_name: String, stream_key: String },
+    PublishStreamRequested { request_id: u32, application_name: String, stream_key: String, mode: PublishMode },
+    PublishStreamFinished { application_name: String, stream_key: String },
+    PlayStreamRequested { request_id: u32, application_name: String, stream_key: String, stream_id: u32, start: f64, duration: f64, reset: bool },
+    PlayStreamFinished { application_name: String, stream_key: String, stream_id: u32 },
+    PlaybackSeekRequested { request_id: u32, application_name: String, stream_key: String, stream_id: u32, milliseconds: f64 },
+    PlaybackPauseChanged { application_name: String, stream_key: String, stream_id: u32, is_paused: bool, milliseconds: f64 },
     StreamMetaDataChanged { application_name: String, stream_key: String, meta_data: StreamMetadata },
-    AudioDataReceived { application_name: String, stream_key: String, data: Vec<u8> },
-    VideoDataReceived { application_name: String, stream_key: String, data: Vec<u8> },
+    AudioDataReceived { application_name: String, stream_key: String, timestamp: RtmpTimestamp, data: Vec<u8> },
+    VideoDataReceived { application_name: String, stream_key: String, timestamp: RtmpTimestamp, data: Vec<u8> },
     UnhandleableAmf0Command { command_name: String },
+    PeerUnresponsive { unanswered_pings: u32 },
 }
\ No newline at end of file
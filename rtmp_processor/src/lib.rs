@@ -1,5 +1,5 @@
 #[macro_use] extern crate quick_error;
-extern crate amf0;
+#[macro_use] extern crate amf0;
 extern crate rtmp_message;
 extern crate rtmp_time;
 
@@ -12,7 +12,8 @@ mod errors;
 
 pub use events::ProcessorEvent;
 pub use metadata::StreamMetadata;
-pub use processor::{RtmpProcessor, ProcessorResult};
+pub use stream::PublishMode;
+pub use processor::{RtmpProcessor, RtmpProcessorConfig, ProcessorResult};
 pub use errors::RtmpProcessorError;
 
 #[cfg(test)]
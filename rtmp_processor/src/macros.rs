@@ -56,7 +56,7 @@ macro_rules! assert_vec_match{
     };
     
     (@step $idx:expr, $vector:expr, $pattern:pat if $cond:expr) => {
-        assert_vec_match!(@match $idx, $vector, $pattern if $cond => $success);
+        assert_vec_match!(@match $idx, $vector, $pattern if $cond => ());
     };
 
     // pattern if condition => success
@@ -1,4 +1,6 @@
-#[derive(PartialEq, Debug)]
+use amf0::{Amf0Value, Amf0Properties};
+
+#[derive(PartialEq, Debug, Clone)]
 pub struct StreamMetadata {
     pub video_width: Option<u32>,
     pub video_height: Option<u32>,
@@ -11,4 +13,149 @@ pub struct StreamMetadata {
     pub audio_channels: Option<u32>,
     pub audo_is_stereo: Option<bool>,
     pub encoder: Option<String>
-}
\ No newline at end of file
+}
+
+impl StreamMetadata {
+    pub fn new() -> Self {
+        StreamMetadata {
+            video_width: None,
+            video_height: None,
+            video_codec: None,
+            video_frame_rate: None,
+            video_bitrate_kbps: None,
+            audio_codec: None,
+            audio_bitrate_kbps: None,
+            audio_sample_rate: None,
+            audio_channels: None,
+            audo_is_stereo: None,
+            encoder: None
+        }
+    }
+
+    /// Reads the properties encoders send in an `onMetaData` message.  Codec
+    /// ids may be sent as either numbers or strings (e.g. `7` or `"avc1"`).
+    pub fn from_properties(properties: &Amf0Properties) -> Self {
+        let number = |name: &str| properties.get(name).and_then(|x| x.as_f64());
+        let string = |name: &str| properties.get(name).and_then(|x| x.as_str()).map(|x| x.to_string());
+        let codec = |name: &str| match properties.get(name) {
+            Some(&Amf0Value::Number(id)) => Some(id.to_string()),
+            Some(&Amf0Value::Utf8String(ref id)) => Some(id.clone()),
+            _ => None
+        };
+
+        StreamMetadata {
+            video_width: number("width").map(|x| x as u32),
+            video_height: number("height").map(|x| x as u32),
+            video_codec: codec("videocodecid"),
+            video_frame_rate: number("framerate").or_else(|| number("fps")),
+            video_bitrate_kbps: number("videodatarate").map(|x| x as u32),
+            audio_codec: codec("audiocodecid"),
+            audio_bitrate_kbps: number("audiodatarate").map(|x| x as u32),
+            audio_sample_rate: number("audiosamplerate").map(|x| x as u32),
+            audio_channels: number("audiochannels").map(|x| x as u32),
+            audo_is_stereo: properties.get("stereo").and_then(|x| x.as_bool()),
+            encoder: string("encoder")
+        }
+    }
+
+    /// Creates the properties sent to viewers in an `onMetaData` message.
+    /// Codec ids that are numeric are sent as numbers.
+    pub fn to_properties(&self) -> Amf0Properties {
+        let mut properties = Amf0Properties::new();
+        {
+            let mut number = |name: &str, value: Option<f64>| if let Some(value) = value {
+                properties.insert(name.to_string(), Amf0Value::Number(value));
+            };
+
+            number("width", self.video_width.map(|x| x as f64));
+            number("height", self.video_height.map(|x| x as f64));
+            number("framerate", self.video_frame_rate);
+            number("videodatarate", self.video_bitrate_kbps.map(|x| x as f64));
+            number("audiodatarate", self.audio_bitrate_kbps.map(|x| x as f64));
+            number("audiosamplerate", self.audio_sample_rate.map(|x| x as f64));
+            number("audiochannels", self.audio_channels.map(|x| x as f64));
+        }
+
+        if let Some(ref codec) = self.video_codec {
+            properties.insert("videocodecid".to_string(), get_codec_value(codec));
+        }
+
+        if let Some(ref codec) = self.audio_codec {
+            properties.insert("audiocodecid".to_string(), get_codec_value(codec));
+        }
+
+        if let Some(is_stereo) = self.audo_is_stereo {
+            properties.insert("stereo".to_string(), Amf0Value::Boolean(is_stereo));
+        }
+
+        if let Some(ref encoder) = self.encoder {
+            properties.insert("encoder".to_string(), Amf0Value::Utf8String(encoder.clone()));
+        }
+
+        properties
+    }
+}
+
+fn get_codec_value(codec: &str) -> Amf0Value {
+    match codec.parse::<f64>() {
+        Ok(id) => Amf0Value::Number(id),
+        Err(_) => Amf0Value::Utf8String(codec.to_string())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use amf0::Amf0Value;
+    use super::*;
+
+    #[test]
+    fn metadata_read_from_obs_properties() {
+        let value = amf0_object!{
+            "width" => 1920.0,
+            "height" => 1080.0,
+            "videocodecid" => 7.0,
+            "videodatarate" => 2500.0,
+            "framerate" => 30.0,
+            "audiocodecid" => "mp4a",
+            "audiodatarate" => 160.0,
+            "audiosamplerate" => 48000.0,
+            "audiochannels" => 2.0,
+            "stereo" => true,
+            "encoder" => "obs-output module",
+        };
+
+        let properties = match value {
+            Amf0Value::Object(properties) => properties,
+            _ => panic!("Expected an object")
+        };
+
+        let metadata = StreamMetadata::from_properties(&properties);
+        assert_eq!(metadata, get_obs_metadata());
+    }
+
+    #[test]
+    fn metadata_converted_to_properties_and_back() {
+        let metadata = get_obs_metadata();
+        let properties = metadata.to_properties();
+
+        assert_eq!(properties.get("videocodecid"), Some(&Amf0Value::Number(7.0)));
+        assert_eq!(properties.get("audiocodecid"), Some(&Amf0Value::Utf8String("mp4a".to_string())));
+        assert_eq!(StreamMetadata::from_properties(&properties), metadata);
+    }
+
+    fn get_obs_metadata() -> StreamMetadata {
+        StreamMetadata {
+            video_width: Some(1920),
+            video_height: Some(1080),
+            video_codec: Some("7".to_string()),
+            video_frame_rate: Some(30.0),
+            video_bitrate_kbps: Some(2500),
+            audio_codec: Some("mp4a".to_string()),
+            audio_bitrate_kbps: Some(160),
+            audio_sample_rate: Some(48000),
+            audio_channels: Some(2),
+            audo_is_stereo: Some(true),
+            encoder: Some("obs-output module".to_string())
+        }
+    }
+}
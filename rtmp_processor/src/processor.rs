@@ -1,12 +1,17 @@
+use std::cmp::min;
 use std::collections::HashMap;
+use std::mem;
 use std::num::Wrapping;
+use std::time::{Duration, Instant};
 //use amf0; // So serialize and deserialize methods are not brought into immediate scope
 use amf0::Amf0Value;
-use rtmp_message::{RtmpMessage, RtmpMessageDetails, PeerBandwidthLimitType};
+use rtmp_message::{RtmpMessage, RtmpMessageDetails, PeerBandwidthLimitType, UserControlEventType};
 use rtmp_time::RtmpTimestamp;
 
 use events::ProcessorEvent;
 use errors::RtmpProcessorError;
+use metadata::StreamMetadata;
+use stream::{Stream, StreamState, PublishMode};
 
 #[derive(PartialEq, Debug)]
 pub enum ProcessorResult {
@@ -18,7 +23,16 @@ pub enum ProcessorResult {
 pub struct RtmpProcessorConfig {
     pub version: String,
     pub peer_bandwidth: u32,
-    pub window_ack_size: u32
+    pub window_ack_size: u32,
+
+    /// How many pings sent by `send_ping()` can go unanswered before a
+    /// `PeerUnresponsive` event is raised.  Zero disables the check.
+    pub max_unanswered_pings: u32,
+
+    /// Most message streams the peer can have open at once.  Further
+    /// `createStream` commands are answered with an error until a stream is
+    /// removed with `deleteStream`.
+    pub max_streams: u32
 }
 
 enum ProcessorState {
@@ -28,7 +42,11 @@ enum ProcessorState {
 }
 
 enum OutstandingRequest {
-    Connection { app: String }
+    Connection { app: String },
+    ReleaseStream { transaction_id: f64 },
+    Publish { stream_id: u32 },
+    Play { stream_id: u32, start: f64, reset: bool, previous_stream_key: Option<String> },
+    Seek { stream_id: u32, transaction_id: f64, milliseconds: f64 },
 }
 
 pub struct RtmpProcessor {
@@ -36,7 +54,36 @@ pub struct RtmpProcessor {
     current_state: ProcessorState,
     next_request_id: Wrapping<u32>,
     outstanding_requests: HashMap<u32, OutstandingRequest>,
-    application_name: Option<String>
+    application_name: Option<String>,
+    active_streams: HashMap<u32, Stream>,
+    next_stream_id: u32,
+    byte_counts: ByteCounts,
+    pings: PingState
+}
+
+struct PingState {
+    start_time: Instant,
+    unanswered_count: u32,
+    round_trip_time: Option<Duration>,
+}
+
+/// Bytes sent and received on the connection.  Counts are RTMP sequence numbers,
+/// so they wrap around at u32.
+struct ByteCounts {
+    received: Wrapping<u32>,
+    received_at_last_ack: Wrapping<u32>,
+    peer_window_ack_size: Option<u32>,
+    sent: Wrapping<u32>,
+    acknowledged_by_peer: Wrapping<u32>,
+    sent_window_ack_size: Option<u32>,
+    outgoing_window: Option<OutgoingWindow>,
+}
+
+/// Limit on unacknowledged bytes sent to the peer, set by the peer's
+/// SetPeerBandwidth messages
+struct OutgoingWindow {
+    size: u32,
+    limit_type: PeerBandwidthLimitType,
 }
 
 impl RtmpProcessor {
@@ -46,7 +93,23 @@ impl RtmpProcessor {
             current_state: ProcessorState::Started,
             next_request_id: Wrapping(0),
             outstanding_requests: HashMap::new(),
-            application_name: None
+            application_name: None,
+            active_streams: HashMap::new(),
+            next_stream_id: 1,
+            byte_counts: ByteCounts {
+                received: Wrapping(0),
+                received_at_last_ack: Wrapping(0),
+                peer_window_ack_size: None,
+                sent: Wrapping(0),
+                acknowledged_by_peer: Wrapping(0),
+                sent_window_ack_size: None,
+                outgoing_window: None,
+            },
+            pings: PingState {
+                start_time: Instant::now(),
+                unanswered_count: 0,
+                round_trip_time: None,
+            }
         }
     }
 
@@ -64,6 +127,24 @@ impl RtmpProcessor {
                 RtmpMessage::WindowAcknowledgement{size} 
                     => self.handle_peer_window_ack(size),
 
+                RtmpMessage::Acknowledgement{sequence_number}
+                    => self.handle_peer_acknowledgement(sequence_number),
+
+                RtmpMessage::SetPeerBandwidth{size, limit_type}
+                    => self.handle_peer_bandwidth(size, limit_type),
+
+                RtmpMessage::UserControl{event_type: UserControlEventType::PingRequest, timestamp, ..}
+                    => self.handle_ping_request(timestamp),
+
+                RtmpMessage::UserControl{event_type: UserControlEventType::PingResponse, timestamp, ..}
+                    => self.handle_ping_response(timestamp),
+
+                RtmpMessage::AudioData{..} | RtmpMessage::VideoData{..}
+                    => self.handle_media_data(details),
+
+                RtmpMessage::Amf0Data{..}
+                    => self.handle_amf0_data(details),
+
                 _ => vec![ProcessorResult::UnhandleableMessage(details)]
             };
 
@@ -85,6 +166,128 @@ impl RtmpProcessor {
         ]
     }
 
+    /// Records bytes received from the peer (including chunk headers), i.e. the
+    /// length of every buffer passed to the chunk deserializer's `process_bytes()`
+    /// (whose running total is `Deserializer::get_total_bytes_received()`).  Once a
+    /// full acknowledgement window has been received since the last
+    /// acknowledgement, an Acknowledgement message is returned to send to the peer.
+    /// The window is the size the peer asked for, or the configured
+    /// `window_ack_size` if the peer has not sent one.
+    pub fn handle_bytes_received(&mut self, byte_count: usize) -> Vec<ProcessorResult> {
+        let counts = &mut self.byte_counts;
+        counts.received = counts.received + Wrapping(byte_count as u32);
+
+        let window = counts.peer_window_ack_size.unwrap_or(self.config.window_ack_size);
+        let unacknowledged = counts.received - counts.received_at_last_ack;
+        if unacknowledged.0 < window {
+            return Vec::new();
+        }
+
+        counts.received_at_last_ack = counts.received;
+        vec![
+            ProcessorResult::ResponseMessage(RtmpMessageDetails {
+                rtmp_timestamp: RtmpTimestamp::new(0),
+                stream_id: 0,
+                message: RtmpMessage::Acknowledgement { sequence_number: counts.received.0 }
+            })
+        ]
+    }
+
+    /// Records bytes sent to the peer (including chunk headers), so they can be
+    /// compared against what the peer has acknowledged.  This is the number of
+    /// bytes produced by the peer's chunk serializer, which keeps a running total
+    /// in `Serializer::get_total_bytes_serialized()`.
+    pub fn handle_bytes_sent(&mut self, byte_count: usize) {
+        self.byte_counts.sent = self.byte_counts.sent + Wrapping(byte_count as u32);
+    }
+
+    /// Number of bytes sent to the peer that it has not acknowledged yet
+    pub fn get_unacknowledged_byte_count(&self) -> u32 {
+        (self.byte_counts.sent - self.byte_counts.acknowledged_by_peer).0
+    }
+
+    /// The most unacknowledged bytes the peer allows to be sent to it, or `None`
+    /// if the peer has not set a limit.  Senders should hold off sending while
+    /// `get_unacknowledged_byte_count()` is at or above this window.
+    pub fn get_outgoing_window_size(&self) -> Option<u32> {
+        self.byte_counts.outgoing_window.as_ref().map(|window| window.size)
+    }
+
+    /// Sends a ping to the peer, and is meant to be called periodically by the
+    /// caller.  If the peer has not answered the last `max_unanswered_pings` pings
+    /// a `PeerUnresponsive` event is raised instead, so the connection can be closed.
+    pub fn send_ping(&mut self) -> Vec<ProcessorResult> {
+        let max_unanswered_pings = self.config.max_unanswered_pings;
+        if max_unanswered_pings > 0 && self.pings.unanswered_count >= max_unanswered_pings {
+            return vec![
+                ProcessorResult::RaisedEvent(ProcessorEvent::PeerUnresponsive { unanswered_pings: self.pings.unanswered_count })
+            ];
+        }
+
+        self.pings.unanswered_count = self.pings.unanswered_count + 1;
+        let timestamp = RtmpTimestamp::new(to_milliseconds(self.pings.start_time.elapsed()));
+
+        vec![
+            ProcessorResult::ResponseMessage(RtmpMessageDetails {
+                rtmp_timestamp: RtmpTimestamp::new(0),
+                stream_id: 0,
+                message: RtmpMessage::UserControl {
+                    event_type: UserControlEventType::PingRequest,
+                    stream_id: None,
+                    buffer_length: None,
+                    timestamp: Some(timestamp)
+                }
+            })
+        ]
+    }
+
+    /// Round trip time measured from the most recently answered ping
+    pub fn get_round_trip_time(&self) -> Option<Duration> {
+        self.pings.round_trip_time
+    }
+
+    /// Creates the message to send audio data to a stream being played.  Nothing
+    /// is returned if playback is paused or the peer does not want audio.
+    pub fn send_audio_data(&mut self, stream_id: u32, timestamp: RtmpTimestamp, data: Vec<u8>)
+        -> Result<Vec<ProcessorResult>, RtmpProcessorError> {
+
+        let stream = try!(self.get_playing_stream(stream_id));
+        if stream.is_paused || !stream.receive_audio {
+            return Ok(vec![]);
+        }
+
+        Ok(vec![get_stream_response(stream_id, timestamp, RtmpMessage::AudioData { data: data })])
+    }
+
+    /// Creates the message to send video data to a stream being played.  Nothing
+    /// is returned if playback is paused or the peer does not want video.
+    pub fn send_video_data(&mut self, stream_id: u32, timestamp: RtmpTimestamp, data: Vec<u8>)
+        -> Result<Vec<ProcessorResult>, RtmpProcessorError> {
+
+        let stream = try!(self.get_playing_stream(stream_id));
+        if stream.is_paused || !stream.receive_video {
+            return Ok(vec![]);
+        }
+
+        Ok(vec![get_stream_response(stream_id, timestamp, RtmpMessage::VideoData { data: data })])
+    }
+
+    /// Creates the `onMetaData` message to send to a stream being played
+    pub fn send_metadata(&mut self, stream_id: u32, metadata: &StreamMetadata)
+        -> Result<Vec<ProcessorResult>, RtmpProcessorError> {
+
+        try!(self.get_playing_stream(stream_id));
+
+        let message = RtmpMessage::Amf0Data {
+            values: vec![
+                Amf0Value::Utf8String("onMetaData".to_string()),
+                Amf0Value::EcmaArray(metadata.to_properties()),
+            ]
+        };
+
+        Ok(vec![get_stream_response(stream_id, RtmpTimestamp::new(0), message)])
+    }
+
     /// Signals that a request event that the processor previously raised 
     /// has been accepted
     pub fn accept_request(&mut self, request_id: u32) -> Result<Vec<ProcessorResult>, RtmpProcessorError> {
@@ -94,14 +297,29 @@ impl RtmpProcessor {
         };
 
         match request {
-            OutstandingRequest::Connection{app} => Ok(accept_connection_request(self, app))
+            OutstandingRequest::Connection{app} => Ok(accept_connection_request(self, app)),
+            OutstandingRequest::ReleaseStream{transaction_id} => Ok(accept_release_stream_request(transaction_id)),
+            OutstandingRequest::Publish{stream_id} => Ok(accept_publish_request(self, stream_id)),
+            OutstandingRequest::Play{stream_id, start, reset, previous_stream_key} => Ok(accept_play_request(self, stream_id, start, reset, previous_stream_key)),
+            OutstandingRequest::Seek{stream_id, milliseconds, ..} => Ok(accept_seek_request(stream_id, milliseconds)),
         }
     }
 
     /// Signals that a request event that the processor previously raised
     /// has been rejected
-    pub fn reject_request(&mut self, _request_id: u32) -> Vec<ProcessorResult> {
-        unimplemented!()
+    pub fn reject_request(&mut self, request_id: u32) -> Result<Vec<ProcessorResult>, RtmpProcessorError> {
+        let request = match self.outstanding_requests.remove(&request_id) {
+            Some(request) => request,
+            None => return Err(RtmpProcessorError::UnknownRequestId)
+        };
+
+        match request {
+            OutstandingRequest::Connection{..} => Ok(reject_connection_request(self)),
+            OutstandingRequest::ReleaseStream{transaction_id} => Ok(reject_release_stream_request(transaction_id)),
+            OutstandingRequest::Publish{stream_id} => Ok(reject_publish_request(self, stream_id)),
+            OutstandingRequest::Play{stream_id, previous_stream_key, ..} => Ok(reject_play_request(self, stream_id, previous_stream_key)),
+            OutstandingRequest::Seek{stream_id, transaction_id, ..} => Ok(vec![get_amf0_error_response(stream_id, transaction_id, Amf0Value::Null)]),
+        }
     }
 
     fn handle_peer_chunk_size(&mut self, size: u32) -> Vec<ProcessorResult> {
@@ -110,8 +328,78 @@ impl RtmpProcessor {
         ]
     }
 
-    fn handle_peer_window_ack(&mut self, _size: u32) -> Vec<ProcessorResult> {
-        vec![] // TODO: add code to track bytes to act on acknowledgements
+    fn handle_peer_window_ack(&mut self, size: u32) -> Vec<ProcessorResult> {
+        // A zero window would have us acknowledge every read, so keep the
+        // previous window instead
+        if size == 0 {
+            return vec![];
+        }
+
+        self.byte_counts.peer_window_ack_size = Some(size);
+        vec![]
+    }
+
+    fn handle_peer_acknowledgement(&mut self, sequence_number: u32) -> Vec<ProcessorResult> {
+        self.byte_counts.acknowledged_by_peer = Wrapping(sequence_number);
+        vec![]
+    }
+
+    fn handle_ping_request(&mut self, timestamp: Option<RtmpTimestamp>) -> Vec<ProcessorResult> {
+        vec![
+            ProcessorResult::ResponseMessage(RtmpMessageDetails {
+                rtmp_timestamp: RtmpTimestamp::new(0),
+                stream_id: 0,
+                message: RtmpMessage::UserControl {
+                    event_type: UserControlEventType::PingResponse,
+                    stream_id: None,
+                    buffer_length: None,
+                    timestamp: Some(timestamp.unwrap_or(RtmpTimestamp::new(0)))
+                }
+            })
+        ]
+    }
+
+    fn handle_ping_response(&mut self, timestamp: Option<RtmpTimestamp>) -> Vec<ProcessorResult> {
+        self.pings.unanswered_count = 0;
+
+        // Pings carry the time they were sent, relative to when the processor started
+        if let Some(timestamp) = timestamp {
+            let now = to_milliseconds(self.pings.start_time.elapsed());
+            let round_trip_milliseconds = (Wrapping(now) - Wrapping(timestamp.value)).0;
+            self.pings.round_trip_time = Some(Duration::from_millis(round_trip_milliseconds as u64));
+        }
+
+        vec![]
+    }
+
+    fn handle_peer_bandwidth(&mut self, size: u32, limit_type: PeerBandwidthLimitType) -> Vec<ProcessorResult> {
+        // Dynamic limits are treated as hard limits, but only when the previous
+        // limit was hard
+        let (new_size, effective_limit_type) = match (limit_type, self.byte_counts.outgoing_window.as_ref()) {
+            (PeerBandwidthLimitType::Hard, _) => (size, PeerBandwidthLimitType::Hard),
+            (PeerBandwidthLimitType::Soft, None) => (size, PeerBandwidthLimitType::Soft),
+            (PeerBandwidthLimitType::Soft, Some(window)) => (min(size, window.size), PeerBandwidthLimitType::Soft),
+            (PeerBandwidthLimitType::Dynamic, Some(window)) if window.limit_type == PeerBandwidthLimitType::Hard
+                => (size, PeerBandwidthLimitType::Hard),
+            (PeerBandwidthLimitType::Dynamic, _) => return vec![],
+        };
+
+        self.byte_counts.outgoing_window = Some(OutgoingWindow { size: new_size, limit_type: effective_limit_type });
+
+        // The peer is told the window it should acknowledge with whenever it
+        // differs from the one it was last told
+        if self.byte_counts.sent_window_ack_size == Some(new_size) {
+            return vec![];
+        }
+
+        self.byte_counts.sent_window_ack_size = Some(new_size);
+        vec![
+            ProcessorResult::ResponseMessage(RtmpMessageDetails {
+                rtmp_timestamp: RtmpTimestamp::new(0),
+                stream_id: 0,
+                message: RtmpMessage::WindowAcknowledgement { size: new_size }
+            })
+        ]
     }
 
     fn handle_amf0_command(&mut self,
@@ -119,14 +407,130 @@ impl RtmpProcessor {
         command_name: String, 
         transaction_id: f64, 
         command_object: Amf0Value, 
-        _additional_arguments: Vec<Amf0Value>) -> Result<Vec<ProcessorResult>, RtmpProcessorError> {
+        additional_arguments: Vec<Amf0Value>) -> Result<Vec<ProcessorResult>, RtmpProcessorError> {
             
         match command_name.as_ref() {
             "connect" => handle_connect_amf0_command(self, stream_id, transaction_id, command_object),
+            "releaseStream" => handle_release_stream_amf0_command(self, stream_id, transaction_id, additional_arguments),
+            "FCPublish" => Ok(handle_fc_publish_amf0_command(self, stream_id, transaction_id, additional_arguments)),
+            "FCUnpublish" => Ok(handle_fc_unpublish_amf0_command(self, stream_id, transaction_id, additional_arguments)),
+            "createStream" => Ok(handle_create_stream_amf0_command(self, stream_id, transaction_id)),
+            "closeStream" => Ok(handle_close_stream_amf0_command(self, stream_id)),
+            "deleteStream" => Ok(handle_delete_stream_amf0_command(self, additional_arguments)),
+            "publish" => handle_publish_amf0_command(self, stream_id, transaction_id, additional_arguments),
+            "play" => handle_play_amf0_command(self, stream_id, transaction_id, additional_arguments),
+            "play2" => handle_play2_amf0_command(self, stream_id, transaction_id, additional_arguments),
+            "seek" => handle_seek_amf0_command(self, stream_id, transaction_id, additional_arguments),
+            "pause" => Ok(handle_pause_amf0_command(self, stream_id, transaction_id, additional_arguments)),
+            "receiveAudio" => Ok(handle_receive_media_amf0_command(self, stream_id, additional_arguments, true)),
+            "receiveVideo" => Ok(handle_receive_media_amf0_command(self, stream_id, additional_arguments, false)),
             _ => Ok(handle_unknown_amf0_command(stream_id, command_name, transaction_id)),
         }
     }
 
+    fn handle_media_data(&mut self, details: RtmpMessageDetails) -> Vec<ProcessorResult> {
+        let (application_name, stream_key) = match self.get_stream_names(details.stream_id, StreamState::PublishStarted) {
+            Some(names) => names,
+            None => return vec![ProcessorResult::UnhandleableMessage(details)]
+        };
+
+        let event = match details.message {
+            RtmpMessage::AudioData{data} => ProcessorEvent::AudioDataReceived {
+                application_name: application_name,
+                stream_key: stream_key,
+                timestamp: details.rtmp_timestamp,
+                data: data
+            },
+
+            RtmpMessage::VideoData{data} => ProcessorEvent::VideoDataReceived {
+                application_name: application_name,
+                stream_key: stream_key,
+                timestamp: details.rtmp_timestamp,
+                data: data
+            },
+
+            message => return vec![ProcessorResult::UnhandleableMessage(RtmpMessageDetails {
+                rtmp_timestamp: details.rtmp_timestamp,
+                stream_id: details.stream_id,
+                message: message
+            })]
+        };
+
+        vec![ProcessorResult::RaisedEvent(event)]
+    }
+
+    fn handle_amf0_data(&mut self, details: RtmpMessageDetails) -> Vec<ProcessorResult> {
+        let metadata = match details.message {
+            RtmpMessage::Amf0Data{ref values} => read_metadata(values),
+            _ => None
+        };
+
+        let metadata = match metadata {
+            Some(metadata) => metadata,
+            None => return vec![ProcessorResult::UnhandleableMessage(details)]
+        };
+
+        let (application_name, stream_key) = match self.get_stream_names(details.stream_id, StreamState::PublishStarted) {
+            Some(names) => names,
+            None => return vec![ProcessorResult::UnhandleableMessage(details)]
+        };
+
+        if let Some(stream) = self.active_streams.get_mut(&details.stream_id) {
+            stream.metadata = Some(metadata.clone());
+        }
+
+        vec![
+            ProcessorResult::RaisedEvent(ProcessorEvent::StreamMetaDataChanged {
+                application_name: application_name,
+                stream_key: stream_key,
+                meta_data: metadata
+            })
+        ]
+    }
+
+    /// Returns the application name and stream key for a stream that is
+    /// in the required state
+    fn get_stream_names(&self, stream_id: u32, required_state: StreamState) -> Option<(String, String)> {
+        let stream = match self.active_streams.get(&stream_id) {
+            Some(stream) if stream.current_state == required_state => stream,
+            _ => return None
+        };
+
+        match (self.application_name.as_ref(), stream.stream_key.as_ref()) {
+            (Some(app), Some(key)) => Some((app.clone(), key.clone())),
+            _ => None
+        }
+    }
+
+    fn get_playing_stream(&self, stream_id: u32) -> Result<&Stream, RtmpProcessorError> {
+        match self.active_streams.get(&stream_id) {
+            Some(stream) if stream.current_state == StreamState::PlayStarted => Ok(stream),
+            _ => Err(RtmpProcessorError::StreamNotPlaying(stream_id))
+        }
+    }
+
+    fn is_connected(&self) -> bool {
+        match self.current_state {
+            ProcessorState::ConnectionAccepted => true,
+            _ => false
+        }
+    }
+
+    fn get_next_stream_id(&mut self) -> u32 {
+        // Stream id 0 is reserved for the NetConnection
+        loop {
+            let id = self.next_stream_id;
+            self.next_stream_id = match self.next_stream_id.wrapping_add(1) {
+                0 => 1,
+                x => x
+            };
+
+            if !self.active_streams.contains_key(&id) {
+                return id;
+            }
+        }
+    }
+
     fn get_next_request_id(&mut self) -> Result<u32, RtmpProcessorError> {
         let last_id = self.next_request_id - Wrapping(1);
         let mut id = self.next_request_id;
@@ -150,6 +554,12 @@ impl RtmpProcessor {
     }
 }
 
+/// Converts to RTMP timestamp milliseconds, which wrap around at u32
+fn to_milliseconds(duration: Duration) -> u32 {
+    let milliseconds = duration.as_secs().wrapping_mul(1000) + (duration.subsec_nanos() / 1_000_000) as u64;
+    milliseconds as u32
+}
+
 fn handle_unknown_amf0_command(stream_id: u32, command_name: String, transaction_id: f64) -> Vec<ProcessorResult> {
     vec![
         get_amf0_error_response(stream_id, transaction_id, Amf0Value::Null),
@@ -162,15 +572,8 @@ fn handle_connect_amf0_command(processor: &mut RtmpProcessor,
     transaction_id: f64, 
     command_object: Amf0Value) -> Result<Vec<ProcessorResult>, RtmpProcessorError> {
 
-    let mut properties;
-    match command_object {
-        Amf0Value::Object(props) => properties = props,
-        _ => return Ok(vec![get_amf0_error_response(stream_id, transaction_id, Amf0Value::Null)])
-    };
-
-    let app_name = match properties.remove("app") {
-        Some(Amf0Value::Utf8String(name)) => name,
-        Some(_) => return Ok(vec![get_amf0_error_response(stream_id, transaction_id, Amf0Value::Null)]),
+    let app_name = match command_object.get("app").and_then(|x| x.as_str()) {
+        Some(name) => name.to_string(),
         None => return Ok(vec![get_amf0_error_response(stream_id, transaction_id, Amf0Value::Null)]),
     };
 
@@ -179,6 +582,7 @@ fn handle_connect_amf0_command(processor: &mut RtmpProcessor,
 
     processor.outstanding_requests.insert(request_id, request);
     processor.current_state = ProcessorState::ConnectionRequested;
+    processor.byte_counts.sent_window_ack_size = Some(processor.config.window_ack_size);
 
     Ok(vec![
         ProcessorResult::ResponseMessage(RtmpMessageDetails {
@@ -204,15 +608,17 @@ fn accept_connection_request(processor: &mut RtmpProcessor, app_name: String) ->
     processor.current_state = ProcessorState::ConnectionAccepted;
     processor.application_name = Some(app_name);
 
-    let mut command_properties = HashMap::new();
-    command_properties.insert("fmsVer".to_string(), Amf0Value::Utf8String(processor.config.version.clone()));
-    command_properties.insert("capabilities".to_string(), Amf0Value::Number(31.0));
+    let command_object = amf0_object!{
+        "fmsVer" => processor.config.version.clone(),
+        "capabilities" => 31.0,
+    };
 
-    let mut information_properties = HashMap::new();
-    information_properties.insert("level".to_string(), Amf0Value::Utf8String("status".to_string()));
-    information_properties.insert("code".to_string(), Amf0Value::Utf8String("NetConnection.Connect.Success".to_string()));
-    information_properties.insert("description".to_string(), Amf0Value::Utf8String("Connection succeeded".to_string()));
-    information_properties.insert("objectEncoding".to_string(), Amf0Value::Number(0.0));
+    let information = amf0_object!{
+        "level" => "status",
+        "code" => "NetConnection.Connect.Success",
+        "description" => "Connection succeeded",
+        "objectEncoding" => 0.0,
+    };
 
     vec![
         ProcessorResult::ResponseMessage(RtmpMessageDetails {
@@ -221,128 +627,1808 @@ fn accept_connection_request(processor: &mut RtmpProcessor, app_name: String) ->
             message: RtmpMessage::Amf0Command {
                 command_name: "_result".to_string(),
                 transaction_id: 1.0,
-                command_object: Amf0Value::Object(command_properties),
-                additional_arguments: vec![Amf0Value::Object(information_properties)]
+                command_object: command_object,
+                additional_arguments: vec![information]
             }
         })
     ]
 }
 
-fn get_amf0_error_response(stream_id: u32, transaction_id: f64, response_data: Amf0Value) -> ProcessorResult {
-    ProcessorResult::ResponseMessage(RtmpMessageDetails {
-        rtmp_timestamp: RtmpTimestamp::new(0),
-        stream_id: stream_id,
-        message: RtmpMessage::Amf0Command{
-            command_name: "_error".to_string(),
-            transaction_id: transaction_id,
-            command_object: response_data,
-            additional_arguments: vec![]
-        }
-    })
+fn reject_connection_request(processor: &mut RtmpProcessor) -> Vec<ProcessorResult> {
+    processor.current_state = ProcessorState::Started;
+
+    let information = amf0_object!{
+        "level" => "error",
+        "code" => "NetConnection.Connect.Rejected",
+        "description" => "Connection rejected",
+    };
+
+    vec![
+        ProcessorResult::ResponseMessage(RtmpMessageDetails {
+            rtmp_timestamp: RtmpTimestamp::new(0),
+            stream_id: 0,
+            message: RtmpMessage::Amf0Command {
+                command_name: "_error".to_string(),
+                transaction_id: 1.0,
+                command_object: Amf0Value::Null,
+                additional_arguments: vec![information]
+            }
+        })
+    ]
 }
 
-#[cfg(test)]
-mod tests {
-    use std::collections::HashMap;
-    use amf0::Amf0Value;
-    use rtmp_message::{RtmpMessage, RtmpMessageDetails, PeerBandwidthLimitType};
+fn handle_release_stream_amf0_command(processor: &mut RtmpProcessor,
+    stream_id: u32,
+    transaction_id: f64,
+    additional_arguments: Vec<Amf0Value>) -> Result<Vec<ProcessorResult>, RtmpProcessorError> {
 
-    use events::ProcessorEvent;
-    use super::*;
-    use tests::utils;
+    let stream_key = match get_stream_key_argument(&additional_arguments) {
+        Some(key) => key,
+        None => return Ok(vec![get_amf0_error_response(stream_id, transaction_id, Amf0Value::Null)])
+    };
 
-    #[test]
-    fn self_chunk_size() {
-        let mut processor = RtmpProcessor::new(get_default_config());
-        let result = processor.set_chunk_size(4096);
+    let application_name = match processor.application_name {
+        Some(ref name) if processor.is_connected() => name.clone(),
+        _ => return Ok(vec![get_amf0_error_response(stream_id, transaction_id, Amf0Value::Null)])
+    };
 
-        assert_vec_match!(result,
-            ProcessorResult::RaisedEvent(ProcessorEvent::SelfChunkSizeChanged { new_chunk_size: 4096 }),
-            ProcessorResult::ResponseMessage(RtmpMessageDetails {
-                stream_id: 0,
-                rtmp_timestamp: _,
-                message: RtmpMessage::SetChunkSize{size: 4096}
-            })
-        );
-    }
+    let request_id = try!(processor.get_next_request_id());
+    processor.outstanding_requests.insert(request_id, OutstandingRequest::ReleaseStream{transaction_id: transaction_id});
 
-    #[test]
-    fn peer_chunk_size() {
-        let mut processor = RtmpProcessor::new(get_default_config());
-        let result = processor.handle(vec![utils::create_set_chunk_size_message(4000)]).unwrap();
+    Ok(vec![
+        ProcessorResult::RaisedEvent(ProcessorEvent::ReleaseStreamRequested {
+            request_id: request_id,
+            application_name: application_name,
+            stream_key: stream_key
+        })
+    ])
+}
 
-        assert_vec_match!(result,
-            ProcessorResult::RaisedEvent(ProcessorEvent::PeerChunkSizeChanged{new_chunk_size: 4000})
-        );
-    }
+fn accept_release_stream_request(transaction_id: f64) -> Vec<ProcessorResult> {
+    vec![
+        ProcessorResult::ResponseMessage(RtmpMessageDetails {
+            rtmp_timestamp: RtmpTimestamp::new(0),
+            stream_id: 0,
+            message: RtmpMessage::Amf0Command {
+                command_name: "_result".to_string(),
+                transaction_id: transaction_id,
+                command_object: Amf0Value::Null,
+                additional_arguments: vec![Amf0Value::Undefined]
+            }
+        })
+    ]
+}
 
-    #[test]
-    fn peer_window_ack_size() {
-        let mut processor = RtmpProcessor::new(get_default_config());
-        let result = processor.handle(vec![utils::create_window_ack_message(5000000)]).unwrap();
+fn reject_release_stream_request(transaction_id: f64) -> Vec<ProcessorResult> {
+    vec![get_amf0_error_response(0, transaction_id, Amf0Value::Null)]
+}
 
-        assert_vec_match!(result); // should be empty
-    }
+fn handle_fc_publish_amf0_command(processor: &mut RtmpProcessor,
+    stream_id: u32,
+    transaction_id: f64,
+    additional_arguments: Vec<Amf0Value>) -> Vec<ProcessorResult> {
 
-    #[test]
-    fn connection_command_received_and_accepted() {
-        let bandwidth_size = 60000;
-        let window_ack_size = 70000;
-        let mut config = get_default_config();
-        config.peer_bandwidth = bandwidth_size;
-        config.window_ack_size = window_ack_size;
+    // FCPublish only announces the stream key that is about to be published,
+    // the publish command itself is what gets accepted or rejected
+    let stream_key = match get_stream_key_argument(&additional_arguments) {
+        Some(ref key) if processor.is_connected() => key.clone(),
+        _ => return vec![get_amf0_error_response(stream_id, transaction_id, Amf0Value::Null)]
+    };
 
-        let mut processor = RtmpProcessor::new(config);
-        let app_name = "myapp".to_string();
-        let command = utils::create_connect_command(app_name.clone());
-        let request_id;
+    let information = amf0_object!{
+        "code" => "NetStream.Publish.Start",
+        "description" => stream_key,
+    };
 
-        let initial_result = processor.handle(vec![command]).unwrap();
-        assert_vec_match!(initial_result,
-            ProcessorResult::ResponseMessage(RtmpMessageDetails {
-                rtmp_timestamp: _,
-                stream_id: 0,
-                message: RtmpMessage::SetPeerBandwidth{size, limit_type: PeerBandwidthLimitType::Hard}
-            }) if size == bandwidth_size,
+    vec![
+        ProcessorResult::ResponseMessage(RtmpMessageDetails {
+            rtmp_timestamp: RtmpTimestamp::new(0),
+            stream_id: 0,
+            message: RtmpMessage::Amf0Command {
+                command_name: "onFCPublish".to_string(),
+                transaction_id: 0.0,
+                command_object: Amf0Value::Null,
+                additional_arguments: vec![information]
+            }
+        })
+    ]
+}
 
-            ProcessorResult::ResponseMessage(RtmpMessageDetails {
-                rtmp_timestamp: _,
-                stream_id: 0,
-                message: RtmpMessage::WindowAcknowledgement { size }
-            }) if size == window_ack_size,
+fn handle_fc_unpublish_amf0_command(processor: &mut RtmpProcessor,
+    stream_id: u32,
+    transaction_id: f64,
+    additional_arguments: Vec<Amf0Value>) -> Vec<ProcessorResult> {
 
-            ProcessorResult::RaisedEvent(ProcessorEvent::ConnectionRequested { request_id: rid, application_name: ref name }) if name == &app_name
+    let stream_key = match get_stream_key_argument(&additional_arguments) {
+        Some(ref key) if processor.is_connected() => key.clone(),
+        _ => return vec![get_amf0_error_response(stream_id, transaction_id, Amf0Value::Null)]
+    };
+
+    // FCUnpublish is sent on the NetConnection, so the stream being unpublished
+    // is found by its stream key
+    let publishing_stream_id = processor.active_streams.iter()
+        .find(|&(_, stream)| stream.current_state == StreamState::PublishStarted && stream.stream_key.as_ref() == Some(&stream_key))
+        .map(|(id, _)| *id);
+
+    let mut results = match publishing_stream_id {
+        Some(id) => stop_stream(processor, id),
+        None => vec![]
+    };
+
+    let information = amf0_object!{
+        "code" => "NetStream.Unpublish.Success",
+        "description" => stream_key,
+    };
+
+    results.push(ProcessorResult::ResponseMessage(RtmpMessageDetails {
+        rtmp_timestamp: RtmpTimestamp::new(0),
+        stream_id: 0,
+        message: RtmpMessage::Amf0Command {
+            command_name: "onFCUnpublish".to_string(),
+            transaction_id: 0.0,
+            command_object: Amf0Value::Null,
+            additional_arguments: vec![information]
+        }
+    }));
+
+    results
+}
+
+fn handle_close_stream_amf0_command(processor: &mut RtmpProcessor, stream_id: u32) -> Vec<ProcessorResult> {
+    // The stream stays allocated so it can be used to publish or play again
+    stop_stream(processor, stream_id)
+}
+
+fn handle_delete_stream_amf0_command(processor: &mut RtmpProcessor, additional_arguments: Vec<Amf0Value>) -> Vec<ProcessorResult> {
+    // deleteStream is sent on the NetConnection with the stream id as an argument
+    let stream_id = match additional_arguments.get(0).and_then(|x| x.as_f64()) {
+        Some(id) if id >= 1.0 && id <= u32::max_value() as f64 => id as u32,
+        _ => return vec![]
+    };
+
+    let results = stop_stream(processor, stream_id);
+    processor.active_streams.remove(&stream_id);
+    results
+}
+
+/// Ends whatever the stream is publishing or playing, putting it back into the
+/// state it was in after `createStream`.  Requests raised for the stream that
+/// have not been answered yet are dropped, since they no longer apply.
+fn stop_stream(processor: &mut RtmpProcessor, stream_id: u32) -> Vec<ProcessorResult> {
+    let stream = match processor.active_streams.get_mut(&stream_id) {
+        Some(stream) => mem::replace(stream, Stream::new()),
+        None => return vec![]
+    };
+
+    processor.outstanding_requests.retain(|_, request| match *request {
+        OutstandingRequest::Publish{stream_id: id} |
+        OutstandingRequest::Play{stream_id: id, ..} |
+        OutstandingRequest::Seek{stream_id: id, ..} => id != stream_id,
+        _ => true
+    });
+
+    let application_name = processor.application_name.clone().unwrap_or_default();
+    let event = match (stream.current_state, stream.stream_key) {
+        (StreamState::PublishStarted, Some(stream_key)) => ProcessorEvent::PublishStreamFinished {
+            application_name: application_name,
+            stream_key: stream_key
+        },
+
+        (StreamState::PlayStarted, Some(stream_key)) => ProcessorEvent::PlayStreamFinished {
+            application_name: application_name,
+            stream_key: stream_key,
+            stream_id: stream_id
+        },
+
+        _ => return vec![]
+    };
+
+    vec![ProcessorResult::RaisedEvent(event)]
+}
+
+fn handle_create_stream_amf0_command(processor: &mut RtmpProcessor, stream_id: u32, transaction_id: f64) -> Vec<ProcessorResult> {
+    if !processor.is_connected() {
+        return vec![get_amf0_error_response(stream_id, transaction_id, Amf0Value::Null)];
+    }
+
+    if processor.active_streams.len() >= processor.config.max_streams as usize {
+        return vec![get_amf0_error_response(stream_id, transaction_id, Amf0Value::Null)];
+    }
+
+    let new_stream_id = processor.get_next_stream_id();
+    processor.active_streams.insert(new_stream_id, Stream::new());
+
+    vec![
+        ProcessorResult::ResponseMessage(RtmpMessageDetails {
+            rtmp_timestamp: RtmpTimestamp::new(0),
+            stream_id: 0,
+            message: RtmpMessage::Amf0Command {
+                command_name: "_result".to_string(),
+                transaction_id: transaction_id,
+                command_object: Amf0Value::Null,
+                additional_arguments: vec![Amf0Value::Number(new_stream_id as f64)]
+            }
+        })
+    ]
+}
+
+fn handle_publish_amf0_command(processor: &mut RtmpProcessor,
+    stream_id: u32,
+    transaction_id: f64,
+    additional_arguments: Vec<Amf0Value>) -> Result<Vec<ProcessorResult>, RtmpProcessorError> {
+
+    let stream_key = match get_stream_key_argument(&additional_arguments) {
+        Some(key) => key,
+        None => return Ok(vec![get_amf0_error_response(stream_id, transaction_id, Amf0Value::Null)])
+    };
+
+    // The publishing type is optional and defaults to live
+    let mode = match additional_arguments.get(1).and_then(|x| x.as_str()) {
+        None => PublishMode::Live,
+        Some(name) => match PublishMode::from_name(name) {
+            Some(mode) => mode,
+            None => return Ok(vec![get_amf0_error_response(stream_id, transaction_id, Amf0Value::Null)])
+        }
+    };
+
+    let application_name = match processor.application_name {
+        Some(ref name) if processor.is_connected() => name.clone(),
+        _ => return Ok(vec![get_amf0_error_response(stream_id, transaction_id, Amf0Value::Null)])
+    };
+
+    // Publishing is only allowed on streams created with createStream that are
+    // not already publishing
+    match processor.active_streams.get(&stream_id) {
+        Some(stream) if stream.current_state == StreamState::Created => (),
+        _ => return Ok(vec![get_amf0_error_response(stream_id, transaction_id, Amf0Value::Null)])
+    }
+
+    let request_id = try!(processor.get_next_request_id());
+    processor.outstanding_requests.insert(request_id, OutstandingRequest::Publish{stream_id: stream_id});
+
+    if let Some(stream) = processor.active_streams.get_mut(&stream_id) {
+        stream.current_state = StreamState::PublishRequested;
+        stream.stream_key = Some(stream_key.clone());
+        stream.publish_mode = Some(mode);
+    }
+
+    Ok(vec![
+        ProcessorResult::RaisedEvent(ProcessorEvent::PublishStreamRequested {
+            request_id: request_id,
+            application_name: application_name,
+            stream_key: stream_key,
+            mode: mode
+        })
+    ])
+}
+
+fn accept_publish_request(processor: &mut RtmpProcessor, stream_id: u32) -> Vec<ProcessorResult> {
+    let stream_key = match processor.active_streams.get_mut(&stream_id) {
+        Some(stream) => {
+            stream.current_state = StreamState::PublishStarted;
+            stream.stream_key.clone().unwrap_or_default()
+        },
+
+        None => return vec![]
+    };
+
+    let information = amf0_object!{
+        "level" => "status",
+        "code" => "NetStream.Publish.Start",
+        "description" => format!("Publishing {}", stream_key),
+        "details" => stream_key,
+    };
+
+    vec![
+        get_user_control_response(UserControlEventType::StreamBegin, stream_id),
+        get_on_status_response(stream_id, information)
+    ]
+}
+
+fn reject_publish_request(processor: &mut RtmpProcessor, stream_id: u32) -> Vec<ProcessorResult> {
+    let stream_key = match processor.active_streams.get_mut(&stream_id) {
+        Some(stream) => {
+            stream.current_state = StreamState::Created;
+            stream.publish_mode = None;
+            stream.stream_key.take().unwrap_or_default()
+        },
+
+        None => return vec![]
+    };
+
+    let information = amf0_object!{
+        "level" => "error",
+        "code" => "NetStream.Publish.Denied",
+        "description" => format!("Publishing {} was denied", stream_key),
+        "details" => stream_key,
+    };
+
+    vec![get_on_status_response(stream_id, information)]
+}
+
+fn handle_play_amf0_command(processor: &mut RtmpProcessor,
+    stream_id: u32,
+    transaction_id: f64,
+    additional_arguments: Vec<Amf0Value>) -> Result<Vec<ProcessorResult>, RtmpProcessorError> {
+
+    let stream_key = match get_stream_key_argument(&additional_arguments) {
+        Some(key) => key,
+        None => return Ok(vec![get_amf0_error_response(stream_id, transaction_id, Amf0Value::Null)])
+    };
+
+    // Start defaults to -2 (live, falling back to recorded), duration defaults
+    // to -1 (until the stream ends) and reset defaults to true
+    let start = additional_arguments.get(1).and_then(|x| x.as_f64()).unwrap_or(-2.0);
+    let duration = additional_arguments.get(2).and_then(|x| x.as_f64()).unwrap_or(-1.0);
+    let reset = additional_arguments.get(3).and_then(|x| x.as_bool()).unwrap_or(true);
+
+    let is_created = match processor.active_streams.get(&stream_id) {
+        Some(stream) => stream.current_state == StreamState::Created,
+        None => false
+    };
+
+    if !is_created {
+        return Ok(vec![get_amf0_error_response(stream_id, transaction_id, Amf0Value::Null)]);
+    }
+
+    request_play(processor, stream_id, transaction_id, stream_key, start, duration, reset)
+}
+
+fn handle_play2_amf0_command(processor: &mut RtmpProcessor,
+    stream_id: u32,
+    transaction_id: f64,
+    additional_arguments: Vec<Amf0Value>) -> Result<Vec<ProcessorResult>, RtmpProcessorError> {
+
+    let parameters = match additional_arguments.get(0) {
+        Some(parameters) => parameters,
+        None => return Ok(vec![get_amf0_error_response(stream_id, transaction_id, Amf0Value::Null)])
+    };
+
+    let stream_key = match parameters.get("streamName").and_then(|x| x.as_str()) {
+        Some(key) => key.to_string(),
+        None => return Ok(vec![get_amf0_error_response(stream_id, transaction_id, Amf0Value::Null)])
+    };
+
+    let start = parameters.get("start").and_then(|x| x.as_f64()).unwrap_or(-2.0);
+    let duration = parameters.get("len").and_then(|x| x.as_f64()).unwrap_or(-1.0);
+    let reset = match parameters.get("transition").and_then(|x| x.as_str()) {
+        None | Some("reset") => true,
+        Some(_) => false
+    };
+
+    // Unlike play, play2 can switch a stream that is already playing
+    let can_play = match processor.active_streams.get(&stream_id) {
+        Some(stream) => stream.current_state == StreamState::Created || stream.current_state == StreamState::PlayStarted,
+        None => false
+    };
+
+    if !can_play {
+        return Ok(vec![get_amf0_error_response(stream_id, transaction_id, Amf0Value::Null)]);
+    }
+
+    request_play(processor, stream_id, transaction_id, stream_key, start, duration, reset)
+}
+
+fn request_play(processor: &mut RtmpProcessor,
+    stream_id: u32,
+    transaction_id: f64,
+    stream_key: String,
+    start: f64,
+    duration: f64,
+    reset: bool) -> Result<Vec<ProcessorResult>, RtmpProcessorError> {
+
+    let application_name = match processor.application_name {
+        Some(ref name) if processor.is_connected() => name.clone(),
+        _ => return Ok(vec![get_amf0_error_response(stream_id, transaction_id, Amf0Value::Null)])
+    };
+
+    let request_id = try!(processor.get_next_request_id());
+    let previous_stream_key = match processor.active_streams.get_mut(&stream_id) {
+        Some(stream) => {
+            let previous_stream_key = match stream.current_state {
+                StreamState::PlayStarted => stream.stream_key.take(),
+                _ => None
+            };
+
+            stream.current_state = StreamState::PlayRequested;
+            stream.stream_key = Some(stream_key.clone());
+            previous_stream_key
+        },
+
+        None => None
+    };
+
+    processor.outstanding_requests.insert(request_id, OutstandingRequest::Play {
+        stream_id: stream_id,
+        start: start,
+        reset: reset,
+        previous_stream_key: previous_stream_key
+    });
+
+    Ok(vec![
+        ProcessorResult::RaisedEvent(ProcessorEvent::PlayStreamRequested {
+            request_id: request_id,
+            application_name: application_name,
+            stream_key: stream_key,
+            stream_id: stream_id,
+            start: start,
+            duration: duration,
+            reset: reset
+        })
+    ])
+}
+
+fn accept_play_request(processor: &mut RtmpProcessor,
+    stream_id: u32,
+    start: f64,
+    reset: bool,
+    previous_stream_key: Option<String>) -> Vec<ProcessorResult> {
+
+    let stream_key = match processor.active_streams.get_mut(&stream_id) {
+        Some(stream) => {
+            stream.current_state = StreamState::PlayStarted;
+            stream.is_paused = false;
+            stream.stream_key.clone().unwrap_or_default()
+        },
+
+        None => return vec![]
+    };
+
+    let mut results = Vec::new();
+
+    // An accepted play2 switch stops the stream that was playing before
+    if let Some(previous_stream_key) = previous_stream_key {
+        results.push(ProcessorResult::RaisedEvent(ProcessorEvent::PlayStreamFinished {
+            application_name: processor.application_name.clone().unwrap_or_default(),
+            stream_key: previous_stream_key,
+            stream_id: stream_id
+        }));
+    }
+
+    // A start of zero or more requests playback of a recording
+    if start >= 0.0 {
+        results.push(get_user_control_response(UserControlEventType::StreamIsRecorded, stream_id));
+    }
+
+    results.push(get_user_control_response(UserControlEventType::StreamBegin, stream_id));
+
+    if reset {
+        results.push(get_on_status_response(stream_id, amf0_object!{
+            "level" => "status",
+            "code" => "NetStream.Play.Reset",
+            "description" => format!("Playing and resetting {}", stream_key),
+            "details" => stream_key.clone(),
+        }));
+    }
+
+    results.push(get_on_status_response(stream_id, amf0_object!{
+        "level" => "status",
+        "code" => "NetStream.Play.Start",
+        "description" => format!("Started playing {}", stream_key),
+        "details" => stream_key,
+    }));
+
+    results.push(get_stream_response(stream_id, RtmpTimestamp::new(0), RtmpMessage::Amf0Data {
+        values: vec![
+            Amf0Value::Utf8String("|RtmpSampleAccess".to_string()),
+            Amf0Value::Boolean(true),
+            Amf0Value::Boolean(true),
+        ]
+    }));
+
+    results
+}
+
+fn reject_play_request(processor: &mut RtmpProcessor, stream_id: u32, previous_stream_key: Option<String>) -> Vec<ProcessorResult> {
+    let stream_key = match processor.active_streams.get_mut(&stream_id) {
+        Some(stream) => {
+            let stream_key = stream.stream_key.take().unwrap_or_default();
+
+            // A rejected play2 switch leaves the previous stream playing
+            stream.current_state = match previous_stream_key {
+                Some(_) => StreamState::PlayStarted,
+                None => StreamState::Created
+            };
+
+            stream.stream_key = previous_stream_key;
+            stream_key
+        },
+
+        None => return vec![]
+    };
+
+    let information = amf0_object!{
+        "level" => "error",
+        "code" => "NetStream.Play.Failed",
+        "description" => format!("Playing {} was denied", stream_key),
+        "details" => stream_key,
+    };
+
+    vec![get_on_status_response(stream_id, information)]
+}
+
+fn handle_seek_amf0_command(processor: &mut RtmpProcessor,
+    stream_id: u32,
+    transaction_id: f64,
+    additional_arguments: Vec<Amf0Value>) -> Result<Vec<ProcessorResult>, RtmpProcessorError> {
+
+    let milliseconds = match additional_arguments.get(0).and_then(|x| x.as_f64()) {
+        Some(milliseconds) => milliseconds,
+        None => return Ok(vec![get_amf0_error_response(stream_id, transaction_id, Amf0Value::Null)])
+    };
+
+    let (application_name, stream_key) = match processor.get_stream_names(stream_id, StreamState::PlayStarted) {
+        Some(names) => names,
+        None => return Ok(vec![get_amf0_error_response(stream_id, transaction_id, Amf0Value::Null)])
+    };
+
+    let request_id = try!(processor.get_next_request_id());
+    processor.outstanding_requests.insert(request_id, OutstandingRequest::Seek {
+        stream_id: stream_id,
+        transaction_id: transaction_id,
+        milliseconds: milliseconds
+    });
+
+    Ok(vec![
+        ProcessorResult::RaisedEvent(ProcessorEvent::PlaybackSeekRequested {
+            request_id: request_id,
+            application_name: application_name,
+            stream_key: stream_key,
+            stream_id: stream_id,
+            milliseconds: milliseconds
+        })
+    ])
+}
+
+fn accept_seek_request(stream_id: u32, milliseconds: f64) -> Vec<ProcessorResult> {
+    let information = amf0_object!{
+        "level" => "status",
+        "code" => "NetStream.Seek.Notify",
+        "description" => format!("Seeking to {}", milliseconds),
+    };
+
+    vec![get_on_status_response(stream_id, information)]
+}
+
+fn handle_pause_amf0_command(processor: &mut RtmpProcessor,
+    stream_id: u32,
+    transaction_id: f64,
+    additional_arguments: Vec<Amf0Value>) -> Vec<ProcessorResult> {
+
+    let is_paused = match additional_arguments.get(0).and_then(|x| x.as_bool()) {
+        Some(is_paused) => is_paused,
+        None => return vec![get_amf0_error_response(stream_id, transaction_id, Amf0Value::Null)]
+    };
+
+    let milliseconds = additional_arguments.get(1).and_then(|x| x.as_f64()).unwrap_or(0.0);
+    let (application_name, stream_key) = match processor.get_stream_names(stream_id, StreamState::PlayStarted) {
+        Some(names) => names,
+        None => return vec![get_amf0_error_response(stream_id, transaction_id, Amf0Value::Null)]
+    };
+
+    if let Some(stream) = processor.active_streams.get_mut(&stream_id) {
+        stream.is_paused = is_paused;
+    }
+
+    let (event_type, code) = if is_paused {
+        (UserControlEventType::StreamEof, "NetStream.Pause.Notify")
+    } else {
+        (UserControlEventType::StreamBegin, "NetStream.Unpause.Notify")
+    };
+
+    let information = amf0_object!{
+        "level" => "status",
+        "code" => code,
+        "description" => stream_key.clone(),
+    };
+
+    vec![
+        get_user_control_response(event_type, stream_id),
+        get_on_status_response(stream_id, information),
+        ProcessorResult::RaisedEvent(ProcessorEvent::PlaybackPauseChanged {
+            application_name: application_name,
+            stream_key: stream_key,
+            stream_id: stream_id,
+            is_paused: is_paused,
+            milliseconds: milliseconds
+        })
+    ]
+}
+
+fn handle_receive_media_amf0_command(processor: &mut RtmpProcessor,
+    stream_id: u32,
+    additional_arguments: Vec<Amf0Value>,
+    is_audio: bool) -> Vec<ProcessorResult> {
+
+    let should_receive = additional_arguments.get(0).and_then(|x| x.as_bool()).unwrap_or(true);
+
+    // The peer does not expect a response to these commands
+    if let Some(stream) = processor.active_streams.get_mut(&stream_id) {
+        if is_audio {
+            stream.receive_audio = should_receive;
+        } else {
+            stream.receive_video = should_receive;
+        }
+    }
+
+    vec![]
+}
+
+/// Reads the stream key, which is the first argument after the command object
+fn get_stream_key_argument(additional_arguments: &Vec<Amf0Value>) -> Option<String> {
+    additional_arguments.get(0)
+        .and_then(|x| x.as_str())
+        .map(|x| x.to_string())
+}
+
+/// Reads stream metadata from `onMetaData` data messages.  Encoders usually
+/// send these wrapped in a `@setDataFrame` call.
+fn read_metadata(values: &Vec<Amf0Value>) -> Option<StreamMetadata> {
+    let skip = match values.get(0).and_then(|x| x.as_str()) {
+        Some("@setDataFrame") => 1,
+        _ => 0
+    };
+
+    match values.get(skip).and_then(|x| x.as_str()) {
+        Some("onMetaData") => (),
+        _ => return None
+    }
+
+    values.get(skip + 1)
+        .and_then(|x| x.as_object())
+        .map(|properties| StreamMetadata::from_properties(properties))
+}
+
+fn get_user_control_response(event_type: UserControlEventType, stream_id: u32) -> ProcessorResult {
+    ProcessorResult::ResponseMessage(RtmpMessageDetails {
+        rtmp_timestamp: RtmpTimestamp::new(0),
+        stream_id: 0,
+        message: RtmpMessage::UserControl {
+            event_type: event_type,
+            stream_id: Some(stream_id),
+            buffer_length: None,
+            timestamp: None
+        }
+    })
+}
+
+fn get_stream_response(stream_id: u32, timestamp: RtmpTimestamp, message: RtmpMessage) -> ProcessorResult {
+    ProcessorResult::ResponseMessage(RtmpMessageDetails {
+        rtmp_timestamp: timestamp,
+        stream_id: stream_id,
+        message: message
+    })
+}
+
+fn get_on_status_response(stream_id: u32, information: Amf0Value) -> ProcessorResult {
+    ProcessorResult::ResponseMessage(RtmpMessageDetails {
+        rtmp_timestamp: RtmpTimestamp::new(0),
+        stream_id: stream_id,
+        message: RtmpMessage::Amf0Command {
+            command_name: "onStatus".to_string(),
+            transaction_id: 0.0,
+            command_object: Amf0Value::Null,
+            additional_arguments: vec![information]
+        }
+    })
+}
+
+fn get_amf0_error_response(stream_id: u32, transaction_id: f64, response_data: Amf0Value) -> ProcessorResult {
+    ProcessorResult::ResponseMessage(RtmpMessageDetails {
+        rtmp_timestamp: RtmpTimestamp::new(0),
+        stream_id: stream_id,
+        message: RtmpMessage::Amf0Command{
+            command_name: "_error".to_string(),
+            transaction_id: transaction_id,
+            command_object: response_data,
+            additional_arguments: vec![]
+        }
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use std::time::Duration;
+    use rtmp_message::{RtmpMessage, RtmpMessageDetails, PeerBandwidthLimitType, UserControlEventType};
+
+    use rtmp_time::RtmpTimestamp;
+    use events::ProcessorEvent;
+    use metadata::StreamMetadata;
+    use stream::PublishMode;
+    use super::*;
+    use tests::utils;
+
+    #[test]
+    fn self_chunk_size() {
+        let mut processor = RtmpProcessor::new(get_default_config());
+        let result = processor.set_chunk_size(4096);
+
+        assert_vec_match!(result,
+            ProcessorResult::RaisedEvent(ProcessorEvent::SelfChunkSizeChanged { new_chunk_size: 4096 }),
+            ProcessorResult::ResponseMessage(RtmpMessageDetails {
+                stream_id: 0,
+                rtmp_timestamp: _,
+                message: RtmpMessage::SetChunkSize{size: 4096}
+            })
+        );
+    }
+
+    #[test]
+    fn peer_chunk_size() {
+        let mut processor = RtmpProcessor::new(get_default_config());
+        let result = processor.handle(vec![utils::create_set_chunk_size_message(4000)]).unwrap();
+
+        assert_vec_match!(result,
+            ProcessorResult::RaisedEvent(ProcessorEvent::PeerChunkSizeChanged{new_chunk_size: 4000})
+        );
+    }
+
+    #[test]
+    fn peer_window_ack_size() {
+        let mut processor = RtmpProcessor::new(get_default_config());
+        let result = processor.handle(vec![utils::create_window_ack_message(5000000)]).unwrap();
+
+        assert_vec_match!(result); // should be empty
+    }
+
+    #[test]
+    fn connection_command_received_and_accepted() {
+        let bandwidth_size = 60000;
+        let window_ack_size = 70000;
+        let mut config = get_default_config();
+        config.peer_bandwidth = bandwidth_size;
+        config.window_ack_size = window_ack_size;
+
+        let mut processor = RtmpProcessor::new(config);
+        let app_name = "myapp".to_string();
+        let command = utils::create_connect_command(app_name.clone());
+        let request_id;
+
+        let initial_result = processor.handle(vec![command]).unwrap();
+        assert_vec_match!(initial_result,
+            ProcessorResult::ResponseMessage(RtmpMessageDetails {
+                rtmp_timestamp: _,
+                stream_id: 0,
+                message: RtmpMessage::SetPeerBandwidth{size, limit_type: PeerBandwidthLimitType::Hard}
+            }) if size == bandwidth_size,
+
+            ProcessorResult::ResponseMessage(RtmpMessageDetails {
+                rtmp_timestamp: _,
+                stream_id: 0,
+                message: RtmpMessage::WindowAcknowledgement { size }
+            }) if size == window_ack_size,
+
+            ProcessorResult::RaisedEvent(ProcessorEvent::ConnectionRequested { request_id: rid, application_name: ref name }) if name == &app_name
                 => {request_id = rid}
         );
 
-        let mut command_object_properties = HashMap::new();
-        command_object_properties.insert("fmsVer".to_string(), Amf0Value::Utf8String("version".to_string()));
-        command_object_properties.insert("capabilities".to_string(), Amf0Value::Number(31.0));
+        let command_object = amf0_object!{
+            "fmsVer" => "version",
+            "capabilities" => 31.0,
+        };
+
+        let arguments = vec![amf0_object!{
+            "level" => "status",
+            "code" => "NetConnection.Connect.Success",
+            "description" => "Connection succeeded",
+            "objectEncoding" => 0.0,
+        }];
+
+        let accept_result = processor.accept_request(request_id).unwrap();
+        assert_vec_match!(accept_result,
+            ProcessorResult::ResponseMessage(RtmpMessageDetails{
+                rtmp_timestamp: _,
+                stream_id: 0,
+                message: RtmpMessage::Amf0Command{
+                    command_name: ref name,
+                    transaction_id: 1.0,
+                    command_object: ref obj,
+                    additional_arguments: ref args
+                }
+            }) if name == "_result" && 
+                    args == &arguments &&
+                    obj == &command_object
+        );
+    }
+
+    #[test]
+    fn acknowledgement_sent_when_window_ack_size_reached() {
+        let mut processor = RtmpProcessor::new(get_default_config());
+
+        let result = processor.handle_bytes_received(30000);
+        assert_vec_match!(result);
+
+        let result = processor.handle_bytes_received(20000);
+        assert_vec_match!(result,
+            ProcessorResult::ResponseMessage(RtmpMessageDetails {
+                rtmp_timestamp: _,
+                stream_id: 0,
+                message: RtmpMessage::Acknowledgement { sequence_number: 50000 }
+            })
+        );
+
+        let result = processor.handle_bytes_received(49999);
+        assert_vec_match!(result);
+    }
+
+    #[test]
+    fn acknowledgement_uses_window_size_requested_by_peer() {
+        let mut processor = RtmpProcessor::new(get_default_config());
+        processor.handle(vec![utils::create_window_ack_message(1000)]).unwrap();
+
+        let result = processor.handle_bytes_received(1500);
+        assert_vec_match!(result,
+            ProcessorResult::ResponseMessage(RtmpMessageDetails {
+                rtmp_timestamp: _,
+                stream_id: 0,
+                message: RtmpMessage::Acknowledgement { sequence_number: 1500 }
+            })
+        );
+    }
+
+    #[test]
+    fn zero_window_ack_size_from_peer_is_ignored() {
+        let mut processor = RtmpProcessor::new(get_default_config());
+        processor.handle(vec![utils::create_window_ack_message(1000)]).unwrap();
+        let result = processor.handle(vec![utils::create_window_ack_message(0)]).unwrap();
+        assert_vec_match!(result);
+
+        let result = processor.handle_bytes_received(1);
+        assert_vec_match!(result);
+
+        let result = processor.handle_bytes_received(999);
+        assert_vec_match!(result,
+            ProcessorResult::ResponseMessage(RtmpMessageDetails {
+                rtmp_timestamp: _,
+                stream_id: 0,
+                message: RtmpMessage::Acknowledgement { sequence_number: 1000 }
+            })
+        );
+    }
+
+    #[test]
+    fn acknowledgement_sequence_number_wraps_at_u32() {
+        let mut processor = RtmpProcessor::new(get_default_config());
+        processor.handle_bytes_received(u32::max_value() as usize - 10000);
+        processor.handle_bytes_received(20000);
+
+        let result = processor.handle_bytes_received(40000);
+        assert_vec_match!(result,
+            ProcessorResult::ResponseMessage(RtmpMessageDetails {
+                rtmp_timestamp: _,
+                stream_id: 0,
+                message: RtmpMessage::Acknowledgement { sequence_number: 49999 }
+            })
+        );
+    }
+
+    #[test]
+    fn peer_acknowledgements_reduce_unacknowledged_bytes() {
+        let mut processor = RtmpProcessor::new(get_default_config());
+        processor.handle_bytes_sent(10000);
+        assert_eq!(processor.get_unacknowledged_byte_count(), 10000);
+
+        let result = processor.handle(vec![utils::create_acknowledgement_message(6000)]).unwrap();
+        assert_vec_match!(result);
+        assert_eq!(processor.get_unacknowledged_byte_count(), 4000);
+    }
+
+    #[test]
+    fn unacknowledged_bytes_handle_wrapped_sequence_numbers() {
+        let mut processor = RtmpProcessor::new(get_default_config());
+        processor.handle_bytes_sent(u32::max_value() as usize);
+        processor.handle(vec![utils::create_acknowledgement_message(u32::max_value() - 100)]).unwrap();
+        processor.handle_bytes_sent(200);
+
+        assert_eq!(processor.get_unacknowledged_byte_count(), 300);
+    }
+
+    #[test]
+    fn hard_peer_bandwidth_sets_outgoing_window() {
+        let mut processor = RtmpProcessor::new(get_default_config());
+        assert_eq!(processor.get_outgoing_window_size(), None);
+
+        let result = processor.handle(vec![utils::create_peer_bandwidth_message(2500000, PeerBandwidthLimitType::Hard)]).unwrap();
+        assert_vec_match!(result,
+            ProcessorResult::ResponseMessage(RtmpMessageDetails {
+                rtmp_timestamp: _,
+                stream_id: 0,
+                message: RtmpMessage::WindowAcknowledgement { size: 2500000 }
+            })
+        );
+
+        assert_eq!(processor.get_outgoing_window_size(), Some(2500000));
+    }
+
+    #[test]
+    fn soft_peer_bandwidth_uses_smaller_window() {
+        let mut processor = RtmpProcessor::new(get_default_config());
+        processor.handle(vec![utils::create_peer_bandwidth_message(2000, PeerBandwidthLimitType::Hard)]).unwrap();
+
+        let result = processor.handle(vec![utils::create_peer_bandwidth_message(3000, PeerBandwidthLimitType::Soft)]).unwrap();
+        assert_vec_match!(result);
+        assert_eq!(processor.get_outgoing_window_size(), Some(2000));
+
+        let result = processor.handle(vec![utils::create_peer_bandwidth_message(1000, PeerBandwidthLimitType::Soft)]).unwrap();
+        assert_vec_match!(result,
+            ProcessorResult::ResponseMessage(RtmpMessageDetails {
+                rtmp_timestamp: _,
+                stream_id: 0,
+                message: RtmpMessage::WindowAcknowledgement { size: 1000 }
+            })
+        );
+
+        assert_eq!(processor.get_outgoing_window_size(), Some(1000));
+    }
+
+    #[test]
+    fn dynamic_peer_bandwidth_applies_only_after_hard_limit() {
+        let mut processor = RtmpProcessor::new(get_default_config());
+        let result = processor.handle(vec![utils::create_peer_bandwidth_message(1000, PeerBandwidthLimitType::Dynamic)]).unwrap();
+        assert_vec_match!(result);
+        assert_eq!(processor.get_outgoing_window_size(), None);
+
+        processor.handle(vec![utils::create_peer_bandwidth_message(1000, PeerBandwidthLimitType::Hard)]).unwrap();
+        processor.handle(vec![utils::create_peer_bandwidth_message(5000, PeerBandwidthLimitType::Dynamic)]).unwrap();
+        assert_eq!(processor.get_outgoing_window_size(), Some(5000));
+
+        processor.handle(vec![utils::create_peer_bandwidth_message(4000, PeerBandwidthLimitType::Soft)]).unwrap();
+        processor.handle(vec![utils::create_peer_bandwidth_message(8000, PeerBandwidthLimitType::Dynamic)]).unwrap();
+        assert_eq!(processor.get_outgoing_window_size(), Some(4000));
+    }
+
+    #[test]
+    fn no_window_ack_sent_when_peer_bandwidth_matches_sent_window() {
+        let mut processor = RtmpProcessor::new(get_default_config());
+        processor.handle(vec![utils::create_connect_command("app".to_string())]).unwrap();
+
+        let result = processor.handle(vec![utils::create_peer_bandwidth_message(50000, PeerBandwidthLimitType::Hard)]).unwrap();
+        assert_vec_match!(result);
+        assert_eq!(processor.get_outgoing_window_size(), Some(50000));
+    }
+
+    #[test]
+    fn ping_request_answered_with_ping_response() {
+        let mut processor = RtmpProcessor::new(get_default_config());
+        let result = processor.handle(vec![utils::create_ping_message(UserControlEventType::PingRequest, 1234)]).unwrap();
+
+        assert_vec_match!(result,
+            ProcessorResult::ResponseMessage(RtmpMessageDetails {
+                rtmp_timestamp: _,
+                stream_id: 0,
+                message: RtmpMessage::UserControl {
+                    event_type: UserControlEventType::PingResponse,
+                    stream_id: None,
+                    buffer_length: None,
+                    timestamp: Some(timestamp)
+                }
+            }) if timestamp == 1234
+        );
+    }
+
+    #[test]
+    fn ping_response_measures_round_trip_time() {
+        let mut processor = RtmpProcessor::new(get_default_config());
+        let ping_timestamp;
+        let result = processor.send_ping();
+        assert_vec_match!(result,
+            ProcessorResult::ResponseMessage(RtmpMessageDetails {
+                rtmp_timestamp: _,
+                stream_id: 0,
+                message: RtmpMessage::UserControl {
+                    event_type: UserControlEventType::PingRequest,
+                    stream_id: None,
+                    buffer_length: None,
+                    timestamp: Some(timestamp)
+                }
+            }) => {ping_timestamp = timestamp}
+        );
+
+        assert_eq!(processor.get_round_trip_time(), None);
+
+        let response = utils::create_ping_message(UserControlEventType::PingResponse, ping_timestamp.value);
+        let result = processor.handle(vec![response]).unwrap();
+        assert_vec_match!(result);
+
+        let round_trip_time = processor.get_round_trip_time().unwrap();
+        assert!(round_trip_time < Duration::from_secs(5), "Unexpected round trip time of {:?}", round_trip_time);
+    }
+
+    #[test]
+    fn event_raised_when_too_many_pings_unanswered() {
+        let mut processor = RtmpProcessor::new(get_default_config());
+        for _ in 0..3 {
+            let result = processor.send_ping();
+            assert_eq!(result.len(), 1);
+        }
+
+        let result = processor.send_ping();
+        assert_vec_match!(result,
+            ProcessorResult::RaisedEvent(ProcessorEvent::PeerUnresponsive { unanswered_pings: 3 })
+        );
+    }
+
+    #[test]
+    fn answered_ping_resets_unanswered_count() {
+        let mut processor = RtmpProcessor::new(get_default_config());
+        processor.send_ping();
+        processor.send_ping();
+        processor.handle(vec![utils::create_ping_message(UserControlEventType::PingResponse, 0)]).unwrap();
+
+        for _ in 0..3 {
+            let result = processor.send_ping();
+            assert_vec_match!(result,
+                ProcessorResult::ResponseMessage(RtmpMessageDetails {
+                    rtmp_timestamp: _,
+                    stream_id: 0,
+                    message: RtmpMessage::UserControl { event_type: UserControlEventType::PingRequest, .. }
+                })
+            );
+        }
+    }
+
+    #[test]
+    fn connection_request_rejected() {
+        let mut processor = RtmpProcessor::new(get_default_config());
+        let result = processor.handle(vec![utils::create_connect_command("app".to_string())]).unwrap();
+        let request_id = get_request_id(&result);
+
+        let reject_result = processor.reject_request(request_id).unwrap();
+        assert_vec_match!(reject_result,
+            ProcessorResult::ResponseMessage(RtmpMessageDetails {
+                rtmp_timestamp: _,
+                stream_id: 0,
+                message: RtmpMessage::Amf0Command { command_name: ref name, transaction_id: 1.0, additional_arguments: ref args, .. }
+            }) if name == "_error" && args[0].get("code").and_then(|x| x.as_str()) == Some("NetConnection.Connect.Rejected")
+        );
+    }
+
+    #[test]
+    fn unknown_request_id_cannot_be_rejected() {
+        let mut processor = RtmpProcessor::new(get_default_config());
+        assert!(processor.reject_request(5).is_err());
+    }
+
+    #[test]
+    fn release_stream_request_accepted() {
+        let mut processor = RtmpProcessor::new(get_default_config());
+        connect(&mut processor, "live");
+
+        let result = processor.handle(vec![utils::create_release_stream_command("key".to_string(), 2.0)]).unwrap();
+        let request_id;
+        assert_vec_match!(result,
+            ProcessorResult::RaisedEvent(ProcessorEvent::ReleaseStreamRequested { request_id: rid, application_name: ref app, stream_key: ref key })
+                if app == "live" && key == "key" => {request_id = rid}
+        );
+
+        let accept_result = processor.accept_request(request_id).unwrap();
+        assert_vec_match!(accept_result,
+            ProcessorResult::ResponseMessage(RtmpMessageDetails {
+                rtmp_timestamp: _,
+                stream_id: 0,
+                message: RtmpMessage::Amf0Command { command_name: ref name, transaction_id: 2.0, .. }
+            }) if name == "_result"
+        );
+    }
+
+    #[test]
+    fn release_stream_request_rejected() {
+        let mut processor = RtmpProcessor::new(get_default_config());
+        connect(&mut processor, "live");
+
+        let result = processor.handle(vec![utils::create_release_stream_command("key".to_string(), 2.0)]).unwrap();
+        let request_id = get_request_id(&result);
+
+        let reject_result = processor.reject_request(request_id).unwrap();
+        assert_vec_match!(reject_result,
+            ProcessorResult::ResponseMessage(RtmpMessageDetails {
+                rtmp_timestamp: _,
+                stream_id: 0,
+                message: RtmpMessage::Amf0Command { command_name: ref name, transaction_id: 2.0, .. }
+            }) if name == "_error"
+        );
+    }
+
+    #[test]
+    fn release_stream_before_connection_accepted_returns_error() {
+        let mut processor = RtmpProcessor::new(get_default_config());
+        let result = processor.handle(vec![utils::create_release_stream_command("key".to_string(), 2.0)]).unwrap();
+
+        assert_vec_match!(result,
+            ProcessorResult::ResponseMessage(RtmpMessageDetails {
+                rtmp_timestamp: _,
+                stream_id: 0,
+                message: RtmpMessage::Amf0Command { command_name: ref name, transaction_id: 2.0, .. }
+            }) if name == "_error"
+        );
+    }
+
+    #[test]
+    fn fc_publish_answered_with_on_fc_publish() {
+        let mut processor = RtmpProcessor::new(get_default_config());
+        connect(&mut processor, "live");
+
+        let result = processor.handle(vec![utils::create_fc_publish_command("key".to_string(), 3.0)]).unwrap();
+        assert_vec_match!(result,
+            ProcessorResult::ResponseMessage(RtmpMessageDetails {
+                rtmp_timestamp: _,
+                stream_id: 0,
+                message: RtmpMessage::Amf0Command { command_name: ref name, additional_arguments: ref args, .. }
+            }) if name == "onFCPublish" &&
+                    args[0].get("code").and_then(|x| x.as_str()) == Some("NetStream.Publish.Start") &&
+                    args[0].get("description").and_then(|x| x.as_str()) == Some("key")
+        );
+    }
+
+    #[test]
+    fn create_stream_allocates_new_stream_ids() {
+        let mut processor = RtmpProcessor::new(get_default_config());
+        connect(&mut processor, "live");
+
+        let first_id = create_stream(&mut processor);
+        let second_id = create_stream(&mut processor);
+
+        assert!(first_id != 0, "Stream id 0 is reserved for the connection");
+        assert!(second_id != 0, "Stream id 0 is reserved for the connection");
+        assert!(first_id != second_id, "Stream ids were not unique");
+    }
+
+    #[test]
+    fn create_stream_before_connection_accepted_returns_error() {
+        let mut processor = RtmpProcessor::new(get_default_config());
+        let result = processor.handle(vec![utils::create_create_stream_command(4.0)]).unwrap();
+
+        assert_vec_match!(result,
+            ProcessorResult::ResponseMessage(RtmpMessageDetails {
+                rtmp_timestamp: _,
+                stream_id: 0,
+                message: RtmpMessage::Amf0Command { command_name: ref name, transaction_id: 4.0, .. }
+            }) if name == "_error"
+        );
+    }
+
+    #[test]
+    fn create_stream_returns_error_when_too_many_streams_open() {
+        let mut config = get_default_config();
+        config.max_streams = 2;
+        let mut processor = RtmpProcessor::new(config);
+        connect(&mut processor, "live");
+
+        let first_id = create_stream(&mut processor);
+        create_stream(&mut processor);
+
+        let result = processor.handle(vec![utils::create_create_stream_command(4.0)]).unwrap();
+        assert_vec_match!(result,
+            ProcessorResult::ResponseMessage(RtmpMessageDetails {
+                rtmp_timestamp: _,
+                stream_id: 0,
+                message: RtmpMessage::Amf0Command { command_name: ref name, transaction_id: 4.0, .. }
+            }) if name == "_error"
+        );
+
+        // Deleting a stream makes room for another one
+        processor.handle(vec![utils::create_delete_stream_command(first_id)]).unwrap();
+        create_stream(&mut processor);
+    }
+
+    #[test]
+    fn delete_stream_ends_publishing() {
+        let mut processor = RtmpProcessor::new(get_default_config());
+        connect(&mut processor, "live");
+        let stream_id = start_publishing(&mut processor, "key");
+
+        let result = processor.handle(vec![utils::create_delete_stream_command(stream_id)]).unwrap();
+        assert_vec_match!(result,
+            ProcessorResult::RaisedEvent(ProcessorEvent::PublishStreamFinished {
+                application_name: ref app,
+                stream_key: ref key
+            }) if app == "live" && key == "key"
+        );
+
+        let result = processor.handle(vec![utils::create_audio_data_message(stream_id, 10, vec![1])]).unwrap();
+        assert_vec_match!(result, ProcessorResult::UnhandleableMessage(_));
+
+        let result = processor.handle(vec![utils::create_publish_command(stream_id, "key".to_string(), "live")]).unwrap();
+        assert_vec_match!(result,
+            ProcessorResult::ResponseMessage(RtmpMessageDetails {
+                rtmp_timestamp: _,
+                stream_id: _,
+                message: RtmpMessage::Amf0Command { command_name: ref name, .. }
+            }) if name == "_error"
+        );
+    }
+
+    #[test]
+    fn fc_unpublish_ends_publishing_and_answers_with_on_fc_unpublish() {
+        let mut processor = RtmpProcessor::new(get_default_config());
+        connect(&mut processor, "live");
+        let stream_id = start_publishing(&mut processor, "key");
+
+        let result = processor.handle(vec![utils::create_fc_unpublish_command("key".to_string(), 5.0)]).unwrap();
+        assert_vec_match!(result,
+            ProcessorResult::RaisedEvent(ProcessorEvent::PublishStreamFinished {
+                application_name: ref app,
+                stream_key: ref key
+            }) if app == "live" && key == "key",
+
+            ProcessorResult::ResponseMessage(RtmpMessageDetails {
+                rtmp_timestamp: _,
+                stream_id: 0,
+                message: RtmpMessage::Amf0Command { command_name: ref name, additional_arguments: ref args, .. }
+            }) if name == "onFCUnpublish" &&
+                    args[0].get("code").and_then(|x| x.as_str()) == Some("NetStream.Unpublish.Success") &&
+                    args[0].get("description").and_then(|x| x.as_str()) == Some("key")
+        );
+
+        let result = processor.handle(vec![utils::create_video_data_message(stream_id, 10, vec![1])]).unwrap();
+        assert_vec_match!(result, ProcessorResult::UnhandleableMessage(_));
+    }
+
+    #[test]
+    fn close_stream_ends_playback_and_keeps_stream() {
+        let mut processor = RtmpProcessor::new(get_default_config());
+        connect(&mut processor, "live");
+        let stream_id = start_playing(&mut processor, "key");
+
+        let result = processor.handle(vec![utils::create_close_stream_command(stream_id)]).unwrap();
+        assert_vec_match!(result,
+            ProcessorResult::RaisedEvent(ProcessorEvent::PlayStreamFinished {
+                application_name: ref app,
+                stream_key: ref key,
+                stream_id: id
+            }) if app == "live" && key == "key" && id == stream_id
+        );
+
+        match processor.send_audio_data(stream_id, RtmpTimestamp::new(10), vec![1]) {
+            Err(RtmpProcessorError::StreamNotPlaying(id)) if id == stream_id => (),
+            x => panic!("Expected StreamNotPlaying error but got {:?}", x)
+        }
+
+        // The closed stream can be used again
+        let result = processor.handle(vec![utils::create_publish_command(stream_id, "key2".to_string(), "live")]).unwrap();
+        assert_vec_match!(result,
+            ProcessorResult::RaisedEvent(ProcessorEvent::PublishStreamRequested { stream_key: ref key, .. }) if key == "key2"
+        );
+    }
+
+    #[test]
+    fn requests_for_deleted_stream_are_dropped() {
+        let mut processor = RtmpProcessor::new(get_default_config());
+        connect(&mut processor, "live");
+        let stream_id = create_stream(&mut processor);
+
+        let result = processor.handle(vec![utils::create_publish_command(stream_id, "key".to_string(), "live")]).unwrap();
+        let request_id = get_request_id(&result);
 
-        let mut information_properties = HashMap::new();
-        information_properties.insert("level".to_string(), Amf0Value::Utf8String("status".to_string()));
-        information_properties.insert("code".to_string(), Amf0Value::Utf8String("NetConnection.Connect.Success".to_string()));
-        information_properties.insert("description".to_string(), Amf0Value::Utf8String("Connection succeeded".to_string()));
-        information_properties.insert("objectEncoding".to_string(), Amf0Value::Number(0.0));
+        let result = processor.handle(vec![utils::create_delete_stream_command(stream_id)]).unwrap();
+        assert_vec_match!(result);
 
-        let command_object = Amf0Value::Object(command_object_properties);
-        let arguments = vec![Amf0Value::Object(information_properties)];
+        match processor.accept_request(request_id) {
+            Err(RtmpProcessorError::UnknownRequestId) => (),
+            x => panic!("Expected UnknownRequestId error but got {:?}", x)
+        }
+    }
+
+    #[test]
+    fn publish_request_accepted() {
+        let mut processor = RtmpProcessor::new(get_default_config());
+        connect(&mut processor, "live");
+        let stream_id = create_stream(&mut processor);
+
+        let result = processor.handle(vec![utils::create_publish_command(stream_id, "key".to_string(), "live")]).unwrap();
+        let request_id;
+        assert_vec_match!(result,
+            ProcessorResult::RaisedEvent(ProcessorEvent::PublishStreamRequested {
+                request_id: rid,
+                application_name: ref app,
+                stream_key: ref key,
+                mode: PublishMode::Live
+            }) if app == "live" && key == "key" => {request_id = rid}
+        );
 
         let accept_result = processor.accept_request(request_id).unwrap();
         assert_vec_match!(accept_result,
-            ProcessorResult::ResponseMessage(RtmpMessageDetails{
+            ProcessorResult::ResponseMessage(RtmpMessageDetails {
                 rtmp_timestamp: _,
                 stream_id: 0,
-                message: RtmpMessage::Amf0Command{
-                    command_name: ref name,
-                    transaction_id: 1.0,
-                    command_object: ref obj,
-                    additional_arguments: ref args
-                }
-            }) if name == "_result" && 
-                    args == &arguments &&
-                    obj == &command_object
+                message: RtmpMessage::UserControl { event_type: UserControlEventType::StreamBegin, stream_id: Some(id), .. }
+            }) if id == stream_id,
+
+            ProcessorResult::ResponseMessage(RtmpMessageDetails {
+                rtmp_timestamp: _,
+                stream_id: id,
+                message: RtmpMessage::Amf0Command { command_name: ref name, additional_arguments: ref args, .. }
+            }) if id == stream_id &&
+                    name == "onStatus" &&
+                    args[0].get("level").and_then(|x| x.as_str()) == Some("status") &&
+                    args[0].get("code").and_then(|x| x.as_str()) == Some("NetStream.Publish.Start")
+        );
+    }
+
+    #[test]
+    fn publish_modes_passed_in_request() {
+        let tests = vec![("live", PublishMode::Live), ("record", PublishMode::Record), ("append", PublishMode::Append)];
+        for (name, expected_mode) in tests {
+            let mut processor = RtmpProcessor::new(get_default_config());
+            connect(&mut processor, "live");
+            let stream_id = create_stream(&mut processor);
+
+            let result = processor.handle(vec![utils::create_publish_command(stream_id, "key".to_string(), name)]).unwrap();
+            assert_vec_match!(result,
+                ProcessorResult::RaisedEvent(ProcessorEvent::PublishStreamRequested { mode, .. }) if mode == expected_mode
+            );
+        }
+    }
+
+    #[test]
+    fn publish_with_unknown_mode_returns_error() {
+        let mut processor = RtmpProcessor::new(get_default_config());
+        connect(&mut processor, "live");
+        let stream_id = create_stream(&mut processor);
+
+        let result = processor.handle(vec![utils::create_publish_command(stream_id, "key".to_string(), "other")]).unwrap();
+        assert_vec_match!(result,
+            ProcessorResult::ResponseMessage(RtmpMessageDetails {
+                message: RtmpMessage::Amf0Command { command_name: ref name, .. }, ..
+            }) if name == "_error"
+        );
+    }
+
+    #[test]
+    fn publish_on_stream_not_created_returns_error() {
+        let mut processor = RtmpProcessor::new(get_default_config());
+        connect(&mut processor, "live");
+
+        let result = processor.handle(vec![utils::create_publish_command(5, "key".to_string(), "live")]).unwrap();
+        assert_vec_match!(result,
+            ProcessorResult::ResponseMessage(RtmpMessageDetails {
+                message: RtmpMessage::Amf0Command { command_name: ref name, .. }, ..
+            }) if name == "_error"
+        );
+    }
+
+    #[test]
+    fn publish_request_rejected() {
+        let mut processor = RtmpProcessor::new(get_default_config());
+        connect(&mut processor, "live");
+        let stream_id = create_stream(&mut processor);
+
+        let result = processor.handle(vec![utils::create_publish_command(stream_id, "key".to_string(), "live")]).unwrap();
+        let request_id = get_request_id(&result);
+
+        let reject_result = processor.reject_request(request_id).unwrap();
+        assert_vec_match!(reject_result,
+            ProcessorResult::ResponseMessage(RtmpMessageDetails {
+                rtmp_timestamp: _,
+                stream_id: id,
+                message: RtmpMessage::Amf0Command { command_name: ref name, additional_arguments: ref args, .. }
+            }) if id == stream_id &&
+                    name == "onStatus" &&
+                    args[0].get("level").and_then(|x| x.as_str()) == Some("error")
+        );
+
+        // The stream can be published again after a rejection
+        let result = processor.handle(vec![utils::create_publish_command(stream_id, "key2".to_string(), "live")]).unwrap();
+        assert_vec_match!(result,
+            ProcessorResult::RaisedEvent(ProcessorEvent::PublishStreamRequested { stream_key: ref key, .. }) if key == "key2"
+        );
+    }
+
+    #[test]
+    fn media_data_on_publishing_stream_raises_events() {
+        let mut processor = RtmpProcessor::new(get_default_config());
+        connect(&mut processor, "live");
+        let stream_id = start_publishing(&mut processor, "key");
+
+        let result = processor.handle(vec![
+            utils::create_audio_data_message(stream_id, 10, vec![1, 2]),
+            utils::create_video_data_message(stream_id, 20, vec![3, 4]),
+        ]).unwrap();
+
+        assert_vec_match!(result,
+            ProcessorResult::RaisedEvent(ProcessorEvent::AudioDataReceived { ref application_name, ref stream_key, timestamp, ref data })
+                if application_name == "live" && stream_key == "key" && timestamp == RtmpTimestamp::new(10) && data == &vec![1, 2],
+
+            ProcessorResult::RaisedEvent(ProcessorEvent::VideoDataReceived { ref application_name, ref stream_key, timestamp, ref data })
+                if application_name == "live" && stream_key == "key" && timestamp == RtmpTimestamp::new(20) && data == &vec![3, 4]
+        );
+    }
+
+    #[test]
+    fn media_data_on_stream_not_publishing_is_unhandleable() {
+        let mut processor = RtmpProcessor::new(get_default_config());
+        connect(&mut processor, "live");
+        let stream_id = create_stream(&mut processor);
+
+        let result = processor.handle(vec![utils::create_audio_data_message(stream_id, 10, vec![1, 2])]).unwrap();
+        assert_vec_match!(result,
+            ProcessorResult::UnhandleableMessage(RtmpMessageDetails { message: RtmpMessage::AudioData { .. }, .. })
+        );
+    }
+
+    #[test]
+    fn metadata_on_publishing_stream_raises_event() {
+        let mut processor = RtmpProcessor::new(get_default_config());
+        connect(&mut processor, "live");
+        let stream_id = start_publishing(&mut processor, "key");
+
+        let metadata = amf0_object!{
+            "width" => 1280.0,
+            "height" => 720.0,
+            "videocodecid" => "avc1",
+        };
+
+        let result = processor.handle(vec![utils::create_metadata_message(stream_id, metadata)]).unwrap();
+        assert_vec_match!(result,
+            ProcessorResult::RaisedEvent(ProcessorEvent::StreamMetaDataChanged { ref application_name, ref stream_key, ref meta_data })
+                if application_name == "live" &&
+                    stream_key == "key" &&
+                    meta_data.video_width == Some(1280) &&
+                    meta_data.video_height == Some(720) &&
+                    meta_data.video_codec == Some("avc1".to_string())
+        );
+    }
+
+    #[test]
+    fn play_request_accepted_for_live_stream() {
+        let mut processor = RtmpProcessor::new(get_default_config());
+        connect(&mut processor, "live");
+        let stream_id = create_stream(&mut processor);
+
+        let result = processor.handle(vec![utils::create_play_command(stream_id, "key".to_string(), -2.0)]).unwrap();
+        let request_id;
+        assert_vec_match!(result,
+            ProcessorResult::RaisedEvent(ProcessorEvent::PlayStreamRequested {
+                request_id: rid,
+                application_name: ref app,
+                stream_key: ref key,
+                stream_id: id,
+                start,
+                duration,
+                reset: true
+            }) if app == "live" && key == "key" && id == stream_id && start == -2.0 && duration == -1.0 => {request_id = rid}
+        );
+
+        let accept_result = processor.accept_request(request_id).unwrap();
+        assert_vec_match!(accept_result,
+            ProcessorResult::ResponseMessage(RtmpMessageDetails {
+                rtmp_timestamp: _,
+                stream_id: 0,
+                message: RtmpMessage::UserControl { event_type: UserControlEventType::StreamBegin, stream_id: Some(id), .. }
+            }) if id == stream_id,
+
+            ProcessorResult::ResponseMessage(RtmpMessageDetails {
+                rtmp_timestamp: _,
+                stream_id: id,
+                message: RtmpMessage::Amf0Command { command_name: ref name, additional_arguments: ref args, .. }
+            }) if id == stream_id &&
+                    name == "onStatus" &&
+                    args[0].get("code").and_then(|x| x.as_str()) == Some("NetStream.Play.Reset"),
+
+            ProcessorResult::ResponseMessage(RtmpMessageDetails {
+                rtmp_timestamp: _,
+                stream_id: id,
+                message: RtmpMessage::Amf0Command { command_name: ref name, additional_arguments: ref args, .. }
+            }) if id == stream_id &&
+                    name == "onStatus" &&
+                    args[0].get("code").and_then(|x| x.as_str()) == Some("NetStream.Play.Start"),
+
+            ProcessorResult::ResponseMessage(RtmpMessageDetails {
+                rtmp_timestamp: _,
+                stream_id: id,
+                message: RtmpMessage::Amf0Data { ref values }
+            }) if id == stream_id && values[0].as_str() == Some("|RtmpSampleAccess")
+        );
+    }
+
+    #[test]
+    fn play_request_for_recording_sends_stream_is_recorded() {
+        let mut processor = RtmpProcessor::new(get_default_config());
+        connect(&mut processor, "live");
+        let stream_id = create_stream(&mut processor);
+
+        let result = processor.handle(vec![utils::create_play_command(stream_id, "key".to_string(), 0.0)]).unwrap();
+        let request_id = get_request_id(&result);
+
+        let accept_result = processor.accept_request(request_id).unwrap();
+        match accept_result[0] {
+            ProcessorResult::ResponseMessage(RtmpMessageDetails {
+                rtmp_timestamp: _,
+                stream_id: 0,
+                message: RtmpMessage::UserControl { event_type: UserControlEventType::StreamIsRecorded, stream_id: Some(id), .. }
+            }) if id == stream_id => (),
+            ref result => panic!("Expected StreamIsRecorded but received {:?}", result)
+        }
+    }
+
+    #[test]
+    fn play_request_rejected() {
+        let mut processor = RtmpProcessor::new(get_default_config());
+        connect(&mut processor, "live");
+        let stream_id = create_stream(&mut processor);
+
+        let result = processor.handle(vec![utils::create_play_command(stream_id, "key".to_string(), -2.0)]).unwrap();
+        let request_id = get_request_id(&result);
+
+        let reject_result = processor.reject_request(request_id).unwrap();
+        assert_vec_match!(reject_result,
+            ProcessorResult::ResponseMessage(RtmpMessageDetails {
+                rtmp_timestamp: _,
+                stream_id: id,
+                message: RtmpMessage::Amf0Command { command_name: ref name, additional_arguments: ref args, .. }
+            }) if id == stream_id &&
+                    name == "onStatus" &&
+                    args[0].get("code").and_then(|x| x.as_str()) == Some("NetStream.Play.Failed")
+        );
+
+        let send_result = processor.send_video_data(stream_id, RtmpTimestamp::new(0), vec![1]);
+        assert!(send_result.is_err(), "Video was sent to a stream that was not playing");
+    }
+
+    #[test]
+    fn play_on_stream_not_created_returns_error() {
+        let mut processor = RtmpProcessor::new(get_default_config());
+        connect(&mut processor, "live");
+
+        let result = processor.handle(vec![utils::create_play_command(5, "key".to_string(), -2.0)]).unwrap();
+        assert_vec_match!(result,
+            ProcessorResult::ResponseMessage(RtmpMessageDetails {
+                message: RtmpMessage::Amf0Command { command_name: ref name, .. }, ..
+            }) if name == "_error"
+        );
+    }
+
+    #[test]
+    fn play2_switches_playing_stream() {
+        let mut processor = RtmpProcessor::new(get_default_config());
+        connect(&mut processor, "live");
+        let stream_id = start_playing(&mut processor, "key");
+
+        let parameters = amf0_object!{
+            "streamName" => "key2",
+            "oldStreamName" => "key",
+            "start" => -2.0,
+            "len" => -1.0,
+            "transition" => "switch",
+        };
+
+        let result = processor.handle(vec![utils::create_play2_command(stream_id, parameters)]).unwrap();
+        let request_id;
+        assert_vec_match!(result,
+            ProcessorResult::RaisedEvent(ProcessorEvent::PlayStreamRequested { request_id: rid, ref stream_key, stream_id: id, reset: false, .. })
+                if stream_key == "key2" && id == stream_id => {request_id = rid}
+        );
+
+        // A rejected switch keeps the original stream playing
+        processor.reject_request(request_id).unwrap();
+        let send_result = processor.send_video_data(stream_id, RtmpTimestamp::new(0), vec![1]).unwrap();
+        assert_eq!(send_result.len(), 1);
+
+        // An accepted switch finishes the original stream before the new one starts
+        let parameters = amf0_object!{
+            "streamName" => "key3",
+            "oldStreamName" => "key",
+            "transition" => "switch",
+        };
+
+        let result = processor.handle(vec![utils::create_play2_command(stream_id, parameters)]).unwrap();
+        let request_id = get_request_id(&result);
+        let accept_result = processor.accept_request(request_id).unwrap();
+
+        match accept_result.first() {
+            Some(&ProcessorResult::RaisedEvent(ProcessorEvent::PlayStreamFinished { ref application_name, ref stream_key, stream_id: id }))
+                if application_name == "live" && stream_key == "key" && id == stream_id => (),
+            x => panic!("Expected PlayStreamFinished event for the original stream but got {:?}", x)
+        }
+
+        let finished_count = accept_result.iter().filter(|result| match **result {
+            ProcessorResult::RaisedEvent(ProcessorEvent::PlayStreamFinished { .. }) => true,
+            _ => false
+        }).count();
+
+        assert_eq!(finished_count, 1);
+    }
+
+    #[test]
+    fn media_sent_to_playing_stream() {
+        let mut processor = RtmpProcessor::new(get_default_config());
+        connect(&mut processor, "live");
+        let stream_id = start_playing(&mut processor, "key");
+
+        let audio_result = processor.send_audio_data(stream_id, RtmpTimestamp::new(10), vec![1, 2]).unwrap();
+        assert_vec_match!(audio_result,
+            ProcessorResult::ResponseMessage(RtmpMessageDetails {
+                rtmp_timestamp,
+                stream_id: id,
+                message: RtmpMessage::AudioData { ref data }
+            }) if id == stream_id && rtmp_timestamp == RtmpTimestamp::new(10) && data == &vec![1, 2]
+        );
+
+        let video_result = processor.send_video_data(stream_id, RtmpTimestamp::new(20), vec![3, 4]).unwrap();
+        assert_vec_match!(video_result,
+            ProcessorResult::ResponseMessage(RtmpMessageDetails {
+                rtmp_timestamp,
+                stream_id: id,
+                message: RtmpMessage::VideoData { ref data }
+            }) if id == stream_id && rtmp_timestamp == RtmpTimestamp::new(20) && data == &vec![3, 4]
+        );
+
+        let mut metadata = StreamMetadata::new();
+        metadata.video_width = Some(1280);
+        let metadata_result = processor.send_metadata(stream_id, &metadata).unwrap();
+        assert_vec_match!(metadata_result,
+            ProcessorResult::ResponseMessage(RtmpMessageDetails {
+                rtmp_timestamp: _,
+                stream_id: id,
+                message: RtmpMessage::Amf0Data { ref values }
+            }) if id == stream_id &&
+                    values[0].as_str() == Some("onMetaData") &&
+                    values[1].get("width").and_then(|x| x.as_f64()) == Some(1280.0)
+        );
+    }
+
+    #[test]
+    fn media_not_sent_while_paused() {
+        let mut processor = RtmpProcessor::new(get_default_config());
+        connect(&mut processor, "live");
+        let stream_id = start_playing(&mut processor, "key");
+
+        let pause_result = processor.handle(vec![utils::create_pause_command(stream_id, true, 500.0)]).unwrap();
+        assert_vec_match!(pause_result,
+            ProcessorResult::ResponseMessage(RtmpMessageDetails {
+                rtmp_timestamp: _,
+                stream_id: 0,
+                message: RtmpMessage::UserControl { event_type: UserControlEventType::StreamEof, .. }
+            }),
+
+            ProcessorResult::ResponseMessage(RtmpMessageDetails {
+                rtmp_timestamp: _,
+                stream_id: id,
+                message: RtmpMessage::Amf0Command { ref command_name, ref additional_arguments, .. }
+            }) if id == stream_id &&
+                    command_name == "onStatus" &&
+                    additional_arguments[0].get("code").and_then(|x| x.as_str()) == Some("NetStream.Pause.Notify"),
+
+            ProcessorResult::RaisedEvent(ProcessorEvent::PlaybackPauseChanged { stream_id: id, is_paused: true, milliseconds, .. })
+                if id == stream_id && milliseconds == 500.0
+        );
+
+        let result = processor.send_audio_data(stream_id, RtmpTimestamp::new(0), vec![1]).unwrap();
+        assert_vec_match!(result);
+
+        let unpause_result = processor.handle(vec![utils::create_pause_command(stream_id, false, 500.0)]).unwrap();
+        assert_vec_match!(unpause_result,
+            ProcessorResult::ResponseMessage(RtmpMessageDetails {
+                rtmp_timestamp: _,
+                stream_id: 0,
+                message: RtmpMessage::UserControl { event_type: UserControlEventType::StreamBegin, .. }
+            }),
+
+            ProcessorResult::ResponseMessage(RtmpMessageDetails {
+                message: RtmpMessage::Amf0Command { ref additional_arguments, .. }, ..
+            }) if additional_arguments[0].get("code").and_then(|x| x.as_str()) == Some("NetStream.Unpause.Notify"),
+
+            ProcessorResult::RaisedEvent(ProcessorEvent::PlaybackPauseChanged { is_paused: false, .. })
+        );
+
+        let result = processor.send_audio_data(stream_id, RtmpTimestamp::new(0), vec![1]).unwrap();
+        assert_eq!(result.len(), 1);
+    }
+
+    #[test]
+    fn receive_audio_and_video_toggle_media() {
+        let mut processor = RtmpProcessor::new(get_default_config());
+        connect(&mut processor, "live");
+        let stream_id = start_playing(&mut processor, "key");
+
+        let result = processor.handle(vec![utils::create_receive_media_command(stream_id, "receiveVideo", false)]).unwrap();
+        assert_vec_match!(result);
+
+        assert_eq!(processor.send_video_data(stream_id, RtmpTimestamp::new(0), vec![1]).unwrap().len(), 0);
+        assert_eq!(processor.send_audio_data(stream_id, RtmpTimestamp::new(0), vec![1]).unwrap().len(), 1);
+
+        processor.handle(vec![
+            utils::create_receive_media_command(stream_id, "receiveVideo", true),
+            utils::create_receive_media_command(stream_id, "receiveAudio", false),
+        ]).unwrap();
+
+        assert_eq!(processor.send_video_data(stream_id, RtmpTimestamp::new(0), vec![1]).unwrap().len(), 1);
+        assert_eq!(processor.send_audio_data(stream_id, RtmpTimestamp::new(0), vec![1]).unwrap().len(), 0);
+    }
+
+    #[test]
+    fn seek_request_accepted() {
+        let mut processor = RtmpProcessor::new(get_default_config());
+        connect(&mut processor, "live");
+        let stream_id = start_playing(&mut processor, "key");
+
+        let result = processor.handle(vec![utils::create_seek_command(stream_id, 1500.0, 5.0)]).unwrap();
+        let request_id;
+        assert_vec_match!(result,
+            ProcessorResult::RaisedEvent(ProcessorEvent::PlaybackSeekRequested { request_id: rid, ref stream_key, stream_id: id, milliseconds, .. })
+                if stream_key == "key" && id == stream_id && milliseconds == 1500.0 => {request_id = rid}
+        );
+
+        let accept_result = processor.accept_request(request_id).unwrap();
+        assert_vec_match!(accept_result,
+            ProcessorResult::ResponseMessage(RtmpMessageDetails {
+                rtmp_timestamp: _,
+                stream_id: id,
+                message: RtmpMessage::Amf0Command { ref command_name, ref additional_arguments, .. }
+            }) if id == stream_id &&
+                    command_name == "onStatus" &&
+                    additional_arguments[0].get("code").and_then(|x| x.as_str()) == Some("NetStream.Seek.Notify")
+        );
+    }
+
+    #[test]
+    fn seek_request_rejected() {
+        let mut processor = RtmpProcessor::new(get_default_config());
+        connect(&mut processor, "live");
+        let stream_id = start_playing(&mut processor, "key");
+
+        let result = processor.handle(vec![utils::create_seek_command(stream_id, 1500.0, 5.0)]).unwrap();
+        let request_id = get_request_id(&result);
+
+        let reject_result = processor.reject_request(request_id).unwrap();
+        assert_vec_match!(reject_result,
+            ProcessorResult::ResponseMessage(RtmpMessageDetails {
+                rtmp_timestamp: _,
+                stream_id: id,
+                message: RtmpMessage::Amf0Command { ref command_name, transaction_id: 5.0, .. }
+            }) if id == stream_id && command_name == "_error"
+        );
+    }
+
+    fn connect(processor: &mut RtmpProcessor, app: &str) {
+        let result = processor.handle(vec![utils::create_connect_command(app.to_string())]).unwrap();
+        let request_id = get_request_id(&result);
+        processor.accept_request(request_id).unwrap();
+    }
+
+    fn create_stream(processor: &mut RtmpProcessor) -> u32 {
+        let result = processor.handle(vec![utils::create_create_stream_command(4.0)]).unwrap();
+        let stream_id;
+        assert_vec_match!(result,
+            ProcessorResult::ResponseMessage(RtmpMessageDetails {
+                rtmp_timestamp: _,
+                stream_id: 0,
+                message: RtmpMessage::Amf0Command { command_name: ref name, transaction_id: 4.0, additional_arguments: ref args, .. }
+            }) if name == "_result" => {stream_id = args[0].as_f64().unwrap() as u32}
         );
+
+        stream_id
+    }
+
+    fn start_publishing(processor: &mut RtmpProcessor, stream_key: &str) -> u32 {
+        let stream_id = create_stream(processor);
+        let result = processor.handle(vec![utils::create_publish_command(stream_id, stream_key.to_string(), "live")]).unwrap();
+        let request_id = get_request_id(&result);
+        processor.accept_request(request_id).unwrap();
+        stream_id
+    }
+
+    fn start_playing(processor: &mut RtmpProcessor, stream_key: &str) -> u32 {
+        let stream_id = create_stream(processor);
+        let result = processor.handle(vec![utils::create_play_command(stream_id, stream_key.to_string(), -2.0)]).unwrap();
+        let request_id = get_request_id(&result);
+        processor.accept_request(request_id).unwrap();
+        stream_id
+    }
+
+    fn get_request_id(results: &Vec<ProcessorResult>) -> u32 {
+        for result in results {
+            match *result {
+                ProcessorResult::RaisedEvent(ProcessorEvent::ConnectionRequested { request_id, .. }) => return request_id,
+                ProcessorResult::RaisedEvent(ProcessorEvent::ReleaseStreamRequested { request_id, .. }) => return request_id,
+                ProcessorResult::RaisedEvent(ProcessorEvent::PublishStreamRequested { request_id, .. }) => return request_id,
+                ProcessorResult::RaisedEvent(ProcessorEvent::PlayStreamRequested { request_id, .. }) => return request_id,
+                ProcessorResult::RaisedEvent(ProcessorEvent::PlaybackSeekRequested { request_id, .. }) => return request_id,
+                _ => ()
+            }
+        }
+
+        panic!("No request event was raised: {:?}", results);
     }
 
     fn get_default_config() -> RtmpProcessorConfig {
@@ -350,6 +2436,8 @@ mod tests {
             version: "version".to_string(),
             peer_bandwidth: 50000,
             window_ack_size: 50000,
+            max_unanswered_pings: 3,
+            max_streams: 10,
         }
     }
 }
\ No newline at end of file
@@ -1,13 +1,59 @@
-// use metadata::StreamMetadata;
-
-// pub enum StreamState {
-//     Created,
-//     PublishRequested,
-//     PublishStarted
-// }
-
-// pub struct Stream {
-//     pub current_state: StreamState,
-//     pub stream_key: Option<String>,
-//     pub metadata: Option<StreamMetadata>,
-// }
\ No newline at end of file
+use metadata::StreamMetadata;
+
+/// How the publisher wants its stream handled, from the publishing type
+/// argument of the `publish` command
+#[derive(PartialEq, Debug, Clone, Copy)]
+pub enum PublishMode {
+    /// Live data that is not recorded
+    Live,
+
+    /// Recorded to a new file, replacing any existing recording
+    Record,
+
+    /// Appended to an existing recording, or recorded to a new file if none exists
+    Append
+}
+
+impl PublishMode {
+    pub fn from_name(name: &str) -> Option<PublishMode> {
+        match name {
+            "live" => Some(PublishMode::Live),
+            "record" => Some(PublishMode::Record),
+            "append" => Some(PublishMode::Append),
+            _ => None
+        }
+    }
+}
+
+#[derive(PartialEq, Debug)]
+pub enum StreamState {
+    Created,
+    PublishRequested,
+    PublishStarted,
+    PlayRequested,
+    PlayStarted
+}
+
+pub struct Stream {
+    pub current_state: StreamState,
+    pub stream_key: Option<String>,
+    pub publish_mode: Option<PublishMode>,
+    pub metadata: Option<StreamMetadata>,
+    pub is_paused: bool,
+    pub receive_audio: bool,
+    pub receive_video: bool,
+}
+
+impl Stream {
+    pub fn new() -> Self {
+        Stream {
+            current_state: StreamState::Created,
+            stream_key: None,
+            publish_mode: None,
+            metadata: None,
+            is_paused: false,
+            receive_audio: true,
+            receive_video: true,
+        }
+    }
+}
@@ -1,6 +1,5 @@
-use std::collections::HashMap;
 use amf0::Amf0Value;
-use rtmp_message::{RtmpMessage, RtmpMessageDetails};
+use rtmp_message::{RtmpMessage, RtmpMessageDetails, PeerBandwidthLimitType, UserControlEventType};
 use rtmp_time::RtmpTimestamp;
 
 
@@ -20,13 +19,43 @@ pub fn create_window_ack_message(size: u32) -> RtmpMessageDetails {
     }
 }
 
+pub fn create_acknowledgement_message(sequence_number: u32) -> RtmpMessageDetails {
+    RtmpMessageDetails {
+        rtmp_timestamp: RtmpTimestamp::new(0),
+        stream_id: 0,
+        message: RtmpMessage::Acknowledgement { sequence_number: sequence_number }
+    }
+}
+
+pub fn create_peer_bandwidth_message(size: u32, limit_type: PeerBandwidthLimitType) -> RtmpMessageDetails {
+    RtmpMessageDetails {
+        rtmp_timestamp: RtmpTimestamp::new(0),
+        stream_id: 0,
+        message: RtmpMessage::SetPeerBandwidth { size: size, limit_type: limit_type }
+    }
+}
+
+pub fn create_ping_message(event_type: UserControlEventType, timestamp: u32) -> RtmpMessageDetails {
+    RtmpMessageDetails {
+        rtmp_timestamp: RtmpTimestamp::new(0),
+        stream_id: 0,
+        message: RtmpMessage::UserControl {
+            event_type: event_type,
+            stream_id: None,
+            buffer_length: None,
+            timestamp: Some(RtmpTimestamp::new(timestamp))
+        }
+    }
+}
+
 pub fn create_connect_command(app: String) -> RtmpMessageDetails {
-    let mut properties = HashMap::new();
-    properties.insert("app".to_string(), Amf0Value::Utf8String(app));
-    properties.insert("type".to_string(), Amf0Value::Utf8String("nonprivate".to_string()));
-    properties.insert("flashVer".to_string(), Amf0Value::Utf8String("FMLE/3.0 (compatible; FMSc/1.0)".to_string()));
-    properties.insert("swfUrl".to_string(), Amf0Value::Utf8String("rtmp://127.0.0.1/live".to_string()));
-    properties.insert("tcUrl".to_string(), Amf0Value::Utf8String("rtmp://127.0.0.1/live".to_string()));
+    let command_object = amf0_object!{
+        "app" => app,
+        "type" => "nonprivate",
+        "flashVer" => "FMLE/3.0 (compatible; FMSc/1.0)",
+        "swfUrl" => "rtmp://127.0.0.1/live",
+        "tcUrl" => "rtmp://127.0.0.1/live",
+    };
 
     RtmpMessageDetails {
         rtmp_timestamp: RtmpTimestamp::new(0),
@@ -34,54 +63,203 @@ pub fn create_connect_command(app: String) -> RtmpMessageDetails {
         message: RtmpMessage::Amf0Command {
             command_name: "connect".to_string(),
             transaction_id: 1.0,
-            command_object: Amf0Value::Object(properties),
+            command_object: command_object,
+            additional_arguments: vec![]
+        }
+    }
+}
+
+pub fn create_release_stream_command(stream_key: String, transaction_id: f64) -> RtmpMessageDetails {
+    RtmpMessageDetails {
+        rtmp_timestamp: RtmpTimestamp::new(0),
+        stream_id: 0,
+        message: RtmpMessage::Amf0Command {
+            command_name: "releaseStream".to_string(),
+            transaction_id: transaction_id,
+            command_object: Amf0Value::Null,
+            additional_arguments: vec![Amf0Value::Utf8String(stream_key)]
+        }
+    }
+}
+
+pub fn create_fc_publish_command(stream_key: String, transaction_id: f64) -> RtmpMessageDetails {
+    RtmpMessageDetails {
+        rtmp_timestamp: RtmpTimestamp::new(0),
+        stream_id: 0,
+        message: RtmpMessage::Amf0Command {
+            command_name: "FCPublish".to_string(),
+            transaction_id: transaction_id,
+            command_object: Amf0Value::Null,
+            additional_arguments: vec![Amf0Value::Utf8String(stream_key)]
+        }
+    }
+}
+
+pub fn create_fc_unpublish_command(stream_key: String, transaction_id: f64) -> RtmpMessageDetails {
+    RtmpMessageDetails {
+        rtmp_timestamp: RtmpTimestamp::new(0),
+        stream_id: 0,
+        message: RtmpMessage::Amf0Command {
+            command_name: "FCUnpublish".to_string(),
+            transaction_id: transaction_id,
+            command_object: Amf0Value::Null,
+            additional_arguments: vec![Amf0Value::Utf8String(stream_key)]
+        }
+    }
+}
+
+pub fn create_create_stream_command(transaction_id: f64) -> RtmpMessageDetails {
+    RtmpMessageDetails {
+        rtmp_timestamp: RtmpTimestamp::new(0),
+        stream_id: 0,
+        message: RtmpMessage::Amf0Command {
+            command_name: "createStream".to_string(),
+            transaction_id: transaction_id,
+            command_object: Amf0Value::Null,
             additional_arguments: vec![]
         }
     }
 }
 
-// pub fn create_publish_command(stream_key: String, transaction_id: f64) -> RtmpMessageDetails {
-//     RtmpMessageDetails {
-//         rtmp_timestamp: RtmpTimestamp::new(0),
-//         stream_id: 0,
-//         message: RtmpMessage::Amf0Command {
-//             command_name: "publish".to_string(),
-//             transaction_id: transaction_id,
-//             command_object: Amf0Value::Null,
-//             additional_arguments: vec![
-//                 Amf0Value::Utf8String(stream_key),
-//                 Amf0Value::Utf8String("live".to_string()),
-//             ]
-//         }
-//     }
-// }
-
-// pub fn create_createStream_command(transaction_id: f64) -> RtmpMessageDetails {
-//     RtmpMessageDetails {
-//         rtmp_timestamp: RtmpTimestamp::new(0),
-//         stream_id: 0,
-//         message: RtmpMessage::Amf0Command {
-//             command_name: "createStream".to_string(),
-//             transaction_id: transaction_id,
-//             command_object: Amf0Value::Null,
-//             additional_arguments: vec![
-//             ]
-//         }
-//     }
-// }
-
-// pub fn create_audio_data_message(data: Vec<u8>) -> RtmpMessageDetails {
-//     RtmpMessageDetails {
-//         rtmp_timestamp: RtmpTimestamp::new(0),
-//         stream_id: 0,
-//         message: RtmpMessage::AudioData { data: data }
-//     }
-// }
-
-// pub fn create_video_data_message(data: Vec<u8>) -> RtmpMessageDetails {
-//     RtmpMessageDetails {
-//         rtmp_timestamp: RtmpTimestamp::new(0),
-//         stream_id: 0,
-//         message: RtmpMessage::VideoData { data: data }
-//     }
-// }
\ No newline at end of file
+pub fn create_close_stream_command(stream_id: u32) -> RtmpMessageDetails {
+    RtmpMessageDetails {
+        rtmp_timestamp: RtmpTimestamp::new(0),
+        stream_id: stream_id,
+        message: RtmpMessage::Amf0Command {
+            command_name: "closeStream".to_string(),
+            transaction_id: 0.0,
+            command_object: Amf0Value::Null,
+            additional_arguments: vec![]
+        }
+    }
+}
+
+pub fn create_delete_stream_command(stream_id: u32) -> RtmpMessageDetails {
+    RtmpMessageDetails {
+        rtmp_timestamp: RtmpTimestamp::new(0),
+        stream_id: 0,
+        message: RtmpMessage::Amf0Command {
+            command_name: "deleteStream".to_string(),
+            transaction_id: 0.0,
+            command_object: Amf0Value::Null,
+            additional_arguments: vec![Amf0Value::Number(stream_id as f64)]
+        }
+    }
+}
+
+pub fn create_publish_command(stream_id: u32, stream_key: String, mode: &str) -> RtmpMessageDetails {
+    RtmpMessageDetails {
+        rtmp_timestamp: RtmpTimestamp::new(0),
+        stream_id: stream_id,
+        message: RtmpMessage::Amf0Command {
+            command_name: "publish".to_string(),
+            transaction_id: 0.0,
+            command_object: Amf0Value::Null,
+            additional_arguments: vec![
+                Amf0Value::Utf8String(stream_key),
+                Amf0Value::Utf8String(mode.to_string()),
+            ]
+        }
+    }
+}
+
+pub fn create_metadata_message(stream_id: u32, metadata: Amf0Value) -> RtmpMessageDetails {
+    RtmpMessageDetails {
+        rtmp_timestamp: RtmpTimestamp::new(0),
+        stream_id: stream_id,
+        message: RtmpMessage::Amf0Data {
+            values: vec![
+                Amf0Value::Utf8String("@setDataFrame".to_string()),
+                Amf0Value::Utf8String("onMetaData".to_string()),
+                metadata,
+            ]
+        }
+    }
+}
+
+pub fn create_audio_data_message(stream_id: u32, timestamp: u32, data: Vec<u8>) -> RtmpMessageDetails {
+    RtmpMessageDetails {
+        rtmp_timestamp: RtmpTimestamp::new(timestamp),
+        stream_id: stream_id,
+        message: RtmpMessage::AudioData { data: data }
+    }
+}
+
+pub fn create_video_data_message(stream_id: u32, timestamp: u32, data: Vec<u8>) -> RtmpMessageDetails {
+    RtmpMessageDetails {
+        rtmp_timestamp: RtmpTimestamp::new(timestamp),
+        stream_id: stream_id,
+        message: RtmpMessage::VideoData { data: data }
+    }
+}
+
+pub fn create_play_command(stream_id: u32, stream_key: String, start: f64) -> RtmpMessageDetails {
+    RtmpMessageDetails {
+        rtmp_timestamp: RtmpTimestamp::new(0),
+        stream_id: stream_id,
+        message: RtmpMessage::Amf0Command {
+            command_name: "play".to_string(),
+            transaction_id: 0.0,
+            command_object: Amf0Value::Null,
+            additional_arguments: vec![
+                Amf0Value::Utf8String(stream_key),
+                Amf0Value::Number(start),
+            ]
+        }
+    }
+}
+
+pub fn create_play2_command(stream_id: u32, parameters: Amf0Value) -> RtmpMessageDetails {
+    RtmpMessageDetails {
+        rtmp_timestamp: RtmpTimestamp::new(0),
+        stream_id: stream_id,
+        message: RtmpMessage::Amf0Command {
+            command_name: "play2".to_string(),
+            transaction_id: 0.0,
+            command_object: Amf0Value::Null,
+            additional_arguments: vec![parameters]
+        }
+    }
+}
+
+pub fn create_seek_command(stream_id: u32, milliseconds: f64, transaction_id: f64) -> RtmpMessageDetails {
+    RtmpMessageDetails {
+        rtmp_timestamp: RtmpTimestamp::new(0),
+        stream_id: stream_id,
+        message: RtmpMessage::Amf0Command {
+            command_name: "seek".to_string(),
+            transaction_id: transaction_id,
+            command_object: Amf0Value::Null,
+            additional_arguments: vec![Amf0Value::Number(milliseconds)]
+        }
+    }
+}
+
+pub fn create_pause_command(stream_id: u32, is_paused: bool, milliseconds: f64) -> RtmpMessageDetails {
+    RtmpMessageDetails {
+        rtmp_timestamp: RtmpTimestamp::new(0),
+        stream_id: stream_id,
+        message: RtmpMessage::Amf0Command {
+            command_name: "pause".to_string(),
+            transaction_id: 0.0,
+            command_object: Amf0Value::Null,
+            additional_arguments: vec![
+                Amf0Value::Boolean(is_paused),
+                Amf0Value::Number(milliseconds),
+            ]
+        }
+    }
+}
+
+pub fn create_receive_media_command(stream_id: u32, command_name: &str, should_receive: bool) -> RtmpMessageDetails {
+    RtmpMessageDetails {
+        rtmp_timestamp: RtmpTimestamp::new(0),
+        stream_id: stream_id,
+        message: RtmpMessage::Amf0Command {
+            command_name: command_name.to_string(),
+            transaction_id: 0.0,
+            command_object: Amf0Value::Null,
+            additional_arguments: vec![Amf0Value::Boolean(should_receive)]
+        }
+    }
+}